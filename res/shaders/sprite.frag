@@ -0,0 +1,14 @@
+#version 330
+
+in FS_IN {
+    smooth vec2 uv;
+    flat vec4 color;
+} fs_in;
+
+uniform sampler2D sprite_tex;
+
+out vec4 fs_out;
+
+void main() {
+    fs_out = fs_in.color * texture(sprite_tex, fs_in.uv);
+}
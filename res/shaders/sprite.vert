@@ -0,0 +1,23 @@
+#version 330
+layout(location = 0) in vec4 bound;
+layout(location = 1) in vec4 uv_bound;
+layout(location = 2) in vec4 color;
+layout(location = 3) in vec2 pivot;
+layout(location = 4) in float rotation;
+
+out GS_IN {
+    flat vec4 bound;
+    flat vec4 uv_bound;
+    flat vec4 color;
+    flat vec2 pivot;
+    flat float rotation;
+} vs_out;
+
+void main() {
+    gl_Position = vec4(0, 0, 0, 1);
+    vs_out.bound = bound;
+    vs_out.uv_bound = uv_bound;
+    vs_out.color = color;
+    vs_out.pivot = pivot;
+    vs_out.rotation = rotation;
+}
@@ -0,0 +1,567 @@
+use std::env;
+use std::ffi::CString;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::Rc;
+
+use music::MusicPlayer;
+use libc::{self, c_char, c_int, c_long, c_uint, c_ulong, c_void};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Voices playing at once. The oldest is cut off to make room for a new one.
+const MAX_VOICES: usize = 16;
+/// Longest stretch rendered in one update, so a stall does not queue up
+/// seconds of sound.
+const MAX_UPDATE_TIME: f32 = 0.1;
+/// Combos past this all sound the same.
+const MAX_COMBO_PITCH: i32 = 12;
+/// Effects in here replace the synthesized ones, by `Sound::name`.
+const SOUNDS_DIR: &str = "res/sounds";
+
+// From alsa/pcm.h.
+const SND_PCM_STREAM_PLAYBACK: c_int = 0;
+const SND_PCM_NONBLOCK: c_int = 1;
+const SND_PCM_FORMAT_S16_LE: c_int = 2;
+const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+const ALSA_LATENCY_US: c_uint = 60_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    PaddleHit,
+    /// A block took a hit but is still standing.
+    BlockHit,
+    /// A block broke, on the given combo.
+    BlockBreak(i32),
+    WallBounce,
+    BallLost,
+    LevelClear,
+}
+
+pub const SOUNDS: &[Sound] = &[
+    Sound::PaddleHit,
+    Sound::BlockHit,
+    Sound::BlockBreak(1),
+    Sound::WallBounce,
+    Sound::BallLost,
+    Sound::LevelClear,
+];
+
+#[derive(Clone, Copy)]
+enum Wave {
+    Sine,
+    Square,
+    Triangle,
+}
+
+struct Voice {
+    samples: Rc<Vec<f32>>,
+    pos: usize,
+}
+
+/// Mixes effects into a mono buffer.
+pub struct Mixer {
+    voices: Vec<Voice>,
+    volume: f32,
+    /// Effects rendered so far, so each is only synthesized or loaded once.
+    bank: Vec<(Sound, Rc<Vec<f32>>)>,
+}
+
+/// Where mixed samples go.
+pub trait Sink {
+    fn write(&mut self, samples: &[f32]);
+}
+
+/// Drops everything, for when there is no sound device.
+pub struct NullSink;
+
+/// Keeps everything and writes it to a WAV file when dropped, to listen to
+/// a session afterwards.
+pub struct WavSink {
+    path: PathBuf,
+    samples: Vec<f32>,
+}
+
+type PcmOpen = unsafe extern "C" fn(*mut *mut c_void, *const c_char, c_int, c_int) -> c_int;
+type PcmSetParams = unsafe extern "C" fn(*mut c_void, c_int, c_int, c_uint, c_uint, c_int, c_uint) -> c_int;
+type PcmWritei = unsafe extern "C" fn(*mut c_void, *const c_void, c_ulong) -> c_long;
+type PcmRecover = unsafe extern "C" fn(*mut c_void, c_int, c_int) -> c_int;
+type PcmClose = unsafe extern "C" fn(*mut c_void) -> c_int;
+
+/// The default ALSA device. libasound is loaded at run time so the game
+/// still starts on machines without it.
+pub struct AlsaSink {
+    lib: *mut c_void,
+    pcm: *mut c_void,
+    writei: PcmWritei,
+    recover: PcmRecover,
+    close: PcmClose,
+}
+
+/// Effects and music playing into a sink as the game runs.
+pub struct Audio {
+    pub mixer: Mixer,
+    pub music: MusicPlayer,
+    sink: Box<dyn Sink>,
+    /// Part of a sample left over from the last update.
+    carry: f32,
+    buffer: Vec<f32>,
+}
+
+impl Sound {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Sound::PaddleHit => "paddle_hit",
+            Sound::BlockHit => "block_hit",
+            Sound::BlockBreak(_) => "block_break",
+            Sound::WallBounce => "wall_bounce",
+            Sound::BallLost => "ball_lost",
+            Sound::LevelClear => "level_clear",
+        }
+    }
+
+    /// Whether music ducks under it while it plays.
+    fn ducks_music(&self) -> bool {
+        match *self {
+            Sound::BallLost | Sound::LevelClear => true,
+            _ => false,
+        }
+    }
+
+    /// Same sound for every combo past the top pitch.
+    fn key(&self) -> Sound {
+        match *self {
+            Sound::BlockBreak(combo) => Sound::BlockBreak(combo.max(1).min(MAX_COMBO_PITCH)),
+            sound => sound,
+        }
+    }
+
+    /// Pitch ratio, a semitone up for each block in the combo.
+    fn pitch(&self) -> f32 {
+        match *self {
+            Sound::BlockBreak(combo) => 2f32.powf((combo.max(1).min(MAX_COMBO_PITCH) - 1) as f32 / 12.),
+            _ => 1.,
+        }
+    }
+}
+
+/// A note gliding from `f0` to `f1` Hz, with a quick attack and an
+/// exponential decay.
+fn tone(wave: Wave, f0: f32, f1: f32, duration: f32, gain: f32) -> Vec<f32> {
+    let n = (duration * SAMPLE_RATE as f32) as usize;
+    let attack = 0.005 * SAMPLE_RATE as f32;
+    let mut phase = 0f32;
+    (0..n)
+        .map(|i| {
+            let t = i as f32 / n as f32;
+            phase = (phase + (f0 + (f1 - f0) * t) / SAMPLE_RATE as f32) % 1.;
+            let value = match wave {
+                Wave::Sine => (2. * PI * phase).sin(),
+                Wave::Square => if phase < 0.5 { 0.6 } else { -0.6 },
+                Wave::Triangle => 4. * (phase - 0.5).abs() - 1.,
+            };
+            let envelope = (i as f32 / attack).min(1.) * (-5. * t).exp();
+            gain * envelope * value
+        })
+        .collect()
+}
+
+fn synthesize(sound: Sound) -> Vec<f32> {
+    match sound {
+        Sound::PaddleHit => tone(Wave::Square, 220., 180., 0.08, 0.4),
+        Sound::BlockHit => tone(Wave::Triangle, 330., 300., 0.06, 0.4),
+        Sound::BlockBreak(_) => tone(Wave::Sine, 523.25 * sound.pitch(), 587.33 * sound.pitch(), 0.12, 0.5),
+        Sound::WallBounce => tone(Wave::Triangle, 440., 440., 0.04, 0.25),
+        Sound::BallLost => tone(Wave::Square, 440., 110., 0.5, 0.4),
+        Sound::LevelClear => {
+            let mut samples = Vec::new();
+            for &f in [523.25, 659.25, 783.99, 1046.5].iter() {
+                samples.extend(tone(Wave::Sine, f, f, 0.12, 0.5));
+            }
+            samples
+        },
+    }
+}
+
+/// Linear resampling, to play a sample at `ratio` times its pitch.
+pub fn resample(samples: &[f32], ratio: f32) -> Vec<f32> {
+    let n = (samples.len() as f32 / ratio) as usize;
+    (0..n)
+        .map(|i| {
+            let x = i as f32 * ratio;
+            let (i0, t) = (x as usize, x.fract());
+            let a = samples[i0.min(samples.len() - 1)];
+            let b = samples[(i0 + 1).min(samples.len() - 1)];
+            a + (b - a) * t
+        })
+        .collect()
+}
+
+/// A 16 bit mono PCM WAV file of `samples`.
+pub fn write_wav<W: Write>(mut writer: W, samples: &[f32]) -> io::Result<()> {
+    let data_len = samples.len() as u32 * 2;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for &sample in samples {
+        writer.write_all(&to_i16(sample).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Reads a 16 bit PCM WAV file as mono samples at `SAMPLE_RATE`.
+pub fn read_wav<P: AsRef<Path>>(path: P) -> io::Result<Vec<f32>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid("not a WAV file"));
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let mut format = None;
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+        let (id, len) = (&bytes[at..at + 4], u32_at(at + 4) as usize);
+        let body = at + 8;
+        if body + len > bytes.len() {
+            return Err(invalid("truncated chunk"));
+        }
+        if id == b"fmt " && len >= 16 {
+            format = Some((u16_at(body), u16_at(body + 2) as usize, u32_at(body + 4), u16_at(body + 14)));
+        } else if id == b"data" {
+            let (tag, channels, rate, bits) = format.ok_or_else(|| invalid("data before fmt"))?;
+            if tag != 1 || bits != 16 || channels == 0 {
+                return Err(invalid("only 16 bit PCM is supported"));
+            }
+            if rate == 0 {
+                return Err(invalid("sample rate is 0"));
+            }
+            let samples: Vec<f32> = bytes[body..body + len]
+                .chunks(2 * channels)
+                .filter(|frame| frame.len() == 2 * channels)
+                .map(|frame| {
+                    let sum: f32 = frame.chunks(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f32).sum();
+                    sum / channels as f32 / 32768.
+                })
+                .collect();
+            if samples.is_empty() || rate == SAMPLE_RATE {
+                return Ok(samples);
+            }
+            return Ok(resample(&samples, rate as f32 / SAMPLE_RATE as f32));
+        }
+        at = body + len + len % 2;
+    }
+    Err(invalid("no data chunk"))
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.max(-1.).min(1.) * i16::max_value() as f32) as i16
+}
+
+impl Mixer {
+    pub fn new(volume: f32) -> Self {
+        Mixer { voices: Vec::new(), volume, bank: Vec::new() }
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    fn samples(&mut self, sound: Sound) -> Rc<Vec<f32>> {
+        let key = sound.key();
+        if let Some(&(_, ref samples)) = self.bank.iter().find(|&&(s, _)| s == key) {
+            return samples.clone();
+        }
+        let path = Path::new(SOUNDS_DIR).join(format!("{}.wav", key.name()));
+        let samples = match read_wav(&path) {
+            Ok(ref samples) if key.pitch() != 1. => resample(samples, key.pitch()),
+            Ok(samples) => samples,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => synthesize(key),
+            Err(err) => {
+                eprintln!("Warning: cannot load {}: {}", path.display(), err);
+                synthesize(key)
+            },
+        };
+        let samples = Rc::new(samples);
+        self.bank.push((key, samples.clone()));
+        samples
+    }
+
+    /// Loads or synthesizes every effect up front, so the first of each does
+    /// not hold up a frame.
+    pub fn preload(&mut self) {
+        for &sound in SOUNDS.iter() {
+            self.samples(sound);
+        }
+        for combo in 2..MAX_COMBO_PITCH + 1 {
+            self.samples(Sound::BlockBreak(combo));
+        }
+    }
+
+    /// Starts `sound` and returns its length in samples.
+    pub fn play(&mut self, sound: Sound) -> usize {
+        let samples = self.samples(sound);
+        if self.voices.len() >= MAX_VOICES {
+            self.voices.remove(0);
+        }
+        let len = samples.len();
+        self.voices.push(Voice { samples, pos: 0 });
+        len
+    }
+
+    /// Fills `out` with the next samples of everything playing.
+    pub fn render(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = 0.;
+        }
+        for voice in self.voices.iter_mut() {
+            let n = (voice.samples.len() - voice.pos).min(out.len());
+            for (sample, &value) in out.iter_mut().zip(voice.samples[voice.pos..voice.pos + n].iter()) {
+                *sample += value;
+            }
+            voice.pos += n;
+        }
+        self.voices.retain(|voice| voice.pos < voice.samples.len());
+        for sample in out.iter_mut() {
+            *sample = (*sample * self.volume).max(-1.).min(1.);
+        }
+    }
+}
+
+impl Sink for NullSink {
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+impl WavSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        WavSink { path: path.as_ref().to_path_buf(), samples: Vec::new() }
+    }
+}
+
+impl Sink for WavSink {
+    fn write(&mut self, samples: &[f32]) {
+        self.samples.extend_from_slice(samples);
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        let result = File::create(&self.path).and_then(|file| write_wav(BufWriter::new(file), &self.samples));
+        if let Err(err) = result {
+            eprintln!("Warning: cannot write {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+/// Looks up a function in a library from `dlopen`. `T` has to be the
+/// function's pointer type.
+pub unsafe fn symbol<T: Copy>(lib: *mut c_void, name: &str) -> Result<T, String> {
+    let c_name = CString::new(name).expect("Symbol names have no NUL");
+    let address = libc::dlsym(lib, c_name.as_ptr());
+    if address.is_null() {
+        return Err(format!("missing {}", name));
+    }
+    Ok(*(&address as *const *mut c_void as *const T))
+}
+
+impl AlsaSink {
+    pub fn open() -> Result<AlsaSink, String> {
+        unsafe {
+            let lib_name = CString::new("libasound.so.2").expect("No NUL");
+            let lib = libc::dlopen(lib_name.as_ptr(), libc::RTLD_NOW);
+            if lib.is_null() {
+                return Err("libasound is not installed".into());
+            }
+            let sink = AlsaSink::open_device(lib);
+            if sink.is_err() {
+                libc::dlclose(lib);
+            }
+            sink
+        }
+    }
+
+    unsafe fn open_device(lib: *mut c_void) -> Result<AlsaSink, String> {
+        let open: PcmOpen = symbol(lib, "snd_pcm_open")?;
+        let set_params: PcmSetParams = symbol(lib, "snd_pcm_set_params")?;
+        let close: PcmClose = symbol(lib, "snd_pcm_close")?;
+        let writei: PcmWritei = symbol(lib, "snd_pcm_writei")?;
+        let recover: PcmRecover = symbol(lib, "snd_pcm_recover")?;
+        let mut pcm = ptr::null_mut();
+        let device = CString::new("default").expect("No NUL");
+        if open(&mut pcm, device.as_ptr(), SND_PCM_STREAM_PLAYBACK, SND_PCM_NONBLOCK) < 0 {
+            return Err("no sound device".into());
+        }
+        let err = set_params(
+            pcm, SND_PCM_FORMAT_S16_LE, SND_PCM_ACCESS_RW_INTERLEAVED, 1, SAMPLE_RATE, 1, ALSA_LATENCY_US,
+        );
+        if err < 0 {
+            close(pcm);
+            return Err(format!("cannot set up the sound device (error {})", err));
+        }
+        Ok(AlsaSink { lib, pcm, writei, recover, close })
+    }
+}
+
+impl Sink for AlsaSink {
+    fn write(&mut self, samples: &[f32]) {
+        let frames: Vec<i16> = samples.iter().map(|&sample| to_i16(sample)).collect();
+        let mut written = 0;
+        while written < frames.len() {
+            let rest = &frames[written..];
+            let n = unsafe { (self.writei)(self.pcm, rest.as_ptr() as *const c_void, rest.len() as c_ulong) };
+            if n >= 0 {
+                written += n as usize;
+                continue;
+            }
+            // A full buffer drops the rest, anything else (like an underrun)
+            // gets one go at recovering.
+            if n == -(libc::EAGAIN as c_long) || unsafe { (self.recover)(self.pcm, n as c_int, 1) } < 0 {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for AlsaSink {
+    fn drop(&mut self) {
+        unsafe {
+            (self.close)(self.pcm);
+            libc::dlclose(self.lib);
+        }
+    }
+}
+
+impl Audio {
+    /// Plays through ALSA, or nowhere if there is no device or
+    /// `BREAKOUT_AUDIO=null` is set. `BREAKOUT_AUDIO=<file>.wav` records to
+    /// that file instead, written when the game quits.
+    pub fn open(volume: f32, music_volume: f32) -> Self {
+        let target = env::var("BREAKOUT_AUDIO").unwrap_or_default();
+        let sink: Box<dyn Sink> = if target == "null" {
+            Box::new(NullSink)
+        } else if target.ends_with(".wav") {
+            Box::new(WavSink::new(target))
+        } else {
+            match AlsaSink::open() {
+                Ok(sink) => Box::new(sink),
+                Err(err) => {
+                    eprintln!("Warning: no sound, {}", err);
+                    Box::new(NullSink)
+                },
+            }
+        };
+        let mut mixer = Mixer::new(volume);
+        mixer.preload();
+        Audio { mixer, music: MusicPlayer::new(music_volume), sink, carry: 0., buffer: Vec::new() }
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        let len = self.mixer.play(sound);
+        if sound.ducks_music() {
+            self.music.duck(len);
+        }
+    }
+
+    /// Mixes `dt` seconds of sound and sends it to the sink.
+    pub fn update(&mut self, dt: f32) {
+        let wanted = dt.min(MAX_UPDATE_TIME) * SAMPLE_RATE as f32 + self.carry;
+        let n = wanted as usize;
+        self.carry = wanted - n as f32;
+        self.buffer.resize(n, 0.);
+        self.mixer.render(&mut self.buffer);
+        self.music.render(&mut self.buffer);
+        self.sink.write(&self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("breakout-{}-{}", process::id(), name))
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0f32, |peak, &sample| peak.max(sample.abs()))
+    }
+
+    fn zero_crossings(samples: &[f32]) -> usize {
+        samples.windows(2).filter(|pair| (pair[0] < 0.) != (pair[1] < 0.)).count()
+    }
+
+    #[test]
+    fn mixer_plays_effects() {
+        let mut mixer = Mixer::new(1.);
+        let mut out = vec![0.; 1024];
+        mixer.render(&mut out);
+        assert_eq!(peak(&out), 0.);
+        mixer.play(Sound::PaddleHit);
+        mixer.render(&mut out);
+        assert!(peak(&out) > 0.1, "peak {}", peak(&out));
+    }
+
+    #[test]
+    fn mixer_clips_and_caps_voices() {
+        let mut mixer = Mixer::new(1.);
+        for _ in 0..MAX_VOICES + 4 {
+            mixer.play(Sound::LevelClear);
+        }
+        assert_eq!(mixer.voices.len(), MAX_VOICES);
+        let mut out = vec![0.; 4096];
+        mixer.render(&mut out);
+        assert_eq!(peak(&out), 1.);
+    }
+
+    #[test]
+    fn block_break_rises_with_combo() {
+        for combo in 1..MAX_COMBO_PITCH {
+            assert!(Sound::BlockBreak(combo + 1).pitch() > Sound::BlockBreak(combo).pitch());
+        }
+        assert_eq!(Sound::BlockBreak(MAX_COMBO_PITCH + 5).pitch(), Sound::BlockBreak(MAX_COMBO_PITCH).pitch());
+        let low = zero_crossings(&synthesize(Sound::BlockBreak(1)));
+        let high = zero_crossings(&synthesize(Sound::BlockBreak(8)));
+        assert!(high > low, "{} crossings at combo 8, {} at combo 1", high, low);
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        let samples = vec![0., 0.5, -0.5, 1., -1., 0.25];
+        let path = temp_path("round-trip.wav");
+        write_wav(File::create(&path).unwrap(), &samples).unwrap();
+        let read = read_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.len(), samples.len());
+        for (&a, &b) in read.iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-4, "{} read back as {}", b, a);
+        }
+    }
+
+    #[test]
+    fn wav_sink_writes_on_drop() {
+        let path = temp_path("sink.wav");
+        let samples = synthesize(Sound::WallBounce);
+        {
+            let mut sink = WavSink::new(&path);
+            sink.write(&samples[..100]);
+            sink.write(&samples[100..]);
+        }
+        let read = read_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.len(), samples.len());
+    }
+}
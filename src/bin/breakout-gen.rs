@@ -0,0 +1,113 @@
+//! Generates levels from seeds, as starting points for hand-made ones.
+//!
+//! Usage: breakout-gen [OPTIONS]
+//!
+//!   --pattern NAME     symmetric, noise or maze (default symmetric)
+//!   --seed N           first seed (default 1)
+//!   --count N          number of levels, one per seed (default 1)
+//!   --difficulty D     0..1, sets density and hit points (default 0.5)
+//!   --density D        share of cells with blocks, overrides difficulty
+//!   --hit-points N     toughest block, 1..9, overrides difficulty
+//!   --width W          grid columns (default 20)
+//!   --height H         grid rows (default 10)
+//!   --out DIR          write DIR/<pattern>-<seed>.level instead of printing
+//!
+//! Printing to standard output only works for a single level.
+
+// The game's modules are shared with this tool, which only uses part of them.
+#![allow(dead_code)]
+
+#[path = "../generator.rs"]
+mod generator;
+#[path = "../level.rs"]
+mod level;
+#[path = "../math.rs"]
+mod math;
+#[path = "../motion.rs"]
+mod motion;
+#[path = "../storage.rs"]
+mod storage;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+
+use generator::{generate, GeneratorParams, Pattern};
+
+const USAGE: &str = "Usage: breakout-gen [--pattern symmetric|noise|maze] [--seed N] [--count N] \
+                     [--difficulty D] [--density D] [--hit-points N] [--width W] [--height H] [--out DIR]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_value<T: FromStr>(option: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| fail(&format!("{} needs a value", option)));
+    value.parse().unwrap_or_else(|_| fail(&format!("invalid value '{}' for {}", value, option)))
+}
+
+fn main() {
+    let mut pattern = Pattern::Symmetric;
+    let mut seed = 1u64;
+    let mut count = 1u64;
+    let mut difficulty = 0.5f32;
+    let mut density = None;
+    let mut hit_points = None;
+    let mut w = None;
+    let mut h = None;
+    let mut out = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pattern" => {
+                let name: String = parse_value(&arg, args.next());
+                pattern = Pattern::parse(&name).unwrap_or_else(|| fail(&format!("unknown pattern '{}'", name)));
+            },
+            "--seed" => seed = parse_value(&arg, args.next()),
+            "--count" => count = parse_value(&arg, args.next()),
+            "--difficulty" => difficulty = parse_value(&arg, args.next()),
+            "--density" => density = Some(parse_value(&arg, args.next())),
+            "--hit-points" => hit_points = Some(parse_value(&arg, args.next())),
+            "--width" => w = Some(parse_value(&arg, args.next())),
+            "--height" => h = Some(parse_value(&arg, args.next())),
+            "--out" => out = Some(PathBuf::from(parse_value::<String>(&arg, args.next()))),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => fail(&format!("unknown argument {}", arg)),
+        }
+    }
+    if out.is_none() && count != 1 {
+        fail("--count needs --out");
+    }
+    if let Some(ref dir) = out {
+        if let Err(err) = fs::create_dir_all(dir) {
+            eprintln!("Error: cannot create {}: {}", dir.display(), err);
+            process::exit(1);
+        }
+    }
+
+    for seed in seed..seed + count {
+        let mut params = GeneratorParams::for_difficulty(pattern, seed, difficulty);
+        params.density = density.unwrap_or(params.density);
+        params.max_hit_points = hit_points.unwrap_or(params.max_hit_points);
+        params.w = w.unwrap_or(params.w);
+        params.h = h.unwrap_or(params.h);
+        let file = generate(&params);
+        match out {
+            Some(ref dir) => {
+                let path = dir.join(format!("{}-{}.level", pattern.name(), seed));
+                if let Err(err) = file.save(&path) {
+                    eprintln!("Error: cannot write {}: {}", path.display(), err);
+                    process::exit(1);
+                }
+                println!("{}", path.display());
+            },
+            None => print!("{}", file),
+        }
+    }
+}
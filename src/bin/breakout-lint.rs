@@ -0,0 +1,98 @@
+//! Checks levels without opening a window.
+//!
+//! Usage: breakout-lint [--json] [--strict] [PATH...]
+//!
+//! Each path is a level file or a directory of them, `res/levels` by default.
+//! Exits with 0 when everything passes, 1 when a level has errors (or
+//! warnings, with `--strict`) and 2 when the arguments are wrong.
+
+// The game's modules are shared with this tool, which only uses part of them.
+#![allow(dead_code)]
+
+#[path = "../level.rs"]
+mod level;
+#[path = "../lint.rs"]
+mod lint;
+#[path = "../math.rs"]
+mod math;
+#[path = "../motion.rs"]
+mod motion;
+#[path = "../storage.rs"]
+mod storage;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use lint::{exit_code, lint_file, Report, Severity};
+
+const USAGE: &str = "Usage: breakout-lint [--json] [--strict] [PATH...]";
+
+/// Expands directories to the `.level` files in them, sorted by name.
+fn level_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut levels = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            levels.push(path.clone());
+            continue;
+        }
+        let entries = fs::read_dir(path).map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+        let mut found: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "level"))
+            .collect();
+        found.sort();
+        levels.extend(found);
+    }
+    Ok(levels)
+}
+
+fn main() {
+    let mut is_json = false;
+    let mut is_strict = false;
+    let mut paths = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => is_json = true,
+            "--strict" => is_strict = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                process::exit(2);
+            },
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("res/levels"));
+    }
+    let levels = match level_paths(&paths) {
+        Ok(levels) => levels,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(2);
+        },
+    };
+
+    let reports: Vec<Report> = levels.iter().map(lint_file).collect();
+    let errors: usize = reports.iter().map(|report| report.count(Severity::Error)).sum();
+    let warnings: usize = reports.iter().map(|report| report.count(Severity::Warning)).sum();
+    if is_json {
+        let levels = reports.iter().map(Report::to_json).collect::<Vec<_>>();
+        println!("{{\"levels\":[{}],\"errors\":{},\"warnings\":{}}}", levels.join(","), errors, warnings);
+    } else {
+        for report in reports.iter() {
+            print!("{}", report);
+        }
+        println!("{} level(s), {} error(s), {} warning(s)", reports.len(), errors, warnings);
+    }
+    let code = exit_code(&reports, is_strict);
+    if code != 0 {
+        process::exit(code);
+    }
+}
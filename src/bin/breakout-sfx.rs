@@ -0,0 +1,160 @@
+//! Renders the game's sound effects to WAV files through the same mixer the
+//! game plays them with, to listen to or check without a sound device.
+//!
+//! Usage: breakout-sfx [OPTIONS]
+//!
+//!   --out DIR          where to write the files (default sfx)
+//!   --volume V         0..1, mixer volume (default 1)
+//!   --combo N          block breaks in combo.wav (default 8)
+//!   --music FILE       a WAV or Ogg track, give it twice for a crossfade
+//!
+//! Writes DIR/<sound>.wav for each effect, DIR/combo.wav with a rising combo
+//! and DIR/mix.wav with overlapping effects. With --music it also writes
+//! DIR/music.wav: the first track, ducking under a level clear, then a
+//! crossfade to the second track or to silence.
+
+// The game's modules are shared with this tool, which only uses part of them.
+#![allow(dead_code)]
+
+extern crate libc;
+
+#[path = "../audio.rs"]
+mod audio;
+#[path = "../music.rs"]
+mod music;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+
+use audio::{write_wav, Mixer, Sound, SAMPLE_RATE, SOUNDS};
+use music::{load_track, MusicPlayer};
+
+const USAGE: &str = "Usage: breakout-sfx [--out DIR] [--volume V] [--combo N] [--music FILE [--music FILE]]";
+/// Silence after the last effect starts, so its tail is not cut off.
+const TAIL_TIME: f32 = 1.;
+/// When music.wav ducks, switches tracks and ends, in seconds.
+const MUSIC_DUCK_AT: f32 = 2.;
+const MUSIC_SWITCH_AT: f32 = 4.;
+const MUSIC_END_AT: f32 = 7.;
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_value<T: FromStr>(option: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| fail(&format!("{} needs a value", option)));
+    value.parse().unwrap_or_else(|_| fail(&format!("invalid value '{}' for {}", value, option)))
+}
+
+/// Mixes `cues`, each a start time in seconds and a sound.
+fn render(volume: f32, cues: &[(f32, Sound)]) -> Vec<f32> {
+    let mut mixer = Mixer::new(volume);
+    let end = cues.iter().fold(0f32, |end, &(t, _)| end.max(t)) + TAIL_TIME;
+    let mut samples = vec![0.; (end * SAMPLE_RATE as f32) as usize];
+    let mut at = 0;
+    for &(t, sound) in cues {
+        let start = ((t * SAMPLE_RATE as f32) as usize).min(samples.len());
+        mixer.render(&mut samples[at..start]);
+        at = start;
+        mixer.play(sound);
+    }
+    mixer.render(&mut samples[at..]);
+    // Trim the silence left over from the tail.
+    let len = samples.iter().rposition(|&sample| sample != 0.).map_or(0, |i| i + 1);
+    samples.truncate(len);
+    samples
+}
+
+/// Plays `tracks` the way the game does between scenes.
+fn render_music(volume: f32, tracks: &[PathBuf]) -> Vec<f32> {
+    let mut names = Vec::new();
+    for track in tracks {
+        // The player looks tracks up in the music directory, an absolute
+        // path gets past that.
+        let name = fs::canonicalize(track).and_then(|path| load_track(&path).map(|_| path));
+        match name {
+            Ok(path) => names.push(path.to_string_lossy().into_owned()),
+            Err(err) => {
+                eprintln!("Error: cannot load {}: {}", track.display(), err);
+                process::exit(1);
+            },
+        }
+    }
+    let seconds = |t: f32| (t * SAMPLE_RATE as f32) as usize;
+    let mut mixer = Mixer::new(volume);
+    let mut music = MusicPlayer::new(volume);
+    let mut samples = vec![0.; seconds(MUSIC_END_AT)];
+    let (duck, switch) = (seconds(MUSIC_DUCK_AT), seconds(MUSIC_SWITCH_AT));
+    music.play(Some(&names[0]));
+    mixer.render(&mut samples[..duck]);
+    music.render(&mut samples[..duck]);
+    let len = mixer.play(Sound::LevelClear);
+    music.duck(len);
+    mixer.render(&mut samples[duck..switch]);
+    music.render(&mut samples[duck..switch]);
+    music.play(names.get(1).map(String::as_str));
+    mixer.render(&mut samples[switch..]);
+    music.render(&mut samples[switch..]);
+    samples
+}
+
+fn save(path: &Path, samples: &[f32]) {
+    let result = File::create(path).and_then(|file| write_wav(BufWriter::new(file), samples));
+    if let Err(err) = result {
+        eprintln!("Error: cannot write {}: {}", path.display(), err);
+        process::exit(1);
+    }
+    println!("{} ({:.2}s)", path.display(), samples.len() as f32 / SAMPLE_RATE as f32);
+}
+
+fn main() {
+    let mut out = PathBuf::from("sfx");
+    let mut volume = 1f32;
+    let mut combo = 8;
+    let mut tracks = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = PathBuf::from(parse_value::<String>(&arg, args.next())),
+            "--volume" => volume = parse_value(&arg, args.next()),
+            "--combo" => combo = parse_value(&arg, args.next()),
+            "--music" => tracks.push(PathBuf::from(parse_value::<String>(&arg, args.next()))),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => fail(&format!("unknown argument {}", arg)),
+        }
+    }
+    if let Err(err) = fs::create_dir_all(&out) {
+        eprintln!("Error: cannot create {}: {}", out.display(), err);
+        process::exit(1);
+    }
+
+    for &sound in SOUNDS.iter() {
+        save(&out.join(format!("{}.wav", sound.name())), &render(volume, &[(0., sound)]));
+    }
+    let breaks: Vec<_> = (1..combo + 1).map(|i| ((i - 1) as f32 * 0.15, Sound::BlockBreak(i))).collect();
+    save(&out.join("combo.wav"), &render(volume, &breaks));
+    save(&out.join("mix.wav"), &render(volume, &[
+        (0., Sound::PaddleHit),
+        (0.2, Sound::WallBounce),
+        (0.4, Sound::BlockHit),
+        (0.45, Sound::BlockBreak(1)),
+        (0.5, Sound::BlockBreak(2)),
+        (0.55, Sound::BlockBreak(3)),
+        (0.9, Sound::BallLost),
+        (1.1, Sound::LevelClear),
+    ]));
+    if tracks.len() > 2 {
+        fail("--music can be given at most twice");
+    }
+    if !tracks.is_empty() {
+        save(&out.join("music.wav"), &render_music(volume, &tracks));
+    }
+}
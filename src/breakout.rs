@@ -4,12 +4,15 @@ use glutin::{
     MouseButton,
     VirtualKeyCode,
 };
+use std::collections::HashMap;
+use std::env;
+use std::mem;
+use std::net::TcpListener;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::path::{Path, PathBuf};
-use std::io::prelude::*;
-use std::io::BufReader;
-use std::fs::File;
-use std::cmp;
 
+use audio::{Audio, Sound};
 use collision::{
     check_rect_contains,
     solve_circle_rect_delta,
@@ -17,90 +20,347 @@ use collision::{
     Rectangle,
     RectangleSide,
 };
+use editor::Editor;
 use fonts::{FontLibrary, FontHandle};
+use gamepad::Gamepads;
+use generator::{generate, generate_garbage_row, generate_row, GeneratorParams, Pattern, Rng};
+use highscores::{HighScores, MAX_INITIALS};
+use input::{Action, InputEvent, InputFrame, InputState};
+use keys::{key_char, key_name};
+use level::{
+    hit_points,
+    is_block,
+    is_destructible,
+    LevelFile,
+    BALL_RADIUS,
+    BALL_SPEED,
+    EMPTY,
+    INDESTRUCTIBLE_BLOCK,
+    BLOCK_H,
+    BLOCK_W,
+    BOTTOM_TO_PADDLE_BOTTOM,
+    DEFAULT_PADDLE_HEIGHT,
+    DEFAULT_PADDLE_RADIUS,
+};
 use math::Vec2;
+use menu::{Menu, MenuItem, MenuNav, MENU_ITEM_H};
+use motion::Motion;
+use netplay::{Checksum, Hello, NetError, PlayerInput, Session};
+use pack::PackManifest;
+use particles::Particles;
 use renderer::{Color, Renderer, Viewport};
+use save::{LevelSnapshot, SaveGame, SavedBall};
+use scenes::{SceneStack, Transition};
+use spectate::{SpectatorClient, SpectatorServer};
+use splits::{format_delta, format_ticks, PersonalBests, RunTimer, TICK_RATE};
+use sprites::{SpriteAtlas, SpriteHandle, SPRITES_DIR};
+use settings::{Control, SettingOption, Settings, ACCESSIBILITY_OPTIONS, CONTROLS, DEFAULT_PADDLE_ACCELERATION, OPTIONS};
+use scoring::{
+    combo_multiplier,
+    time_bonus,
+    BlockGroup,
+    Score,
+    ScorePopup,
+    CLUSTER_CLEAR_BONUS,
+    NO_MISS_BONUS,
+    POINTS_PER_BLOCK,
+    ROW_CLEAR_BONUS,
+};
+use storage;
+use theme::{Theme, DEFAULT_THEME, PACK_THEME};
+use tween::{parallel, sequence, tween, wait, Anim, Ease, Timeline};
 
 
 struct LevelState {
     is_launching_ball: bool,
     w: f32,
     h: f32,
+    /// Where the shaken field sits at full shake. Settings scale it when
+    /// it is drawn.
     screen_p: Vec2,
     screen_v: Vec2,
     delay: f32,
+    paddle_acceleration: f32,
+    /// How fast the field runs, under 1 for slow mode.
     time_scale: f32,
+    /// Hit-stop after each bounce.
+    bounce_delay: f32,
+    /// What the ball's speed is multiplied by off the paddle.
+    bounce_speed_scale: f32,
+    time: f32,
+    par_time: Option<f32>,
+    /// Track from the level file, over the pack's level music.
+    music: Option<String>,
+    name: Option<String>,
+    author: Option<String>,
+    balls_lost: i32,
+    blocks_broken: i32,
+    best_combo: i32,
     paddle: Paddle,
     ball_proto: Ball,
     active_balls: Vec<Ball>,
     blocks: Vec<Block>,
     invalid_block_start: usize,
+    rows: Vec<BlockGroup>,
+    clusters: Vec<BlockGroup>,
+    popups: Vec<ScorePopup>,
+    particles: Particles,
+    dying_blocks: Vec<DyingBlock>,
+    paddle_squash: Option<Timeline<(), Vec2>>,
+    endless: Option<EndlessState>,
+}
+
+/// Endless mode: new rows keep arriving from the top, pushing the others down,
+/// until one reaches the paddle.
+struct EndlessState {
+    rng: Rng,
+    /// Time spent with a ball in play, which sets the difficulty.
+    elapsed: f32,
+    since_row: f32,
+    paddle_hits: i32,
+    rows_added: i32,
+    next_block_id: usize,
+}
+
+/// Two fields side by side, one per player. In versus, breaking blocks on
+/// one field piles garbage rows onto the other. In co-op both players have to
+/// clear their field.
+struct VersusState {
+    fields: Vec<LevelState>,
+    players: Vec<VersusPlayer>,
+    rng: Rng,
+    is_coop: bool,
+    outcome: Option<VersusOutcome>,
+    /// Connection to the other player's game, when they are not local.
+    net: Option<Session>,
+}
+
+enum VersusOutcome {
+    Won(usize),
+    Draw,
+    Cleared,
+    Defeated,
+    NetError(NetError),
+}
+
+struct VersusPlayer {
+    score: Score,
+    balls_left: i32,
+    /// Blocks broken towards the next garbage row.
+    garbage_progress: i32,
+    next_block_id: usize,
+}
+
+struct FinishState {
+    /// Initials typed so far, while the player is entering a new high score.
+    name_entry: Option<String>,
+    /// Time attack personal best the run is compared against.
+    best_before: Option<Vec<u32>>,
+    is_new_best: bool,
+    /// Whether the game ended by beating the last level rather than losing.
+    is_victory: bool,
+}
+
+/// The card over a level that is about to start.
+struct LevelIntroState {
+    /// Simulation time since it came up.
+    time: f32,
+    number: usize,
+    name: Option<String>,
+    author: Option<String>,
+}
+
+/// The tally over a cleared level until the next one comes in.
+struct LevelClearState {
+    time: f32,
+    level_time: f32,
+    blocks_broken: i32,
+    best_combo: i32,
+    time_bonus: i32,
+    no_miss_bonus: i32,
+}
+
+/// Shown over the level a game was lost on, before the final score.
+struct GameOverState {
+    time: f32,
 }
 
 enum Scene {
-    Start,
+    Start(Vec<Menu<MenuAction>>),
     Level(LevelState),
-    Finish,
+    Versus(VersusState),
+    Finish(FinishState),
+    Editor(Editor),
+    /// Open pause menus over a level or versus game, innermost last.
+    Pause(Vec<Menu<MenuAction>>),
+    LevelIntro(LevelIntroState),
+    LevelClear(LevelClearState),
+    GameOver(GameOverState),
 }
 
-fn load_level<P: AsRef<Path>>(path: P) -> Scene {
-    const DEFAULT_PADDLE_RADIUS: f32 = 48.;
-    const DEFAULT_PADDLE_HEIGHT: f32 = 16.;
-    const BLOCK_W: f32 = 24.;
-    const BLOCK_H: f32 = 16.;
-    const BOTTOM_TO_PADDLE_BOTTOM: f32 = 32.;
-    const BOTTOM_TO_BLOCK_BOTTOM: f32 = 256.;
-
-    let file = File::open(Path::new("res/levels/").join(path)).expect("Cannot open file");
-    let mut file = BufReader::new(file);
-    let mut read_line = || {
-        let mut line = String::new();
-        if file.read_line(&mut line).unwrap_or(0) > 0 {
-            let len = line.len();
-            line.truncate(len - 1);
-            Some(line)
-        } else {
-            None
+impl Scene {
+    /// For screens that wait for a key press or a timeout: how long they
+    /// have been up, and the most they stay up.
+    fn wait_time(&mut self) -> Option<(&mut f32, f32)> {
+        match *self {
+            Scene::LevelIntro(ref mut intro) => Some((&mut intro.time, LEVEL_INTRO_TIME)),
+            Scene::LevelClear(ref mut clear) => Some((&mut clear.time, LEVEL_CLEAR_TIME)),
+            Scene::GameOver(ref mut over) => Some((&mut over.time, GAME_OVER_TIME)),
+            _ => None,
         }
-    };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MenuAction {
+    Continue,
+    Play,
+    Endless,
+    TimeAttack,
+    Versus,
+    Coop,
+    LevelSelect,
+    StartLevel(usize),
+    LevelEditor,
+    EditLevel(usize),
+    NewLevel,
+    HighScores,
+    Settings,
+    Accessibility,
+    Bind(Control),
+    ResetKeys,
+    Setting(SettingOption),
+    Quit,
+    Resume,
+    RestartLevel,
+    QuitToTitle,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GameMode {
+    Campaign,
+    Endless,
+    TimeAttack,
+    Versus,
+}
+
+/// Modes with a high score table. Time attack keeps personal bests instead.
+const GAME_MODES: &[GameMode] = &[GameMode::Campaign, GameMode::Endless];
+
+impl GameMode {
+    fn name(&self) -> &'static str {
+        match *self {
+            GameMode::Campaign => "campaign",
+            GameMode::Endless => "endless",
+            GameMode::TimeAttack => "time_attack",
+            GameMode::Versus => "versus",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match *self {
+            GameMode::Campaign => "Campaign",
+            GameMode::Endless => "Endless",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Versus => "Versus",
+        }
+    }
+}
+
+fn load_level<P: AsRef<Path>>(path: P) -> Scene {
+    let path = Path::new("res/levels/").join(path);
+    let file = LevelFile::load(&path).unwrap_or_else(|err| panic!("Cannot load level: {}", err));
+    Scene::Level(build_level(&file))
+}
+
+fn build_level(file: &LevelFile) -> LevelState {
     let mut blocks = Vec::new();
-    let mut grid_w = 0;
-    let mut lines = Vec::new();
-    while let Some(line) = read_line() {
-        grid_w = cmp::max(line.len(), grid_w);
-        lines.push(line);
-    }
-    let grid_h = lines.len();
-    for (j, line) in lines.iter().enumerate() {
-        for (i, c) in line.chars().enumerate() {
-            if c == '#' {
-                blocks.push(Block {
-                    r: Rectangle::new(
-                        Vec2::new(
-                            i as f32 * BLOCK_W,
-                            ((grid_h - j - 1) as f32 * BLOCK_H) + BOTTOM_TO_BLOCK_BOTTOM,
-                        ),
-                        BLOCK_W,
-                        BLOCK_H,
-                    ),
-                    hits: 0,
-                });
+    let grid_h = file.height();
+    let mut cells = HashMap::new();
+    for (j, line) in file.grid.iter().enumerate() {
+        for (i, &c) in line.iter().enumerate() {
+            if !is_block(c) {
+                continue;
+            }
+            let motion = if c.is_ascii_lowercase() { file.motion(c) } else { Motion::Static };
+            let (x, y) = file.cell_origin(i, j);
+            let origin = Vec2::new(x, y);
+            // Indestructible blocks never count towards rows or clusters.
+            if is_destructible(c) {
+                cells.insert((i, j), blocks.len());
+            }
+            blocks.push(Block {
+                id: blocks.len(),
+                r: Rectangle::new(origin + motion.offset(0.), BLOCK_W, BLOCK_H),
+                origin,
+                v: Vec2::default(),
+                motion,
+                hits: 0,
+                hit_points: hit_points(c),
+                is_indestructible: !is_destructible(c),
+                row: j,
+                cluster: 0,
+            });
+        }
+    }
+
+    // Clusters are groups of blocks touching edge to edge in the grid.
+    let mut clusters: Vec<BlockGroup> = Vec::new();
+    let mut visited = vec![false; blocks.len()];
+    let mut cell_list: Vec<_> = cells.iter().map(|(&cell, &index)| (cell, index)).collect();
+    cell_list.sort_by_key(|&(_, index)| index);
+    for (cell, index) in cell_list {
+        if visited[index] {
+            continue;
+        }
+        let cluster = clusters.len();
+        let mut size = 0;
+        let mut stack = vec![cell];
+        visited[index] = true;
+        while let Some((i, j)) = stack.pop() {
+            blocks[cells[&(i, j)]].cluster = cluster;
+            size += 1;
+            let neighbors = [(i.wrapping_sub(1), j), (i + 1, j), (i, j.wrapping_sub(1)), (i, j + 1)];
+            for neighbor in neighbors.iter() {
+                if let Some(&n) = cells.get(neighbor) {
+                    if !visited[n] {
+                        visited[n] = true;
+                        stack.push(*neighbor);
+                    }
+                }
             }
         }
+        clusters.push(BlockGroup::new(size));
+    }
+    let mut rows = vec![BlockGroup::new(0); grid_h];
+    for block in blocks.iter().filter(|block| !block.is_indestructible) {
+        rows[block.row].size += 1;
+        rows[block.row].remaining += 1;
     }
 
-    let w = grid_w as f32 * BLOCK_W;
-    let h = grid_h as f32 * BLOCK_H + BOTTOM_TO_BLOCK_BOTTOM;
+    let (w, h) = file.size();
     let blocks_len = blocks.len();
 
-    Scene::Level(LevelState {
+    LevelState {
         is_launching_ball: true,
         w,
         h,
         screen_p: Vec2::default(),
         screen_v: Vec2::default(),
         delay: 0.,
+        paddle_acceleration: DEFAULT_PADDLE_ACCELERATION,
         time_scale: 1.,
+        bounce_delay: BOUNCE_DELAY,
+        bounce_speed_scale: BOUNCE_SPEED_SCALE,
+        time: 0.,
+        par_time: file.par_time,
+        music: file.music.clone(),
+        name: file.name.clone(),
+        author: file.author.clone(),
+        balls_lost: 0,
+        blocks_broken: 0,
+        best_combo: 0,
         paddle: Paddle {
             r: Rectangle::new(
                Vec2::new(
@@ -113,13 +373,395 @@ fn load_level<P: AsRef<Path>>(path: P) -> Scene {
             dx: 0.,
         },
         ball_proto: Ball {
-            c: Circle::new(Vec2::default(), 8.),
-            v: Vec2::new(0., 300.),
+            c: Circle::new(Vec2::default(), BALL_RADIUS),
+            v: Vec2::new(0., BALL_SPEED),
+            combo: 0,
         },
         active_balls: Vec::new(),
         blocks,
         invalid_block_start: blocks_len,
-    })
+        rows,
+        clusters,
+        popups: Vec::new(),
+        particles: Particles::new(),
+        dying_blocks: Vec::new(),
+        paddle_squash: None,
+        endless: None,
+    }
+}
+
+/// A few generated rows at the top of a tall, mostly empty `w` by `h` field,
+/// leaving room for rows still to come.
+fn seeded_field(seed: u64, w: usize, h: usize, rows: usize) -> LevelState {
+    let pattern = if seed % 2 == 0 { Pattern::Symmetric } else { Pattern::Noise };
+    let mut params = GeneratorParams::for_difficulty(pattern, seed, 0.);
+    params.w = w;
+    params.h = rows;
+    let mut file = generate(&params);
+    file.par_time = None;
+    file.resize(w, rows);
+    for _ in rows..h {
+        file.grid.push(vec![EMPTY; w]);
+    }
+    build_level(&file)
+}
+
+fn endless_level(seed: u64) -> LevelState {
+    let mut level = seeded_field(seed, ENDLESS_GRID_W, ENDLESS_GRID_H, ENDLESS_START_ROWS);
+    level.endless = Some(EndlessState {
+        rng: Rng::new(seed),
+        elapsed: 0.,
+        since_row: 0.,
+        paddle_hits: 0,
+        rows_added: 0,
+        next_block_id: level.blocks.len(),
+    });
+    level
+}
+
+impl EndlessState {
+    /// Ramps from 0 to 1 over `ENDLESS_RAMP_TIME`.
+    fn difficulty(&self) -> f32 {
+        (self.elapsed / ENDLESS_RAMP_TIME).min(1.)
+    }
+
+    fn row_interval(&self) -> f32 {
+        ENDLESS_ROW_INTERVAL - (ENDLESS_ROW_INTERVAL - ENDLESS_MIN_ROW_INTERVAL) * self.difficulty()
+    }
+}
+
+/// Adds a new endless row along the top, harder the longer the game goes.
+fn add_endless_row(level: &mut LevelState) {
+    let (row, next_id) = match level.endless {
+        Some(ref mut endless) => {
+            let difficulty = endless.difficulty();
+            let density = 0.3 + 0.5 * difficulty;
+            let max_hit_points = 1 + (difficulty * 4.).round() as i32;
+            endless.since_row = 0.;
+            endless.paddle_hits = 0;
+            endless.rows_added += 1;
+            (generate_row(&mut endless.rng, ENDLESS_GRID_W, density, max_hit_points), endless.next_block_id)
+        },
+        None => return,
+    };
+    let next_id = push_row(level, &row, next_id);
+    if let Some(ref mut endless) = level.endless {
+        endless.next_block_id = next_id;
+    }
+}
+
+/// Moves every block down a row and adds `row` along the top. Each run of
+/// touching blocks in the new row is its own cluster. New blocks are numbered
+/// from `next_id`, and the next free id is returned.
+fn push_row(level: &mut LevelState, row: &[char], mut next_id: usize) -> usize {
+    // Broken blocks are only kept around for snapshots, which modes adding
+    // rows do not take.
+    level.blocks.truncate(level.invalid_block_start);
+    for block in level.blocks.iter_mut() {
+        block.origin.y -= BLOCK_H;
+        block.r.p.y -= BLOCK_H;
+    }
+    let row_index = level.rows.len();
+    level.rows.push(BlockGroup::new(0));
+    for (i, &c) in row.iter().enumerate() {
+        if !is_block(c) {
+            continue;
+        }
+        if i == 0 || !is_block(row[i - 1]) {
+            level.clusters.push(BlockGroup::new(0));
+        }
+        let cluster = level.clusters.len() - 1;
+        level.rows[row_index].size += 1;
+        level.rows[row_index].remaining += 1;
+        level.clusters[cluster].size += 1;
+        level.clusters[cluster].remaining += 1;
+        let origin = Vec2::new(i as f32 * BLOCK_W, level.h - BLOCK_H);
+        level.blocks.push(Block {
+            id: next_id,
+            r: Rectangle::new(origin, BLOCK_W, BLOCK_H),
+            origin,
+            v: Vec2::default(),
+            motion: Motion::Static,
+            hits: 0,
+            hit_points: hit_points(c),
+            is_indestructible: false,
+            row: row_index,
+            cluster,
+        });
+        next_id += 1;
+    }
+    level.invalid_block_start = level.blocks.len();
+    next_id
+}
+
+/// Advances the endless clock and adds rows when they are due. Returns
+/// whether the blocks have reached the paddle.
+fn step_endless(level: &mut LevelState, dt: f32) -> bool {
+    let is_row_due = match level.endless {
+        Some(ref mut endless) => {
+            if !level.is_launching_ball {
+                endless.elapsed += dt;
+                endless.since_row += dt;
+            }
+            endless.since_row >= endless.row_interval() || endless.paddle_hits >= ENDLESS_PADDLE_HITS_PER_ROW
+        },
+        None => return false,
+    };
+    if is_row_due || is_level_cleared(level) {
+        add_endless_row(level);
+    }
+    blocks_reach_paddle(level)
+}
+
+/// Both players start on the same blocks.
+fn versus_state(seed: u64, is_coop: bool) -> VersusState {
+    let fields: Vec<_> = (0..2)
+        .map(|_| seeded_field(seed, VERSUS_GRID_W, VERSUS_GRID_H, VERSUS_START_ROWS))
+        .collect();
+    let players = fields.iter()
+        .map(|field| VersusPlayer {
+            score: Score::default(),
+            balls_left: VERSUS_LIVES,
+            garbage_progress: 0,
+            next_block_id: field.blocks.len(),
+        })
+        .collect();
+    VersusState {
+        fields,
+        players,
+        rng: Rng::new(seed),
+        is_coop,
+        outcome: None,
+        net: None,
+    }
+}
+
+/// Runs one tick of both fields, sends garbage between them and decides the
+/// game once a player runs out of balls, gets buried or clears their field.
+/// Returns the sounds from both fields.
+fn step_versus(versus: &mut VersusState, frame: &InputFrame, theme: &Theme) -> Vec<Sound> {
+    let mut sounds = Vec::new();
+    let moves = [frame.move_paddle, frame.player2_move_paddle];
+    let launches = [Action::Launch, Action::Player2Launch];
+    let mut garbage = [0; 2];
+    for i in 0..2 {
+        let (field, player) = (&mut versus.fields[i], &mut versus.players[i]);
+        if frame.has(launches[i]) && field.is_launching_ball {
+            launch_ball(field);
+        }
+        let events = step_level(field, &mut player.score, moves[i], theme);
+        if events.is_ball_lost {
+            player.balls_left -= 1;
+        }
+        sounds.extend(events.sounds);
+        if !versus.is_coop {
+            player.garbage_progress += events.blocks_broken;
+            garbage[1 - i] += player.garbage_progress / VERSUS_BLOCKS_PER_GARBAGE + events.rows_cleared;
+            player.garbage_progress %= VERSUS_BLOCKS_PER_GARBAGE;
+        }
+    }
+    for i in 0..2 {
+        for _ in 0..garbage[i] {
+            let row = generate_garbage_row(&mut versus.rng, VERSUS_GRID_W, VERSUS_GARBAGE_GAPS);
+            let next_id = versus.players[i].next_block_id;
+            versus.players[i].next_block_id = push_row(&mut versus.fields[i], &row, next_id);
+        }
+    }
+
+    let has_lost: Vec<bool> = (0..2)
+        .map(|i| versus.players[i].balls_left == 0 || blocks_reach_paddle(&versus.fields[i]))
+        .collect();
+    let has_cleared: Vec<bool> = versus.fields.iter().map(is_level_cleared).collect();
+    if versus.is_coop {
+        if has_lost[0] || has_lost[1] {
+            versus.outcome = Some(VersusOutcome::Defeated);
+        } else if has_cleared[0] && has_cleared[1] {
+            versus.outcome = Some(VersusOutcome::Cleared);
+            sounds.push(Sound::LevelClear);
+        }
+        return sounds;
+    }
+    let is_out = [has_lost[0] || has_cleared[1], has_lost[1] || has_cleared[0]];
+    let scores = [versus.players[0].score.total(), versus.players[1].score.total()];
+    versus.outcome = match (is_out[0], is_out[1]) {
+        (false, false) => None,
+        (false, true) => Some(VersusOutcome::Won(0)),
+        (true, false) => Some(VersusOutcome::Won(1)),
+        // Both went down on the same tick, points decide.
+        _ if scores[0] > scores[1] => Some(VersusOutcome::Won(0)),
+        _ if scores[1] > scores[0] => Some(VersusOutcome::Won(1)),
+        _ => Some(VersusOutcome::Draw),
+    };
+    sounds
+}
+
+/// Everything that could differ between two copies of the same game.
+fn versus_checksum(versus: &VersusState) -> u64 {
+    let mut checksum = Checksum::new();
+    checksum.add_u64(versus.rng.clone().next_u64());
+    for (field, player) in versus.fields.iter().zip(versus.players.iter()) {
+        checksum.add_i32(player.score.total());
+        checksum.add_i32(player.balls_left);
+        checksum.add_i32(player.garbage_progress);
+        checksum.add_f32(field.time);
+        checksum.add_f32(field.delay);
+        checksum.add_f32(field.paddle.r.p.x);
+        checksum.add_f32(field.paddle.dx);
+        for ball in field.active_balls.iter() {
+            checksum.add_f32(ball.c.p.x);
+            checksum.add_f32(ball.c.p.y);
+            checksum.add_f32(ball.v.x);
+            checksum.add_f32(ball.v.y);
+        }
+        for block in field.blocks[..field.invalid_block_start].iter() {
+            checksum.add_u64(block.id as u64);
+            checksum.add_i32(block.hits);
+            checksum.add_f32(block.r.p.y);
+        }
+    }
+    checksum.value()
+}
+
+/// The simulation's view of both players' network inputs.
+fn net_frame(inputs: &[PlayerInput; 2]) -> InputFrame {
+    let mut frame = InputFrame {
+        move_paddle: inputs[0].move_paddle(),
+        player2_move_paddle: inputs[1].move_paddle(),
+        actions: Vec::new(),
+    };
+    if inputs[0].launch {
+        frame.actions.push(Action::Launch);
+    }
+    if inputs[1].launch {
+        frame.actions.push(Action::Player2Launch);
+    }
+    frame
+}
+
+/// Moves a networked game on by a tick once the other side's input is in,
+/// with `input` from the local player. Returns the sounds from the tick.
+fn step_net_versus(versus: &mut VersusState, input: PlayerInput, theme: &Theme) -> Vec<Sound> {
+    let mut sounds = Vec::new();
+    let result = match versus.net {
+        Some(ref mut net) => net.advance(input),
+        None => return sounds,
+    };
+    let result = match result {
+        Ok(Some(inputs)) => {
+            sounds = step_versus(versus, &net_frame(&inputs), theme);
+            let checksum = versus_checksum(versus);
+            match versus.net {
+                Some(ref mut net) if net.is_checksum_due() => net.check(checksum),
+                _ => Ok(()),
+            }
+        },
+        Ok(None) => Ok(()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        versus.outcome = Some(VersusOutcome::NetError(err));
+        versus.net = None;
+    }
+    sounds
+}
+
+/// Moves a versus game on by a tick with this machine's `frame`. A local
+/// game plays with `settings`, a networked one only takes the local
+/// player's input from this side and leaves the rest to the defaults.
+fn step_versus_game(versus: &mut VersusState, frame: &InputFrame, settings: &Settings, theme: &Theme) -> Vec<Sound> {
+    if versus.net.is_some() {
+        let input = PlayerInput::new(frame.move_paddle, frame.has(Action::Launch));
+        return step_net_versus(versus, input, theme);
+    }
+    for field in versus.fields.iter_mut() {
+        apply_settings(field, settings);
+    }
+    step_versus(versus, frame, theme)
+}
+
+/// Plays a computer player's paddle towards its lowest ball, so test games
+/// last a while.
+fn autopilot(field: &LevelState) -> PlayerInput {
+    let paddle_x = field.paddle.r.p.x + field.paddle.r.w / 2.;
+    let target = field.active_balls.iter()
+        .min_by(|a, b| a.c.p.y.partial_cmp(&b.c.p.y).unwrap_or(::std::cmp::Ordering::Equal))
+        .map_or(field.w / 2., |ball| ball.c.p.x);
+    PlayerInput::new((target - paddle_x) / DEFAULT_PADDLE_RADIUS, true)
+}
+
+/// Runs two networked games against each other over loopback for `ticks`
+/// ticks, both played by the computer, and fails if they ever disagree.
+/// Each side plays with its own `settings`, as two machines would.
+/// `desync_at` nudges one side's ball on that tick to show that a desync is
+/// caught. Returns a summary on success.
+pub fn net_check(ticks: u32, input_delay: u32, desync_at: Option<u32>, settings: [&Settings; 2]) -> Result<String, NetError> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let joining = thread::spawn(move || Session::join(addr));
+    let hello = Hello { seed: 1, input_delay, is_coop: false };
+    let host = Session::host(&listener, hello)?;
+    let client = joining.join().map_err(|_| NetError::Protocol("join panicked".into()))??;
+    let mut games: Vec<VersusState> = vec![versus_state(hello.seed, false), versus_state(hello.seed, false)];
+    games[0].net = Some(host);
+    games[1].net = Some(client);
+    let theme = Theme::classic();
+    let mut rounds = 1;
+    while games.iter().any(|game| game.net.as_ref().map_or(false, |net| net.tick() < ticks)) {
+        for (side, game) in games.iter_mut().enumerate() {
+            let local = match game.net {
+                Some(ref net) if net.tick() < ticks => net.local,
+                _ => continue,
+            };
+            let input = autopilot(&game.fields[local]);
+            let mut frame = InputFrame { move_paddle: input.move_paddle(), player2_move_paddle: 0., actions: Vec::new() };
+            if input.launch {
+                frame.actions.push(Action::Launch);
+            }
+            step_versus_game(game, &frame, settings[side], &theme);
+            let tick = game.net.as_ref().map_or(0, |net| net.tick());
+            if side == 1 && Some(tick) == desync_at {
+                for ball in game.fields[0].active_balls.iter_mut() {
+                    ball.c.p.x += 1.;
+                }
+            }
+            match game.outcome.take() {
+                Some(VersusOutcome::NetError(err)) => return Err(err),
+                // Both sides see the game end on the same tick, and start
+                // the next one from a seed they both know.
+                Some(_) => {
+                    let net = game.net.take();
+                    *game = versus_state(hello.seed + tick as u64, false);
+                    game.net = net;
+                    if side == 0 {
+                        rounds += 1;
+                    }
+                },
+                None => (),
+            }
+        }
+    }
+    // Drain the last checksums.
+    for game in games.iter_mut() {
+        if let Some(ref mut net) = game.net {
+            net.poll()?;
+        }
+    }
+    if versus_checksum(&games[0]) != versus_checksum(&games[1]) {
+        return Err(NetError::Desync { tick: ticks });
+    }
+    Ok(format!("{} ticks in sync over {} game(s), input delay {}", ticks, rounds, input_delay))
+}
+
+/// A different seed for every game.
+fn time_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() ^ d.subsec_nanos() as u64)
+        .unwrap_or(1)
+}
+
+fn blocks_reach_paddle(level: &LevelState) -> bool {
+    let paddle_line = level.paddle.r.p.y + level.paddle.r.h;
+    level.blocks[..level.invalid_block_start].iter().any(|block| block.r.p.y <= paddle_line)
 }
 
 struct Paddle {
@@ -131,27 +773,91 @@ struct Paddle {
 struct Ball {
     c: Circle,
     v: Vec2,
+    combo: i32,
 }
 
 struct Block {
+    id: usize,
     r: Rectangle,
+    origin: Vec2,
+    v: Vec2,
+    motion: Motion,
     hits: i32,
+    hit_points: i32,
+    is_indestructible: bool,
+    row: usize,
+    cluster: usize,
+}
+
+/// A broken block shrinking and fading away, on the level's clock.
+struct DyingBlock {
+    r: Rectangle,
+    color: Color,
+    fx: Timeline<BlockFx, f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BlockFx {
+    Scale,
+    Alpha,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TitleFx {
+    Title,
+    Subtitle,
 }
 
 struct State {
-    score: i32,
-    scene: Scene,
+    score: Score,
+    scenes: SceneStack<Scene>,
     balls_left: i32,
     current_level: usize,
+    pack: String,
+    mode: GameMode,
     levels: Vec<PathBuf>,
-    key_l_state: bool,
-    key_r_state: bool,
+    /// Editor to return to when a play-test ends.
+    editor: Option<Editor>,
+    /// Timing of a time attack run.
+    run: Option<RunTimer>,
+    /// Whether any assist was on at some point in this game.
+    is_assisted: bool,
 }
 
 struct Assets {
     default_font: FontHandle,
     primary_font: FontHandle,
     secondary_font: FontHandle,
+    sprites: SpriteAtlas,
+    art: Art,
+}
+
+/// Sprites for what is in play, by the names of the theme's colors. Anything
+/// without one is drawn flat in its theme color.
+struct Art {
+    background: Option<SpriteHandle>,
+    playfield: Option<SpriteHandle>,
+    paddle: Option<SpriteHandle>,
+    ball: Option<SpriteHandle>,
+    block: Option<SpriteHandle>,
+    tough_block: Option<SpriteHandle>,
+    indestructible_block: Option<SpriteHandle>,
+    moving_block: Option<SpriteHandle>,
+}
+
+impl Art {
+    fn find(sprites: &SpriteAtlas) -> Self {
+        Art {
+            background: sprites.find("background"),
+            playfield: sprites.find("playfield"),
+            paddle: sprites.find("paddle"),
+            ball: sprites.find("ball"),
+            block: sprites.find("block"),
+            tough_block: sprites.find("block.tough"),
+            indestructible_block: sprites.find("block.indestructible"),
+            moving_block: sprites.find("block.moving"),
+        }
+    }
 }
 
 pub struct Game {
@@ -160,10 +866,35 @@ pub struct Game {
     viewport: Viewport,
     state: State,
     assets: Assets,
+    high_scores: HighScores,
+    save_path: PathBuf,
+    saved_game: Option<SaveGame>,
+    settings: Settings,
+    /// Control waiting for a key press to rebind it.
+    capturing: Option<Control>,
+    input: InputState,
+    gamepads: Gamepads,
+    mouse_p: Vec2,
+    is_quit_requested: bool,
+    pending: InputFrame,
+    /// Time not yet simulated, less than a tick.
+    tick_time: f32,
+    /// Simulation time since the base scene last changed.
+    scene_time: f32,
+    title_slide: Anim<TitleFx, Vec2>,
+    /// How far the title is lit up towards the theme's glow, 0..1.
+    title_pulse: Timeline<(), f32>,
+    personal_bests: PersonalBests,
+    /// Spectators watching this game.
+    broadcast: Option<SpectatorServer>,
+    /// Game being watched instead of played.
+    spectating: Option<SpectatorClient>,
+    audio: Audio,
+    pack_manifest: PackManifest,
+    theme: Theme,
 }
 
 const PADDLE_ADJ_FACTOR: f32 = 0.4;
-const POINTS_PER_BLOCK: i32 = 10;
 const BALL_MASS: f32 = 3.;
 const BOUNCE_DELAY: f32 = 0.01;
 const BOUNCE_SPEED_SCALE: f32 = 1.05;
@@ -171,6 +902,39 @@ const LEVEL_MASS: f32 = 5.;
 const LEVEL_DAMP: f32 = 0.3;
 const LEVEL_YOUNGS_MODULUS: f32 = 3.;
 const BALL_ANGLE_CLAMP: f32 = 0.5;
+const BLOCK_MOMENTUM_TRANSFER: f32 = 0.5;
+/// How far ahead the aim assist shows the ball's path, and through how many
+/// bounces.
+const AIM_LENGTH: f32 = 600.;
+const AIM_BOUNCES: usize = 3;
+const SPARK_COLOR: Color = Color { r: 1., g: 0.9, b: 0.6, a: 1. };
+const TICK_DT: f32 = 1. / TICK_RATE as f32;
+/// The longest the screens between levels stay up without a key press.
+const LEVEL_INTRO_TIME: f32 = 3.;
+const LEVEL_CLEAR_TIME: f32 = 6.;
+const GAME_OVER_TIME: f32 = 5.;
+/// How soon a key press can skip them, so one meant for the game does not.
+const SKIP_DELAY: f32 = 0.5;
+const ACCESSIBILITY_TITLE: &str = "Accessibility";
+/// Ticks a slow frame may catch up on before the game just runs slower.
+const MAX_TICKS_PER_STEP: u32 = 8;
+const ENDLESS_GRID_W: usize = 20;
+const ENDLESS_GRID_H: usize = 24;
+const ENDLESS_START_ROWS: usize = 5;
+/// Seconds between new rows, from the start down to full difficulty.
+const ENDLESS_ROW_INTERVAL: f32 = 10.;
+const ENDLESS_MIN_ROW_INTERVAL: f32 = 4.;
+/// A new row also comes after this many paddle hits, whichever is first.
+const ENDLESS_PADDLE_HITS_PER_ROW: i32 = 8;
+const ENDLESS_RAMP_TIME: f32 = 300.;
+const VERSUS_GRID_W: usize = 16;
+const VERSUS_GRID_H: usize = 24;
+const VERSUS_START_ROWS: usize = 5;
+const VERSUS_LIVES: i32 = 3;
+/// Blocks a player breaks to send one garbage row. Clearing a whole row
+/// sends one more.
+const VERSUS_BLOCKS_PER_GARBAGE: i32 = 6;
+const VERSUS_GARBAGE_GAPS: usize = 2;
 
 fn adjust_velocity(mut v: Vec2, dx: f32) -> Vec2 {
     let mag = v.norm();
@@ -193,326 +957,2501 @@ fn clamp_angle_x(v: Vec2, angle: f32) -> Vec2 {
     }
 }
 
-impl Game {
-    pub fn new(screen_w: u32, screen_h: u32) -> Self {
-        let mut font_lib = FontLibrary::new();
-        let state = State {
-            score: 0,
-            scene: Scene::Start,
-            balls_left: 0,
-            current_level: 0,
-            levels: vec![
-                "0.level".into(),
-                "1.level".into(),
-                "2.level".into(),
-            ],
-            key_l_state: false,
-            key_r_state: false,
-        };
-        let assets = Assets {
-            default_font: font_lib.load_from_file("res/fonts/yoster.ttf", 20),
-            primary_font: font_lib.load_from_file("res/fonts/yoster.ttf", 64),
-            secondary_font: font_lib.load_from_file("res/fonts/yoster.ttf", 36),
-        };
-        Game {
-            renderer: Renderer::new(),
-            font_lib,
-            viewport: Viewport {
-                p: Vec2::default(),
-                w: screen_w as _,
-                h: screen_h as _,
-            },
-            state,
-            assets,
-        }
+/// Picks up part of a moving block's velocity after a bounce, making sure the
+/// ball still leaves the side it hit faster than the block follows it.
+fn transfer_block_momentum(mut v: Vec2, side: RectangleSide, block_v: Vec2) -> Vec2 {
+    v = v + BLOCK_MOMENTUM_TRANSFER * block_v;
+    match side {
+        RectangleSide::North => if v.y <= block_v.y { v.y = block_v.y + v.y.abs() },
+        RectangleSide::South => if v.y >= block_v.y { v.y = block_v.y - v.y.abs() },
+        RectangleSide::East => if v.x <= block_v.x { v.x = block_v.x + v.x.abs() },
+        RectangleSide::West => if v.x >= block_v.x { v.x = block_v.x - v.x.abs() },
     }
+    v
+}
 
-    pub fn on_mouse_button(&mut self, button: MouseButton, state: ElementState) { }
+fn launch_ball(level: &mut LevelState) {
+    level.is_launching_ball = false;
+    let (c, v) = ball_on_paddle(level);
+    level.active_balls.push(Ball { c, v, combo: 0 });
+}
 
-    pub fn on_mouse_motion(&mut self, x: f32, y: f32) { }
+/// Where the ball waiting on the paddle is and how it would leave if
+/// launched now.
+fn ball_on_paddle(level: &LevelState) -> (Circle, Vec2) {
+    let p = level.paddle.r.p + Vec2::new(
+        level.paddle.r.w / 2., 
+        level.paddle.r.h + level.ball_proto.c.r
+        );
+    let v = clamp_angle_x(
+        adjust_velocity(level.ball_proto.v, level.paddle.dx * PADDLE_ADJ_FACTOR),
+        BALL_ANGLE_CLAMP,
+    );
+    (Circle::new(p, level.ball_proto.c.r), v)
+}
 
-    pub fn on_key(&mut self, keycode: VirtualKeyCode, key_state: ElementState) {
-        if keycode == VirtualKeyCode::Left {
-            self.state.key_l_state = key_state == ElementState::Pressed;
-        }
-        if keycode == VirtualKeyCode::Right {
-            self.state.key_r_state = key_state == ElementState::Pressed;
-        }
-        match self.state.scene {
-            Scene::Start => {
-                if (key_state == ElementState::Pressed) {
-                    self.state.score = 0;
-                    self.state.balls_left = 3;
-                    self.state.current_level = 0;
-                    self.state.scene = load_level(&self.state.levels[0]);
+/// The walls around a field the ball bounces off, left, right and top.
+fn field_walls(level: &LevelState) -> [Rectangle; 3] {
+    [
+        Rectangle::new(Vec2::new(-24., 0.), 24., level.h),
+        Rectangle::new(Vec2::new(level.w, 0.), 24., level.h),
+        Rectangle::new(Vec2::new(-24., level.h), level.w + 48., 24.),
+    ]
+}
+
+/// Carries the player's paddle feel and gentler or slower settings over to
+/// a field. Only for games on this machine, a networked field keeps the
+/// defaults so it runs the same for both sides.
+fn apply_settings(level: &mut LevelState, settings: &Settings) {
+    level.paddle_acceleration = settings.paddle_acceleration;
+    level.time_scale = settings.game_speed;
+    level.bounce_delay = settings.hit_stop * BOUNCE_DELAY;
+    level.bounce_speed_scale = 1. + settings.ball_speed_up * (BOUNCE_SPEED_SCALE - 1.);
+}
+
+/// The path a ball at `c` moving along `v` takes off the walls, up to the
+/// first block or paddle in its way, `AIM_BOUNCES` bounces or `AIM_LENGTH`.
+/// Blocks are taken where they are now.
+fn aim_path(level: &LevelState, c: Circle, v: Vec2) -> Vec<Vec2> {
+    let walls = field_walls(level);
+    let mut c = c;
+    let mut dir = v.unit();
+    let mut length = AIM_LENGTH;
+    let mut path = vec![c.p];
+    for _ in 0..AIM_BOUNCES + 1 {
+        let delta = length * dir;
+        let obstacles = level.blocks[..level.invalid_block_start].iter()
+            .map(|block| (block.r, false))
+            .chain(Some((level.paddle.r, false)))
+            .chain(walls.iter().map(|&wall| (wall, true)));
+        let mut nearest: Option<(f32, RectangleSide, bool)> = None;
+        for (rect, is_wall) in obstacles {
+            if let Some((t, side)) = solve_circle_rect_delta(c, rect, delta) {
+                if nearest.map_or(true, |(nearest_t, _, _)| t < nearest_t) {
+                    nearest = Some((t, side, is_wall));
                 }
-            },
-            Scene::Level(ref mut level) => {
-                if keycode == VirtualKeyCode::Space && key_state == ElementState::Pressed && level.is_launching_ball {
-                    level.is_launching_ball = false;
-                    let p = level.paddle.r.p + Vec2::new(
-                        level.paddle.r.w / 2., 
-                        level.paddle.r.h + level.ball_proto.c.r
-                        );
-                    let v = clamp_angle_x(
-                        adjust_velocity(level.ball_proto.v, level.paddle.dx * PADDLE_ADJ_FACTOR),
-                        BALL_ANGLE_CLAMP,
-                    );
-                    let c = Circle::new(p, level.ball_proto.c.r);
-                    level.active_balls.push(Ball {c, v});
+            }
+        }
+        match nearest {
+            Some((t, side, true)) => {
+                c.p = c.p + t * delta;
+                length *= 1. - t;
+                match side {
+                    RectangleSide::North => dir.y = dir.y.abs(),
+                    RectangleSide::South => dir.y = -dir.y.abs(),
+                    RectangleSide::East => dir.x = dir.x.abs(),
+                    RectangleSide::West => dir.x = -dir.x.abs(),
                 }
+                path.push(c.p);
             },
-            Scene::Finish => {
-                if (key_state == ElementState::Pressed) {
-                    self.state.scene = Scene::Start;
-                }
+            Some((t, _, false)) => {
+                path.push(c.p + t * delta);
+                break;
+            },
+            None => {
+                path.push(c.p + delta);
+                break;
             },
         }
     }
+    path
+}
 
-    pub fn on_viewport_change(&mut self, w: u32, h: u32) {
-        /* We probably want to ignore resize
-        self.viewport.w = w as _;
-        self.viewport.h = h as _;
-        unsafe {
-            gl::Viewport(0, 0, w as _, h as _);
+/// Unit vector out of a rectangle's `side`.
+fn side_normal(side: RectangleSide) -> Vec2 {
+    match side {
+        RectangleSide::North => Vec2::new(0., 1.),
+        RectangleSide::East => Vec2::new(1., 0.),
+        RectangleSide::South => Vec2::new(0., -1.),
+        RectangleSide::West => Vec2::new(-1., 0.),
+    }
+}
+
+/// What happened on a field during a tick, for the mode to act on.
+#[derive(Default)]
+struct TickEvents {
+    blocks_broken: i32,
+    rows_cleared: i32,
+    is_ball_lost: bool,
+    sounds: Vec<Sound>,
+}
+
+/// Moves a field on by one tick: moving blocks, screen shake, the paddle
+/// under `move_paddle` and the balls with everything they hit. Points go to
+/// `score`.
+fn step_level(level: &mut LevelState, score: &mut Score, move_paddle: f32, theme: &Theme) -> TickEvents {
+    let mut events = TickEvents::default();
+    let mut dt = level.time_scale * TICK_DT;
+    let reduce = if dt > level.delay { level.delay } else { dt };
+    dt -= reduce;
+    level.delay -= reduce;
+
+    let t1 = level.time + dt;
+    for block in level.blocks[..level.invalid_block_start].iter_mut() {
+        if block.motion.is_static() {
+            continue;
         }
-        */
+        let p0 = block.origin + block.motion.offset(level.time);
+        let p1 = block.origin + block.motion.offset(t1);
+        block.r.p = p0;
+        block.v = if dt > 0. { (1. / dt) * (p1 - p0) } else { Vec2::default() };
     }
 
-    pub fn step(&mut self, dt: f32) {
-        let mut next_scene = None;
-        if let Scene::Level(ref mut level) = self.state.scene {
-            if level.invalid_block_start == 0 {
-                self.state.current_level += 1;
-                if self.state.current_level < self.state.levels.len() {
-                    next_scene = Some(load_level(&self.state.levels[self.state.current_level]));
-                } else {
-                    next_scene = Some(Scene::Finish);
-                }
-            } else if self.state.balls_left == 0 {
-                next_scene = Some(Scene::Finish);
-            }
-            let mut dt = level.time_scale * dt;
-            let reduce = if dt > level.delay { level.delay } else { dt };
-            dt -= reduce;
-            level.delay -= reduce;
-
-            level.screen_p = level.screen_p + dt * level.screen_v;
-            level.screen_v = (1. - LEVEL_DAMP) * level.screen_v + (-LEVEL_YOUNGS_MODULUS) * level.screen_p;
-
-            let mut ddx = 0.;
-            if self.state.key_l_state {
-                ddx += -96.;
-            }
-            if self.state.key_r_state {
-                ddx += 96.;
-            }
-            level.paddle.dx += ddx;
-            level.paddle.dx *= 0.9;
-            level.paddle.r.p.x += level.paddle.dx * dt;
-            if level.paddle.r.p.x < 0. {
-                level.paddle.r.p.x = 0.;
-                if level.paddle.dx < 0. {
-                    level.paddle.dx = 0.;
-                }
-            }
-            if level.paddle.r.p.x + level.paddle.r.w > level.w {
-                level.paddle.r.p.x = level.w - level.paddle.r.w ;
-                if level.paddle.dx > 0. {
-                    level.paddle.dx = 0.;
+    level.screen_p = level.screen_p + dt * level.screen_v;
+    level.screen_v = (1. - LEVEL_DAMP) * level.screen_v + (-LEVEL_YOUNGS_MODULUS) * level.screen_p;
+
+    level.paddle.dx += move_paddle * level.paddle_acceleration;
+    level.paddle.dx *= 0.9;
+    level.paddle.r.p.x += level.paddle.dx * dt;
+    if level.paddle.r.p.x < 0. {
+        level.paddle.r.p.x = 0.;
+        if level.paddle.dx < 0. {
+            level.paddle.dx = 0.;
+        }
+    }
+    if level.paddle.r.p.x + level.paddle.r.w > level.w {
+        level.paddle.r.p.x = level.w - level.paddle.r.w ;
+        if level.paddle.dx > 0. {
+            level.paddle.dx = 0.;
+        }
+    }
+    let boundary_rects = field_walls(level);
+    'ball_loop: for ball in level.active_balls.iter_mut() {
+        level.particles.trail(ball.c.p, ball.c.r, theme.ball);
+        let reduce = if dt > level.delay { level.delay } else { dt };
+        dt -= reduce;
+        level.delay -= reduce;
+        while dt > 0. {
+            let dcp = dt * ball.v;
+            {
+
+                enum What {
+                    Block(usize),
+                    Boundary,
                 }
-            }
-            let boundary_rects = [
-                Rectangle::new(Vec2::new(-24., 0.), 24., level.h),
-                Rectangle::new(Vec2::new(level.w, 0.), 24., level.h),
-                Rectangle::new(Vec2::new(-24., level.h), level.w + 48., 24.),
-            ];
-            'ball_loop: for ball in level.active_balls.iter_mut() {
-                let reduce = if dt > level.delay { level.delay } else { dt };
-                dt -= reduce;
-                level.delay -= reduce;
-                while dt > 0. {
-                    let dcp = dt * ball.v;
-                    {
-
-                        enum What {
-                            Block(usize),
-                            Boundary,
-                        }
 
-                        let mut collision = None;
-                        for (i, block) in level.blocks[..level.invalid_block_start].iter().enumerate() {
-                            if let Some((t, side)) = solve_circle_rect_delta(ball.c, block.r, dcp) {
-                                if let Some((tt, _, _)) = collision {
-                                    if tt > t {
-                                        collision = Some((t, side, What::Block(i)));
-                                    }
-                                } else {
-                                    collision = Some((t, side, What::Block(i)));
-                                }
+                let mut collision = None;
+                for (i, block) in level.blocks[..level.invalid_block_start].iter().enumerate() {
+                    // Sweep in the block's frame so fast blocks cannot skip past the ball.
+                    let rel_dcp = dcp - dt * block.v;
+                    if let Some((t, side)) = solve_circle_rect_delta(ball.c, block.r, rel_dcp) {
+                        if let Some((tt, _, _)) = collision {
+                            if tt > t {
+                                collision = Some((t, side, What::Block(i)));
                             }
+                        } else {
+                            collision = Some((t, side, What::Block(i)));
                         }
-                        for rect in &boundary_rects {
-                            if let Some((t, side)) = solve_circle_rect_delta(ball.c, *rect, dcp) {
-                                if let Some((tt, _, _)) = collision {
-                                    if tt > t {
-                                        collision = Some((t, side, What::Boundary));
-                                    }
-                                } else {
-                                    collision = Some((t, side, What::Boundary));
-                                }
+                    }
+                }
+                for rect in &boundary_rects {
+                    if let Some((t, side)) = solve_circle_rect_delta(ball.c, *rect, dcp) {
+                        if let Some((tt, _, _)) = collision {
+                            if tt > t {
+                                collision = Some((t, side, What::Boundary));
                             }
+                        } else {
+                            collision = Some((t, side, What::Boundary));
                         }
-                        if let Some((t, side, what)) = collision {
-                            ball.c.p = ball.c.p + t * dcp;
-                            let original_v = ball.v.clone();
-                            match side {
-                                RectangleSide::North => ball.v.y = ball.v.y.abs(),
-                                RectangleSide::South => ball.v.y = -1. * ball.v.y.abs(),
-                                RectangleSide::East => ball.v.x = ball.v.x.abs(),
-                                RectangleSide::West => ball.v.x = -1. * ball.v.x.abs(),
-                            }
-                            level.screen_v = level.screen_v + (BALL_MASS / LEVEL_MASS) * (original_v - ball.v);
-                            level.delay = BOUNCE_DELAY;
-                            if let What::Block(i) = what {
-                                level.blocks[i].hits += 1;
-                                level.invalid_block_start -= 1;
-                                level.blocks.swap(i, level.invalid_block_start);
-                                self.state.score += POINTS_PER_BLOCK;
-                            }
+                    }
+                }
+                if let Some((t, side, what)) = collision {
+                    let original_v = ball.v.clone();
+                    let block_v = match what {
+                        What::Block(i) => level.blocks[i].v,
+                        What::Boundary => Vec2::default(),
+                    };
+                    // Ride along with the block for the rest of the step so it
+                    // does not end the frame overlapping the ball.
+                    ball.c.p = ball.c.p + t * dcp + ((1. - t) * dt) * block_v;
+                    match side {
+                        RectangleSide::North => ball.v.y = ball.v.y.abs(),
+                        RectangleSide::South => ball.v.y = -1. * ball.v.y.abs(),
+                        RectangleSide::East => ball.v.x = ball.v.x.abs(),
+                        RectangleSide::West => ball.v.x = -1. * ball.v.x.abs(),
+                    }
+                    if let What::Block(_) = what {
+                        ball.v = transfer_block_momentum(ball.v, side, block_v);
+                    }
+                    level.screen_v = level.screen_v + (BALL_MASS / LEVEL_MASS) * (original_v - ball.v);
+                    level.delay = level.bounce_delay;
+                    if let What::Boundary = what {
+                        events.sounds.push(Sound::WallBounce);
+                        let normal = side_normal(side);
+                        level.particles.sparks(ball.c.p + (-ball.c.r) * normal, normal, SPARK_COLOR);
+                    }
+                    if let What::Block(i) = what {
+                        let color = block_fill(&level.blocks[i], theme);
+                        level.blocks[i].hits += 1;
+                        if level.blocks[i].is_indestructible || level.blocks[i].hits < level.blocks[i].hit_points {
+                            events.sounds.push(Sound::BlockHit);
                             continue 'ball_loop;
                         }
+                        events.blocks_broken += 1;
+                        level.blocks_broken += 1;
+                        ball.combo += 1;
+                        level.best_combo = level.best_combo.max(ball.combo);
+                        events.sounds.push(Sound::BlockBreak(ball.combo));
+                        let multiplier = combo_multiplier(ball.combo);
+                        let block = &level.blocks[i];
+                        let mut points = POINTS_PER_BLOCK * multiplier;
+                        score.blocks += POINTS_PER_BLOCK;
+                        score.combo += POINTS_PER_BLOCK * (multiplier - 1);
+                        if level.rows[block.row].remove_one() {
+                            points += ROW_CLEAR_BONUS;
+                            score.rows += ROW_CLEAR_BONUS;
+                        }
+                        if level.clusters[block.cluster].remove_one() {
+                            points += CLUSTER_CLEAR_BONUS;
+                            score.clusters += CLUSTER_CLEAR_BONUS;
+                        }
+                        let text = if multiplier > 1 {
+                            format!("+{} x{}", points, multiplier)
+                        } else {
+                            format!("+{}", points)
+                        };
+                        let center = block.r.p + Vec2::new(block.r.w / 2., block.r.h / 2.);
+                        level.popups.push(ScorePopup::new(center, text));
+                        level.particles.debris(block.r.p, block.r.w, block.r.h, color);
+                        level.dying_blocks.push(DyingBlock {
+                            r: block.r,
+                            color,
+                            fx: Timeline::new(block_break_anim(), level.time),
+                        });
+                        level.invalid_block_start -= 1;
+                        level.blocks.swap(i, level.invalid_block_start);
                     }
-                    if let Some((t, RectangleSide::North)) = solve_circle_rect_delta(ball.c, level.paddle.r, dcp) {
-                        let original_v = ball.v.clone();
-                        ball.c.p = ball.c.p + t * dcp;
-                        ball.v.y = ball.v.y.abs();
-                        ball.v = clamp_angle_x(
-                            adjust_velocity(ball.v, level.paddle.dx * PADDLE_ADJ_FACTOR),
-                            BALL_ANGLE_CLAMP,
-                            );
-                        dt -= t;
-                        let v = original_v - ball.v;
-                        level.screen_v = level.screen_v + (BALL_MASS / LEVEL_MASS) * v;
-                        level.delay = BOUNCE_DELAY;
-                        ball.v = BOUNCE_SPEED_SCALE * ball.v;
-                        continue 'ball_loop;
-                    }
-                    ball.c.p = ball.c.p + dcp;
-                    dt = 0.;
+                    continue 'ball_loop;
                 }
             }
-            let level_rect = Rectangle::new(Vec2::default(), level.w, level.h);
-            level.active_balls = level.active_balls
-                .iter()
-                .filter(|ball| check_rect_contains(level_rect, ball.c.p + Vec2::new(0., -ball.c.r - 12.)))
-                .cloned()
-                .collect();
-            if level.active_balls.len() == 0 && !level.is_launching_ball {
-                self.state.balls_left -= 1;
-                level.is_launching_ball = true;
+            if let Some((t, RectangleSide::North)) = solve_circle_rect_delta(ball.c, level.paddle.r, dcp) {
+                let original_v = ball.v.clone();
+                ball.c.p = ball.c.p + t * dcp;
+                ball.v.y = ball.v.y.abs();
+                ball.v = clamp_angle_x(
+                    adjust_velocity(ball.v, level.paddle.dx * PADDLE_ADJ_FACTOR),
+                    BALL_ANGLE_CLAMP,
+                    );
+                dt -= t;
+                ball.combo = 0;
+                if let Some(ref mut endless) = level.endless {
+                    endless.paddle_hits += 1;
+                }
+                let v = original_v - ball.v;
+                level.screen_v = level.screen_v + (BALL_MASS / LEVEL_MASS) * v;
+                level.delay = level.bounce_delay;
+                ball.v = level.bounce_speed_scale * ball.v;
+                events.sounds.push(Sound::PaddleHit);
+                level.particles.sparks(ball.c.p + Vec2::new(0., -ball.c.r), Vec2::new(0., 1.), SPARK_COLOR);
+                level.paddle_squash = Some(Timeline::new(paddle_squash_anim(), level.time));
+                continue 'ball_loop;
             }
+            ball.c.p = ball.c.p + dcp;
+            dt = 0.;
         }
-        if let Some(scene) = next_scene {
-            self.state.scene = scene;
+    }
+    for block in level.blocks[..level.invalid_block_start].iter_mut() {
+        if !block.motion.is_static() {
+            block.r.p = block.origin + block.motion.offset(t1);
         }
     }
+    for popup in level.popups.iter_mut() {
+        popup.step(t1 - level.time);
+    }
+    level.popups.retain(|popup| !popup.is_expired());
+    level.time = t1;
+    level.dying_blocks.retain(|dying| !dying.fx.is_done(t1));
+    if level.paddle_squash.as_ref().map_or(false, |fx| fx.is_done(t1)) {
+        level.paddle_squash = None;
+    }
+    let level_rect = Rectangle::new(Vec2::default(), level.w, level.h);
+    level.active_balls = level.active_balls
+        .iter()
+        .filter(|ball| check_rect_contains(level_rect, ball.c.p + Vec2::new(0., -ball.c.r - 12.)))
+        .cloned()
+        .collect();
+    if level.active_balls.len() == 0 && !level.is_launching_ball {
+        level.balls_lost += 1;
+        level.is_launching_ball = true;
+        events.is_ball_lost = true;
+        events.sounds.push(Sound::BallLost);
+    }
+    events
+}
 
-    pub fn render(&mut self) {
-        unsafe {
-            gl::ClearColor(0.1, 0.1, 0.1, 0.);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+/// Whether only blocks the ball cannot break are left.
+fn is_level_cleared(level: &LevelState) -> bool {
+    level.blocks[..level.invalid_block_start].iter().all(|block| block.is_indestructible)
+}
+
+fn snapshot_level(level: &LevelState) -> LevelSnapshot {
+    LevelSnapshot {
+        time: level.time,
+        balls_lost: level.balls_lost,
+        is_launching_ball: level.is_launching_ball,
+        paddle_x: level.paddle.r.p.x,
+        paddle_dx: level.paddle.dx,
+        balls: level.active_balls.iter()
+            .map(|ball| SavedBall { p: ball.c.p, v: ball.v, combo: ball.combo })
+            .collect(),
+        block_count: level.blocks.len(),
+        blocks: level.blocks[..level.invalid_block_start].iter().map(|block| (block.id, block.hits)).collect(),
+    }
+}
+
+fn restore_snapshot(level: &mut LevelState, snapshot: &LevelSnapshot) {
+    if snapshot.block_count != level.blocks.len() {
+        eprintln!("Warning: level changed since it was saved, restarting it");
+        return;
+    }
+    let mut remaining = vec![None; level.blocks.len()];
+    for &(id, hits) in snapshot.blocks.iter() {
+        if id < remaining.len() {
+            remaining[id] = Some(hits);
         }
-        match self.state.scene {
-            Scene::Start => {
-                self.renderer.draw_text(
-                    &self.viewport,
-                    "B R E A K O U T ! !",
-                    Vec2::new(24., 256.),
-                    Color::new(1., 0., 0., 1.), 
-                    self.font_lib.get(self.assets.primary_font)
-                );
-                self.renderer.draw_text(
-                    &self.viewport,
-                    "A small game written by Eugene Che ~~",
-                    Vec2::new(24., 212.),
-                    Color::new(1., 1., 0., 1.), 
-                    self.font_lib.get(self.assets.secondary_font)
-                );
-                self.renderer.draw_text(
-                    &self.viewport,
-                    "Press any key to continue...",
-                    Vec2::new(24., 64.),
-                    Color::new(0., 0., 1., 1.), 
-                    self.font_lib.get(self.assets.default_font)
-                );
-            },
-            Scene::Finish => {
-                self.renderer.draw_text(
-                    &self.viewport,
-                    &format!("Your final score is {}", self.state.score),
-                    Vec2::new(24., 256.),
-                    Color::new(1., 0., 0., 1.), 
-                    self.font_lib.get(self.assets.primary_font)
-                );
-                self.renderer.draw_text(
-                    &self.viewport,
-                    "N I C E ! !",
-                    Vec2::new(24., 212.),
-                    Color::new(1., 1., 0., 1.), 
-                    self.font_lib.get(self.assets.secondary_font)
-                );
-                self.renderer.draw_text(
-                    &self.viewport,
-                    "Press any key to continue...",
-                    Vec2::new(24., 64.),
-                    Color::new(0., 0., 1., 1.), 
-                    self.font_lib.get(self.assets.default_font)
-                );
-            },
-            Scene::Level(ref level) => {
-                let vp_x = (self.viewport.w as f32 - level.w) / 2.;
-                let vp_y = (self.viewport.h as f32 - level.h) / 2.;
-                let vp = Vec2::new(vp_x, vp_y) + level.screen_p;
-                self.renderer.begin_batch();
-                self.renderer.draw_rectangle(vp, level.w, level.h, Color::new(0.01, 0.01, 0.01, 1.));
-                for block in level.blocks[..level.invalid_block_start].iter() {
-                    self.renderer.draw_rectangle(vp + block.r.p, block.r.w, block.r.h, Color::new(1., 1., 0., 1.));
-                }
-                self.renderer.draw_rectangle(
-                    vp + level.paddle.r.p,
-                    level.paddle.r.w,
-                    level.paddle.r.h,
-                    Color::new(1., 0., 0., 1.),
-                );
-                if level.is_launching_ball {
-                    let p = level.paddle.r.p + Vec2::new(
-                        level.paddle.r.w / 2., 
-                        level.paddle.r.h + level.ball_proto.c.r
-                    );
-                    self.renderer.draw_circle(vp + p, level.ball_proto.c.r, Color::new(0., 0., 1., 1.));
-                }
-                for ball in level.active_balls.iter() {
-                    self.renderer.draw_circle(vp + ball.c.p, ball.c.r, Color::new(0., 0., 1., 1.));
-                }
-                self.renderer.end_batch(&self.viewport);
-
-                self.renderer.draw_text(
-                    &self.viewport,
-                    &format!("Score: {}", self.state.score),
-                    Vec2::new(24., 64.),
-                    Color::new(0., 1., 0., 1.), 
-                    self.font_lib.get(self.assets.default_font)
-                );
-                self.renderer.draw_text(
-                    &self.viewport,
-                    &format!("Lives: {}", self.state.balls_left),
-                    Vec2::new(24., 32.),
-                    Color::new(0., 1., 0., 1.), 
-                    self.font_lib.get(self.assets.default_font)
-                );
-            }
+    }
+    let mut i = 0;
+    while i < level.invalid_block_start {
+        if let Some(hits) = remaining[level.blocks[i].id] {
+            level.blocks[i].hits = hits;
+            i += 1;
+            continue;
+        }
+        let (row, cluster) = (level.blocks[i].row, level.blocks[i].cluster);
+        level.rows[row].remove_one();
+        level.clusters[cluster].remove_one();
+        level.invalid_block_start -= 1;
+        level.blocks.swap(i, level.invalid_block_start);
+    }
+    for block in level.blocks.iter_mut() {
+        block.r.p = block.origin + block.motion.offset(snapshot.time);
+    }
+    level.time = snapshot.time;
+    level.balls_lost = snapshot.balls_lost;
+    level.is_launching_ball = snapshot.is_launching_ball;
+    level.paddle.r.p.x = snapshot.paddle_x;
+    level.paddle.dx = snapshot.paddle_dx;
+    let proto = level.ball_proto.clone();
+    level.active_balls = snapshot.balls.iter()
+        .map(|ball| Ball { c: Circle::new(ball.p, proto.c.r), v: ball.v, combo: ball.combo })
+        .collect();
+    if level.active_balls.is_empty() {
+        level.is_launching_ball = true;
+    }
+}
+
+/// One line per finished level with its time, and how it compares to the
+/// same level in `best` when there is one.
+fn split_table(splits: &[u32], best: Option<&[u32]>) -> String {
+    splits.iter()
+        .enumerate()
+        .map(|(i, &ticks)| match best.and_then(|best| best.get(i)) {
+            Some(&best_ticks) => format!("Level {}: {} ({})", i + 1, format_ticks(ticks), format_delta(ticks, best_ticks)),
+            None => format!("Level {}: {}", i + 1, format_ticks(ticks)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How a block in play is drawn, by how many hits it has left and whether
+/// it moves.
+fn block_fill(block: &Block, theme: &Theme) -> Color {
+    if block.is_indestructible {
+        theme.indestructible_block
+    } else if block.hit_points - block.hits > 1 {
+        theme.tough_block
+    } else if !block.motion.is_static() {
+        theme.moving_block
+    } else {
+        theme.block
+    }
+}
+
+/// The sprite for a block in play, picked like `block_fill`.
+fn block_art(block: &Block, art: &Art) -> Option<SpriteHandle> {
+    if block.is_indestructible {
+        art.indestructible_block
+    } else if block.hit_points - block.hits > 1 {
+        art.tough_block
+    } else if !block.motion.is_static() {
+        art.moving_block
+    } else {
+        art.block
+    }
+}
+
+/// How a cell is drawn in the editor. Tagged blocks get a hue of their own
+/// so their groups stand apart.
+fn block_color(c: char, theme: &Theme) -> Color {
+    if c == INDESTRUCTIBLE_BLOCK {
+        return theme.indestructible_block;
+    }
+    if hit_points(c) > 1 {
+        // Darker the more hits it takes.
+        let shade = 1. - (hit_points(c) - 2) as f32 / 10.;
+        let tough = theme.tough_block;
+        return Color::new(shade * tough.r, shade * tough.g, shade * tough.b, tough.a);
+    }
+    if !c.is_ascii_lowercase() {
+        return theme.block;
+    }
+    let hue = ((c as u8 - b'a') as f32 * 7. % 26.) / 26. * 6.;
+    let x = 1. - (hue % 2. - 1.).abs();
+    let (r, g, b) = match hue as i32 {
+        0 => (1., x, 0.),
+        1 => (x, 1., 0.),
+        2 => (0., 1., x),
+        3 => (0., x, 1.),
+        4 => (x, 0., 1.),
+        _ => (1., 0., x),
+    };
+    Color::new(r, g, b, 1.)
+}
+
+/// Lines describing a field for spectators: the field itself, then its
+/// balls, blocks and popups.
+fn write_field_view(view: &mut String, level: &LevelState) {
+    let paddle = level.paddle.r;
+    view.push_str(&format!(
+        "field {} {} {} {} {} {} {} {} {}\n",
+        level.w, level.h, level.screen_p.x, level.screen_p.y, level.is_launching_ball as u8,
+        paddle.p.x, paddle.p.y, paddle.w, paddle.h,
+    ));
+    for ball in level.active_balls.iter() {
+        view.push_str(&format!("ball {} {} {}\n", ball.c.p.x, ball.c.p.y, ball.c.r));
+    }
+    for block in level.blocks[..level.invalid_block_start].iter() {
+        // Hits left decide the color, none left means indestructible.
+        let hits_left = if block.is_indestructible { 0 } else { block.hit_points - block.hits };
+        view.push_str(&format!("block {} {} {} {} {}\n", block.r.p.x, block.r.p.y, block.r.w, block.r.h, hits_left));
+    }
+    for popup in level.popups.iter() {
+        view.push_str(&format!("popup {} {} {} {}\n", popup.p.x, popup.p.y, popup.age, popup.text));
+    }
+}
+
+/// A frame for spectators showing `scene`, with the score and lives for a
+/// level's HUD. `read_view` reads it back.
+fn write_view(scene: &Scene, score: &Score, balls_left: i32) -> String {
+    let mut view = String::new();
+    match *scene {
+        Scene::Level(ref level) => {
+            view.push_str("scene level\n");
+            view.push_str(&format!("hud {} {}\n", score.total(), balls_left));
+            write_field_view(&mut view, level);
+        },
+        Scene::Versus(ref versus) => {
+            view.push_str("scene versus\n");
+            for (field, player) in versus.fields.iter().zip(versus.players.iter()) {
+                write_field_view(&mut view, field);
+                view.push_str(&format!("player {} {}\n", player.score.total(), player.balls_left));
+            }
+            match versus.outcome {
+                Some(VersusOutcome::Won(i)) => view.push_str(&format!("outcome won {}\n", i)),
+                Some(VersusOutcome::Draw) => view.push_str("outcome draw\n"),
+                Some(VersusOutcome::Cleared) => view.push_str("outcome cleared\n"),
+                Some(VersusOutcome::Defeated) => view.push_str("outcome defeated\n"),
+                Some(VersusOutcome::NetError(_)) => view.push_str("outcome disconnected\n"),
+                None => (),
+            }
+        },
+        _ => view.push_str("scene idle\n"),
+    }
+    view
+}
+
+/// Rebuilds what spectators need to draw a game from a frame made by
+/// `write_view`. Returns the scene with the score and lives for the HUD.
+fn read_view(view: &str) -> Result<(Scene, Score, i32), String> {
+    let mut fields: Vec<LevelState> = Vec::new();
+    let mut players = Vec::new();
+    let mut outcome = None;
+    let mut score = Score::default();
+    let mut lives = 0;
+    let mut is_versus = false;
+    for line in view.lines() {
+        let mut words = line.splitn(2, ' ');
+        let (kind, rest) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+        let bad_line = || format!("bad line '{}'", line);
+        let numbers = rest.split_whitespace().map(|word| word.parse::<f32>().ok()).collect::<Option<Vec<_>>>();
+        match kind {
+            "scene" => match rest {
+                "level" => (),
+                "versus" => is_versus = true,
+                _ => return Ok((Scene::Start(Vec::new()), score, lives)),
+            },
+            "hud" => {
+                let numbers = numbers.filter(|n| n.len() == 2).ok_or_else(bad_line)?;
+                // Only the total is shown, so it all goes under one heading.
+                score.blocks = numbers[0] as i32;
+                lives = numbers[1] as i32;
+            },
+            "field" => {
+                let n = numbers.filter(|n| n.len() == 9).ok_or_else(bad_line)?;
+                let mut level = build_level(&LevelFile::new(0, 0));
+                level.w = n[0];
+                level.h = n[1];
+                level.screen_p = Vec2::new(n[2], n[3]);
+                level.is_launching_ball = n[4] != 0.;
+                level.paddle.r = Rectangle::new(Vec2::new(n[5], n[6]), n[7], n[8]);
+                fields.push(level);
+            },
+            "ball" | "block" | "popup" => {
+                let level = fields.last_mut().ok_or_else(bad_line)?;
+                if kind == "popup" {
+                    let mut words = rest.splitn(4, ' ');
+                    let mut number = || words.next().and_then(|word| word.parse::<f32>().ok()).ok_or_else(bad_line);
+                    let p = Vec2::new(number()?, number()?);
+                    let age = number()?;
+                    let mut popup = ScorePopup::new(p, words.next().unwrap_or("").into());
+                    popup.age = age;
+                    level.popups.push(popup);
+                } else if kind == "ball" {
+                    let n = numbers.filter(|n| n.len() == 3).ok_or_else(bad_line)?;
+                    let c = Circle::new(Vec2::new(n[0], n[1]), n[2]);
+                    level.active_balls.push(Ball { c, v: Vec2::default(), combo: 0 });
+                } else {
+                    let n = numbers.filter(|n| n.len() == 5).ok_or_else(bad_line)?;
+                    let origin = Vec2::new(n[0], n[1]);
+                    level.blocks.push(Block {
+                        id: level.blocks.len(),
+                        r: Rectangle::new(origin, n[2], n[3]),
+                        origin,
+                        v: Vec2::default(),
+                        motion: Motion::Static,
+                        hits: 0,
+                        hit_points: n[4] as i32,
+                        is_indestructible: n[4] == 0.,
+                        row: 0,
+                        cluster: 0,
+                    });
+                    level.invalid_block_start = level.blocks.len();
+                }
+            },
+            "player" => {
+                let n = numbers.filter(|n| n.len() == 2).ok_or_else(bad_line)?;
+                let mut player_score = Score::default();
+                player_score.blocks = n[0] as i32;
+                players.push(VersusPlayer { score: player_score, balls_left: n[1] as i32, garbage_progress: 0, next_block_id: 0 });
+            },
+            "outcome" => {
+                outcome = Some(match rest {
+                    "draw" => VersusOutcome::Draw,
+                    "cleared" => VersusOutcome::Cleared,
+                    "defeated" => VersusOutcome::Defeated,
+                    "disconnected" => VersusOutcome::NetError(NetError::Disconnected),
+                    _ => VersusOutcome::Won(rest.trim_start_matches("won ").parse().map_err(|_| bad_line())?),
+                });
+            },
+            "" => (),
+            _ => return Err(bad_line()),
+        }
+    }
+    if !is_versus {
+        let level = fields.pop().ok_or("frame without a field")?;
+        return Ok((Scene::Level(level), score, lives));
+    }
+    if fields.len() != 2 || players.len() != 2 {
+        return Err("versus frame without two players".into());
+    }
+    let mut versus = versus_state(0, false);
+    versus.fields = fields;
+    versus.players = players;
+    versus.outcome = outcome;
+    Ok((Scene::Versus(versus), score, lives))
+}
+
+/// Saves the campaign position in `state` to `path`, with a snapshot of the
+/// level in play. Other modes are not saved, and leave the campaign's save
+/// alone. Returns what was saved.
+fn write_progress(state: &State, path: &Path) -> Option<SaveGame> {
+    if state.mode != GameMode::Campaign {
+        return None;
+    }
+    let snapshot = match state.scenes.base {
+        // A cleared level already counts, the save points past it.
+        Scene::Level(ref level) if !is_level_cleared(level) => Some(snapshot_level(level)),
+        _ => None,
+    };
+    let save = SaveGame {
+        pack: state.pack.clone(),
+        mode: state.mode.name().into(),
+        level: state.current_level,
+        balls_left: state.balls_left,
+        score: state.score,
+        is_assisted: state.is_assisted,
+        snapshot,
+    };
+    if let Err(err) = save.save(path) {
+        eprintln!("Warning: cannot save progress: {}", err);
+    }
+    Some(save)
+}
+
+/// Removes the campaign save at `path` when the campaign in `state` is
+/// over. Returns whether it was removed.
+fn delete_progress(state: &State, path: &Path) -> bool {
+    if state.mode != GameMode::Campaign {
+        return false;
+    }
+    if let Err(err) = SaveGame::delete(path) {
+        eprintln!("Warning: cannot remove saved game: {}", err);
+    }
+    true
+}
+
+fn draw_menu(
+    renderer: &mut Renderer,
+    font_lib: &FontLibrary,
+    assets: &Assets,
+    viewport: &Viewport,
+    menu: &Menu<MenuAction>,
+) {
+    if !menu.title.is_empty() {
+        renderer.draw_text(
+            viewport,
+            &menu.title,
+            menu.p + Vec2::new(0., 1.5 * MENU_ITEM_H),
+            Color::new(1., 1., 0., 1.),
+            font_lib.get(assets.secondary_font),
+        );
+    }
+    for (i, item) in menu.items.iter().enumerate() {
+        let p = menu.p + Vec2::new(0., -(i as f32) * MENU_ITEM_H);
+        let (text, color) = if item.action.is_none() {
+            (item.label.clone(), Color::new(0.6, 0.6, 0.6, 1.))
+        } else if menu.focus == Some(i) {
+            (format!("> {}", item.label), Color::new(1., 1., 1., 1.))
+        } else {
+            (item.label.clone(), Color::new(0.3, 0.5, 1., 1.))
+        };
+        renderer.draw_text(viewport, &text, p, color, font_lib.get(assets.default_font));
+    }
+}
+
+/// A broken block swells a little, then shrinks away as it fades.
+fn block_break_anim() -> Anim<BlockFx, f32> {
+    parallel(vec![
+        sequence(vec![
+            tween(BlockFx::Scale, 1., 1.2, 0.05, Ease::OutQuad),
+            tween(BlockFx::Scale, 1.2, 0., 0.2, Ease::InQuad),
+        ]),
+        tween(BlockFx::Alpha, 1., 0., 0.25, Ease::Linear),
+    ])
+}
+
+/// The paddle's width and height scale after the ball lands on it.
+fn paddle_squash_anim() -> Anim<(), Vec2> {
+    sequence(vec![
+        tween((), Vec2::new(1., 1.), Vec2::new(1.1, 0.6), 0.05, Ease::OutQuad),
+        tween((), Vec2::new(1.1, 0.6), Vec2::new(1., 1.), 0.3, Ease::OutBack),
+    ])
+}
+
+/// Offsets of the title lines, sliding in from the left one after another.
+fn title_slide() -> Anim<TitleFx, Vec2> {
+    parallel(vec![
+        tween(TitleFx::Title, Vec2::new(-900., 0.), Vec2::default(), 0.6, Ease::OutCubic),
+        sequence(vec![
+            wait(0.2),
+            tween(TitleFx::Subtitle, Vec2::new(-900., 0.), Vec2::default(), 0.6, Ease::OutCubic),
+        ]),
+    ])
+}
+
+/// Draws the sprite for `art` over a rectangle, or the rectangle in `color`
+/// if there is none.
+fn draw_art(renderer: &mut Renderer, assets: &Assets, art: Option<SpriteHandle>, p: Vec2, w: f32, h: f32, color: Color) {
+    match art {
+        Some(sprite) => renderer.draw_sprite(assets.sprites.get(sprite), p, w, h, 0., Color::new(1., 1., 1., 1.)),
+        None => renderer.draw_rectangle(p, w, h, color),
+    }
+}
+
+/// The aim assist: where the waiting ball would go if launched now, and
+/// where each ball in play is headed.
+fn draw_aim(renderer: &mut Renderer, viewport: &Viewport, theme: &Theme, level: &LevelState, vp: Vec2) {
+    let ball = theme.ball;
+    let color = Color::new(ball.r, ball.g, ball.b, 0.5 * ball.a);
+    let mut paths = Vec::new();
+    if level.is_launching_ball {
+        let (c, v) = ball_on_paddle(level);
+        paths.push(aim_path(level, c, v));
+    }
+    for ball in level.active_balls.iter() {
+        paths.push(aim_path(level, ball.c, ball.v));
+    }
+    renderer.begin_batch();
+    for path in paths.iter() {
+        for segment in path.windows(2) {
+            renderer.draw_line(vp + segment[0], vp + segment[1], color);
+        }
+    }
+    renderer.end_batch(viewport);
+}
+
+/// The title's glow once it is in, looped.
+fn title_pulse() -> Anim<(), f32> {
+    sequence(vec![
+        tween((), 0., 1., 0.9, Ease::InOutSine),
+        tween((), 1., 0., 0.9, Ease::InOutSine),
+    ])
+}
+
+/// Darkens everything drawn so far, under an overlay.
+fn draw_dim(renderer: &mut Renderer, viewport: &Viewport, alpha: f32) {
+    renderer.begin_batch();
+    renderer.draw_rectangle(
+        Vec2::default(),
+        viewport.w,
+        viewport.h,
+        Color::new(0., 0., 0., alpha),
+    );
+    renderer.end_batch(viewport);
+}
+
+/// Dims the game behind an open pause menu.
+fn draw_pause(
+    renderer: &mut Renderer,
+    font_lib: &FontLibrary,
+    assets: &Assets,
+    viewport: &Viewport,
+    menu: &Menu<MenuAction>,
+) {
+    draw_dim(renderer, viewport, 0.6);
+    draw_menu(renderer, font_lib, assets, viewport, menu);
+}
+
+/// The theme the player picked, or else the level pack's.
+fn pick_theme(settings: &Settings, manifest: &PackManifest) -> Theme {
+    if settings.theme != PACK_THEME {
+        return Theme::load(&settings.theme);
+    }
+    Theme::load(manifest.theme.as_ref().map_or(DEFAULT_THEME, String::as_str))
+}
+
+impl Game {
+    pub fn new(screen_w: u32, screen_h: u32, settings: Settings) -> Self {
+        let mut font_lib = FontLibrary::new();
+        let state = State {
+            score: Score::default(),
+            scenes: SceneStack::new(Scene::Start(Vec::new())),
+            balls_left: 0,
+            current_level: 0,
+            pack: "default".into(),
+            mode: GameMode::Campaign,
+            levels: vec![
+                "0.level".into(),
+                "1.level".into(),
+                "2.level".into(),
+            ],
+            editor: None,
+            run: None,
+            is_assisted: false,
+        };
+        let sprites = SpriteAtlas::load(SPRITES_DIR);
+        let assets = Assets {
+            default_font: font_lib.load_from_file("res/fonts/yoster.ttf", 20),
+            primary_font: font_lib.load_from_file("res/fonts/yoster.ttf", 64),
+            secondary_font: font_lib.load_from_file("res/fonts/yoster.ttf", 36),
+            art: Art::find(&sprites),
+            sprites,
+        };
+        let pack_manifest = PackManifest::load("res/levels/pack.txt");
+        let theme = pick_theme(&settings, &pack_manifest);
+        let mut game = Game {
+            audio: Audio::open(settings.volume, settings.music_volume),
+            pack_manifest,
+            theme,
+            renderer: Renderer::new(),
+            font_lib,
+            viewport: Viewport {
+                p: Vec2::default(),
+                w: screen_w as _,
+                h: screen_h as _,
+            },
+            state,
+            assets,
+            high_scores: HighScores::load(storage::data_dir().join("highscores.txt")),
+            save_path: storage::data_dir().join("save.txt"),
+            saved_game: SaveGame::load(storage::data_dir().join("save.txt")),
+            settings,
+            capturing: None,
+            input: InputState::new(),
+            gamepads: Gamepads::new(),
+            mouse_p: Vec2::default(),
+            is_quit_requested: false,
+            pending: InputFrame::default(),
+            tick_time: 0.,
+            scene_time: 0.,
+            title_slide: title_slide(),
+            title_pulse: Timeline::new(title_pulse(), title_slide().duration()),
+            personal_bests: PersonalBests::load(storage::data_dir().join("splits.txt")),
+            broadcast: None,
+            spectating: None,
+        };
+        game.state.scenes.base = game.title_scene();
+        // Lets a pipe or a recorded evdev dump stand in for a controller.
+        if let Some(path) = env::var_os("BREAKOUT_GAMEPAD") {
+            if let Err(err) = game.gamepads.open(&path) {
+                eprintln!("Warning: cannot open gamepad {}: {}", path.to_string_lossy(), err);
+            }
+        }
+        game
+    }
+
+    pub fn is_quit_requested(&self) -> bool {
+        self.is_quit_requested
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    fn title_scene(&self) -> Scene {
+        let mut items = Vec::new();
+        if let Some(ref save) = self.saved_game {
+            items.push(MenuItem::new(
+                format!("Continue (level {}, {} points)", save.level + 1, save.score.total()),
+                MenuAction::Continue,
+            ));
+        }
+        items.push(MenuItem::new("Play", MenuAction::Play));
+        items.push(MenuItem::new("Endless", MenuAction::Endless));
+        items.push(MenuItem::new("Time Attack", MenuAction::TimeAttack));
+        items.push(MenuItem::new("Versus", MenuAction::Versus));
+        items.push(MenuItem::new("Co-op", MenuAction::Coop));
+        items.push(MenuItem::new("Level Select", MenuAction::LevelSelect));
+        items.push(MenuItem::new("High Scores", MenuAction::HighScores));
+        items.push(MenuItem::new("Level Editor", MenuAction::LevelEditor));
+        items.push(MenuItem::new("Settings", MenuAction::Settings));
+        items.push(MenuItem::new("Quit", MenuAction::Quit));
+        Scene::Start(vec![Menu::new("", Vec2::new(24., 560.), items)])
+    }
+
+    fn level_select_menu(&self) -> Menu<MenuAction> {
+        let mut items: Vec<_> = (0..self.state.levels.len())
+            .map(|i| MenuItem::new(format!("Level {}", i + 1), MenuAction::StartLevel(i)))
+            .collect();
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new("Level Select", Vec2::new(24., 560.), items)
+    }
+
+    fn level_editor_menu(&self) -> Menu<MenuAction> {
+        let mut items: Vec<_> = self.state.levels.iter()
+            .enumerate()
+            .map(|(i, path)| MenuItem::new(format!("Edit {}", path.display()), MenuAction::EditLevel(i)))
+            .collect();
+        items.push(MenuItem::new("New Level", MenuAction::NewLevel));
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new("Level Editor", Vec2::new(24., 560.), items)
+    }
+
+    fn high_scores_menu(&self) -> Menu<MenuAction> {
+        let mut items = Vec::new();
+        for &mode in GAME_MODES.iter() {
+            let top = self.high_scores.top(&self.state.pack, mode.name());
+            items.push(MenuItem::text(format!("- {} -", mode.label())));
+            for (i, entry) in top.iter().enumerate() {
+                let assisted = if entry.is_assisted { " (assisted)" } else { "" };
+                items.push(MenuItem::text(format!("{}. {} {}{}", i + 1, entry.initials, entry.score, assisted)));
+            }
+            if top.is_empty() {
+                items.push(MenuItem::text("No scores yet"));
+            }
+        }
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new("High Scores", Vec2::new(24., 560.), items)
+    }
+
+    fn settings_menu(&self, p: Vec2) -> Menu<MenuAction> {
+        let mut items = Vec::new();
+        for &control in CONTROLS.iter() {
+            let label = if self.capturing == Some(control) {
+                format!("{}: press a key to add or remove it...", control.label())
+            } else {
+                let names = self.settings.keys(control).iter().map(|&k| key_name(k)).collect::<Vec<_>>();
+                format!("{}: {}", control.label(), names.join(", "))
+            };
+            items.push(MenuItem::new(label, MenuAction::Bind(control)));
+        }
+        items.push(MenuItem::new("Reset keys", MenuAction::ResetKeys));
+        for &option in OPTIONS.iter() {
+            items.push(MenuItem::new(self.settings.label(option), MenuAction::Setting(option)));
+        }
+        items.push(MenuItem::new("Accessibility", MenuAction::Accessibility));
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new("Settings", p, items)
+    }
+
+    fn accessibility_menu(&self, p: Vec2) -> Menu<MenuAction> {
+        let mut items: Vec<_> = ACCESSIBILITY_OPTIONS.iter()
+            .map(|&option| MenuItem::new(self.settings.label(option), MenuAction::Setting(option)))
+            .collect();
+        items.push(MenuItem::text("Slower games, less speed-up and aim assist mark scores as assisted"));
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new(ACCESSIBILITY_TITLE, p, items)
+    }
+
+    /// Rebuilds the settings menu on top of the menu stack after a change.
+    fn refresh_settings_menu(&mut self) {
+        let (p, is_accessibility) = match self.active_menus().and_then(|menus| menus.last()) {
+            Some(menu) => (menu.p, menu.title == ACCESSIBILITY_TITLE),
+            None => return,
+        };
+        let mut menu = if is_accessibility { self.accessibility_menu(p) } else { self.settings_menu(p) };
+        if let Some(top) = self.active_menus().and_then(|menus| menus.last_mut()) {
+            menu.focus = top.focus;
+            *top = menu;
+        }
+    }
+
+    fn on_settings_changed(&mut self) {
+        self.theme = pick_theme(&self.settings, &self.pack_manifest);
+        self.audio.mixer.set_volume(self.settings.volume);
+        self.audio.music.set_volume(self.settings.music_volume);
+        if let Err(err) = self.settings.save() {
+            eprintln!("Warning: cannot save settings: {}", err);
+        }
+        self.refresh_settings_menu();
+    }
+
+    fn pause_menu(&self) -> Menu<MenuAction> {
+        Menu::new("Paused", self.pause_menu_p(), vec![
+            MenuItem::new("Resume", MenuAction::Resume),
+            MenuItem::new("Restart Level", MenuAction::RestartLevel),
+            MenuItem::new("Settings", MenuAction::Settings),
+            MenuItem::new("Quit to Title", MenuAction::QuitToTitle),
+        ])
+    }
+
+    /// Pause menu of a networked game, which carries on underneath it and
+    /// cannot be restarted by one side alone.
+    fn net_pause_menu(&self) -> Menu<MenuAction> {
+        Menu::new("Paused", self.pause_menu_p(), vec![
+            MenuItem::new("Resume", MenuAction::Resume),
+            MenuItem::new("Settings", MenuAction::Settings),
+            MenuItem::new("Quit to Title", MenuAction::QuitToTitle),
+        ])
+    }
+
+    /// Bottom left corner of a playfield centered in the window.
+    fn field_origin(&self, w: f32, h: f32) -> Vec2 {
+        Vec2::new((self.viewport.w - w) / 2., (self.viewport.h - h) / 2.)
+    }
+
+    /// Bottom left corner of player `i`'s field in versus, one field to each
+    /// half of the window.
+    fn versus_origin(&self, i: usize, w: f32, h: f32) -> Vec2 {
+        let center = self.viewport.w * (2 * i + 1) as f32 / 4.;
+        Vec2::new(center - w / 2., (self.viewport.h - h) / 2.)
+    }
+
+    fn editor_origin(&self) -> Vec2 {
+        match self.state.scenes.base {
+            Scene::Editor(ref editor) => {
+                let (w, h) = editor.file.size();
+                self.field_origin(w, h)
+            },
+            _ => Vec2::default(),
+        }
+    }
+
+    fn pause_menu_p(&self) -> Vec2 {
+        Vec2::new(self.viewport.w / 2. - 120., self.viewport.h / 2. + 64.)
+    }
+
+    /// The menu stack taking input right now, if any.
+    fn active_menus(&mut self) -> Option<&mut Vec<Menu<MenuAction>>> {
+        match *self.state.scenes.top_mut() {
+            Scene::Start(ref mut menus) | Scene::Pause(ref mut menus) => Some(menus),
+            _ => None,
+        }
+    }
+
+    /// Swaps in `scene` through `transition`, or a plain fade with reduced
+    /// motion on.
+    fn switch_scene(&mut self, scene: Scene, transition: Transition) {
+        let transition = match transition {
+            Transition::Wipe | Transition::Iris if self.settings.reduced_motion => Transition::Fade,
+            transition => transition,
+        };
+        self.state.scenes.switch(scene, transition);
+    }
+
+    /// Runs once a new base scene is in, while the screen is still covered.
+    fn enter_scene(&mut self) {
+        self.scene_time = 0.;
+        let intro = match self.state.scenes.base {
+            // Play-tests are not part of the campaign.
+            Scene::Level(ref level) if self.state.editor.is_none() && level.endless.is_none() => {
+                Some(LevelIntroState {
+                    time: 0.,
+                    number: self.state.current_level + 1,
+                    name: level.name.clone(),
+                    author: level.author.clone(),
+                })
+            },
+            _ => None,
+        };
+        if let Some(intro) = intro {
+            self.save_progress();
+            self.state.scenes.push(Scene::LevelIntro(intro));
+        }
+        if let Scene::Finish(_) = self.state.scenes.base {
+            self.clear_progress();
+        }
+    }
+
+    /// Runs as `scene` gives way to the new base, before it is entered.
+    fn exit_scene(&mut self, scene: Scene) {
+        // Presses meant for the old scene do not carry over.
+        self.pending.actions.clear();
+        self.capturing = None;
+        if let Scene::Editor(editor) = scene {
+            // A play-test is starting, the editor waits for it to end.
+            if let Scene::Level(_) = self.state.scenes.base {
+                self.state.editor = Some(editor);
+            }
+        }
+    }
+
+    /// Whether a networked game is in play, under any overlays.
+    fn is_net_game(&self) -> bool {
+        match self.state.scenes.base {
+            Scene::Versus(ref versus) => versus.net.is_some(),
+            _ => false,
+        }
+    }
+
+    fn push_menu(&mut self, menu: Menu<MenuAction>) {
+        if let Some(menus) = self.active_menus() {
+            menus.push(menu);
+        }
+    }
+
+    fn on_menu_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::Continue => self.continue_game(),
+            MenuAction::Play => self.new_game(0),
+            MenuAction::Endless => self.new_endless_game(),
+            MenuAction::TimeAttack => {
+                self.new_game(0);
+                self.state.mode = GameMode::TimeAttack;
+                self.state.run = Some(RunTimer::default());
+            },
+            MenuAction::Versus => self.new_versus_game(false),
+            MenuAction::Coop => self.new_versus_game(true),
+            MenuAction::StartLevel(i) => self.new_game(i),
+            MenuAction::LevelSelect => {
+                let menu = self.level_select_menu();
+                self.push_menu(menu);
+            },
+            MenuAction::HighScores => {
+                let menu = self.high_scores_menu();
+                self.push_menu(menu);
+            },
+            MenuAction::LevelEditor => {
+                let menu = self.level_editor_menu();
+                self.push_menu(menu);
+            },
+            MenuAction::EditLevel(i) => {
+                let path = Path::new("res/levels/").join(&self.state.levels[i]);
+                self.open_editor(path);
+            },
+            MenuAction::NewLevel => {
+                let path = (1..)
+                    .map(|n| Path::new("res/levels/").join(format!("custom-{}.level", n)))
+                    .find(|path| !path.exists())
+                    .expect("Some level name is free");
+                self.open_editor(path);
+            },
+            MenuAction::Settings => {
+                let p = match *self.state.scenes.top() {
+                    Scene::Pause(_) => self.pause_menu_p(),
+                    _ => Vec2::new(24., 560.),
+                };
+                let menu = self.settings_menu(p);
+                self.push_menu(menu);
+            },
+            MenuAction::Accessibility => {
+                let p = match self.active_menus().and_then(|menus| menus.last()) {
+                    Some(menu) => menu.p,
+                    None => return,
+                };
+                let menu = self.accessibility_menu(p);
+                self.push_menu(menu);
+            },
+            MenuAction::Bind(control) => {
+                self.capturing = Some(control);
+                self.refresh_settings_menu();
+            },
+            MenuAction::ResetKeys => {
+                self.settings.reset_keys();
+                self.on_settings_changed();
+            },
+            MenuAction::Setting(option) => {
+                self.settings.cycle(option);
+                self.on_settings_changed();
+            },
+            MenuAction::Quit => self.is_quit_requested = true,
+            MenuAction::Resume => {
+                if let Scene::Pause(_) = *self.state.scenes.top() {
+                    self.state.scenes.pop();
+                }
+            },
+            MenuAction::RestartLevel => match self.state.mode {
+                // Restarting costs a time attack run the time already spent.
+                GameMode::Campaign | GameMode::TimeAttack => {
+                    let scene = load_level(&self.state.levels[self.state.current_level]);
+                    self.switch_scene(scene, Transition::Iris);
+                },
+                GameMode::Endless => self.new_endless_game(),
+                GameMode::Versus => {
+                    let is_coop = match self.state.scenes.base {
+                        Scene::Versus(ref versus) => versus.is_coop,
+                        _ => false,
+                    };
+                    self.new_versus_game(is_coop);
+                },
+            },
+            MenuAction::QuitToTitle => {
+                self.save_progress();
+                let scene = self.title_scene();
+                self.switch_scene(scene, Transition::Fade);
+            },
+            MenuAction::Back => {
+                let is_resumed = match *self.state.scenes.top_mut() {
+                    // The title menu itself stays put.
+                    Scene::Start(ref mut menus) if menus.len() > 1 => {
+                        menus.pop();
+                        false
+                    },
+                    Scene::Pause(ref mut menus) => {
+                        menus.pop();
+                        menus.is_empty()
+                    },
+                    _ => false,
+                };
+                if is_resumed {
+                    self.state.scenes.pop();
+                }
+            },
+        }
+    }
+
+    fn new_game(&mut self, level: usize) {
+        self.state.mode = GameMode::Campaign;
+        self.state.run = None;
+        self.state.is_assisted = false;
+        self.state.score = Score::default();
+        self.state.balls_left = 3;
+        self.state.current_level = level;
+        let scene = load_level(&self.state.levels[level]);
+        self.switch_scene(scene, Transition::Fade);
+    }
+
+    fn new_endless_game(&mut self) {
+        let seed = time_seed();
+        self.state.mode = GameMode::Endless;
+        self.state.run = None;
+        self.state.is_assisted = false;
+        self.state.score = Score::default();
+        self.state.balls_left = 3;
+        self.switch_scene(Scene::Level(endless_level(seed)), Transition::Fade);
+    }
+
+    fn new_versus_game(&mut self, is_coop: bool) {
+        self.state.mode = GameMode::Versus;
+        self.state.run = None;
+        self.switch_scene(Scene::Versus(versus_state(time_seed(), is_coop)), Transition::Fade);
+    }
+
+    /// Serves the game to spectators on `addr`, a TCP `host:port` or
+    /// `unix:<path>`.
+    pub fn start_broadcast(&mut self, addr: &str) {
+        match SpectatorServer::bind(addr) {
+            Ok(server) => self.broadcast = Some(server),
+            Err(err) => eprintln!("Warning: cannot broadcast on {}: {}", addr, err),
+        }
+    }
+
+    /// Watches the game broadcast on `addr` instead of playing.
+    pub fn spectate(&mut self, addr: &str) {
+        match SpectatorClient::connect(addr) {
+            Ok(client) => {
+                self.spectating = Some(client);
+                self.state.scenes = SceneStack::new(Scene::Start(Vec::new()));
+            },
+            Err(err) => eprintln!("Warning: cannot spectate {}: {}", addr, err),
+        }
+    }
+
+    /// The game in play as a frame for spectators.
+    fn view(&self) -> String {
+        write_view(&self.state.scenes.base, &self.state.score, self.state.balls_left)
+    }
+
+    /// Shows the latest frame from the watched game.
+    fn step_spectating(&mut self) {
+        let frame = match self.spectating {
+            Some(ref mut client) if client.is_connected => client.poll(),
+            _ => return,
+        };
+        let view = match frame {
+            Ok(Some(view)) => view,
+            Ok(None) => return,
+            Err(err) => {
+                eprintln!("Warning: lost the watched game: {}", err);
+                self.state.scenes.base = Scene::Start(Vec::new());
+                return;
+            },
+        };
+        match read_view(&view) {
+            // Frames replace each other as they come, with no transition.
+            Ok((scene, score, balls_left)) => {
+                self.state.scenes.base = scene;
+                self.state.score = score;
+                self.state.balls_left = balls_left;
+            },
+            Err(err) => eprintln!("Warning: skipping bad spectator frame: {}", err),
+        }
+    }
+
+    /// Starts a game against, or with, the player at the other end of
+    /// `session`.
+    pub fn start_net_game(&mut self, session: Session) {
+        let mut versus = versus_state(session.hello.seed, session.hello.is_coop);
+        versus.net = Some(session);
+        self.state.mode = GameMode::Versus;
+        self.state.run = None;
+        self.switch_scene(Scene::Versus(versus), Transition::Cut);
+    }
+
+    fn continue_game(&mut self) {
+        let save = match self.saved_game.clone() {
+            Some(save) => save,
+            None => return self.new_game(0),
+        };
+        self.state.mode = GameMode::Campaign;
+        self.state.run = None;
+        if save.pack != self.state.pack || save.mode != self.state.mode.name() || save.level >= self.state.levels.len() {
+            eprintln!("Warning: saved game does not match the installed levels, starting over");
+            return self.new_game(0);
+        }
+        self.state.score = save.score;
+        self.state.is_assisted = save.is_assisted;
+        self.state.balls_left = save.balls_left;
+        self.state.current_level = save.level;
+        let mut scene = load_level(&self.state.levels[save.level]);
+        if let (Scene::Level(ref mut level), Some(ref snapshot)) = (&mut scene, save.snapshot) {
+            restore_snapshot(level, snapshot);
+        }
+        self.switch_scene(scene, Transition::Fade);
+    }
+
+    /// Writes the campaign position, plus a snapshot of the level in play.
+    fn save_progress(&mut self) {
+        if let Some(save) = write_progress(&self.state, &self.save_path) {
+            self.saved_game = Some(save);
+        }
+    }
+
+    /// Forgets the saved campaign once it is over.
+    fn clear_progress(&mut self) {
+        if delete_progress(&self.state, &self.save_path) {
+            self.saved_game = None;
+        }
+    }
+
+    pub fn on_quit(&mut self) {
+        // A lost game is over, not waiting to be continued.
+        if let Scene::GameOver(_) = *self.state.scenes.top() {
+            return;
+        }
+        if let Scene::Level(_) = self.state.scenes.base {
+            // Play-tests are not part of the campaign.
+            if self.state.editor.is_none() {
+                self.save_progress();
+            }
+        }
+    }
+
+    /// Opens the level editor on `path`, a new level if nothing is there yet.
+    /// Bare file names are looked up among the installed levels.
+    pub fn open_editor<P: AsRef<Path>>(&mut self, path: P) {
+        let mut path = path.as_ref().to_path_buf();
+        if !path.exists() && path.parent().map_or(true, |parent| parent.as_os_str().is_empty()) {
+            path = Path::new("res/levels/").join(path);
+        }
+        match Editor::open(&path) {
+            Ok(editor) => self.switch_scene(Scene::Editor(editor), Transition::Wipe),
+            Err(err) => eprintln!("Warning: cannot edit level: {}", err),
+        }
+    }
+
+    /// Plays the level being edited with the real simulation, keeping the
+    /// editor aside until the play-test ends.
+    fn start_play_test(&mut self) {
+        let level = match self.state.scenes.base {
+            Scene::Editor(ref editor) => build_level(&editor.file),
+            _ => return,
+        };
+        self.state.run = None;
+        self.state.score = Score::default();
+        self.state.balls_left = 3;
+        self.switch_scene(Scene::Level(level), Transition::Wipe);
+    }
+
+    fn end_play_test(&mut self) {
+        if let Some(mut editor) = self.state.editor.take() {
+            editor.status = format!("Play-test over, {} points", self.state.score.total());
+            self.switch_scene(Scene::Editor(editor), Transition::Wipe);
+        }
+    }
+
+    /// Handles a key in the editor. Returns whether it was used.
+    fn on_editor_key(&mut self, keycode: VirtualKeyCode) -> bool {
+        let editor = match self.state.scenes.base {
+            Scene::Editor(ref mut editor) => editor,
+            _ => return false,
+        };
+        if keycode != VirtualKeyCode::Escape {
+            editor.is_exit_pending = false;
+        }
+        match keycode {
+            VirtualKeyCode::Escape => {
+                if editor.is_dirty && !editor.is_exit_pending {
+                    editor.is_exit_pending = true;
+                    editor.status = "Unsaved changes, press Esc again to discard them".into();
+                } else {
+                    let scene = self.title_scene();
+                    self.switch_scene(scene, Transition::Fade);
+                }
+            },
+            VirtualKeyCode::Tab | VirtualKeyCode::B => editor.cycle_brush(true),
+            VirtualKeyCode::V => editor.cycle_brush(false),
+            VirtualKeyCode::Left => editor.resize(-1, 0),
+            VirtualKeyCode::Right => editor.resize(1, 0),
+            VirtualKeyCode::Up => editor.resize(0, 1),
+            VirtualKeyCode::Down => editor.resize(0, -1),
+            VirtualKeyCode::Z => editor.undo(),
+            VirtualKeyCode::Y => editor.redo(),
+            VirtualKeyCode::S => editor.save(),
+            VirtualKeyCode::P | VirtualKeyCode::Return => {
+                if editor.has_blocks() {
+                    self.start_play_test();
+                } else {
+                    editor.status = "Add some blocks before play-testing".into();
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn finish_scene(&mut self, is_victory: bool) -> Scene {
+        let score = self.state.score.total();
+        let qualifies = GAME_MODES.contains(&self.state.mode)
+            && self.high_scores.qualifies(&self.state.pack, self.state.mode.name(), score);
+        let best_before = self.personal_bests.best(&self.state.pack).map(|best| best.to_vec());
+        let mut is_new_best = false;
+        if let Some(ref run) = self.state.run {
+            // Only unassisted runs through every level count.
+            if run.splits.len() == self.state.levels.len() && !self.state.is_assisted {
+                is_new_best = self.personal_bests.record(&self.state.pack, &run.splits);
+                if let Err(err) = self.personal_bests.save() {
+                    eprintln!("Warning: cannot save personal bests: {}", err);
+                }
+            }
+        }
+        Scene::Finish(FinishState {
+            name_entry: if qualifies { Some(String::new()) } else { None },
+            best_before,
+            is_new_best,
+            is_victory,
+        })
+    }
+
+    pub fn on_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if self.spectating.is_some() {
+            return;
+        }
+        let origin = self.editor_origin();
+        if let Scene::Editor(ref mut editor) = *self.state.scenes.top_mut() {
+            match (button, state) {
+                (MouseButton::Left, ElementState::Pressed) => {
+                    let brush = editor.brush;
+                    editor.begin_stroke(brush);
+                },
+                (MouseButton::Right, ElementState::Pressed) => editor.begin_stroke(EMPTY),
+                (_, ElementState::Released) => editor.end_stroke(),
+                _ => (),
+            }
+            editor.paint(self.mouse_p - origin);
+            return;
+        }
+        if button != MouseButton::Left || state != ElementState::Pressed {
+            return;
+        }
+        if self.capturing.take().is_some() {
+            self.refresh_settings_menu();
+        }
+        let p = self.mouse_p;
+        let action = self.active_menus()
+            .and_then(|menus| menus.last_mut())
+            .and_then(|menu| menu.on_click(p));
+        if let Some(action) = action {
+            self.on_menu_action(action);
+        }
+    }
+
+    pub fn on_mouse_motion(&mut self, x: f32, y: f32) {
+        if self.spectating.is_some() {
+            return;
+        }
+        // Window coordinates grow downwards, ours grow upwards.
+        let p = Vec2::new(x, self.viewport.h - y);
+        let is_playing = match *self.state.scenes.top() {
+            Scene::Level(_) | Scene::Versus(_) => true,
+            _ => false,
+        };
+        if is_playing {
+            self.input.handle(&self.settings, InputEvent::MouseMotion { dx: p.x - self.mouse_p.x });
+        }
+        self.mouse_p = p;
+        let origin = self.editor_origin();
+        if let Scene::Editor(ref mut editor) = *self.state.scenes.top_mut() {
+            if editor.is_painting() {
+                editor.paint(p - origin);
+            }
+        }
+        if let Some(menu) = self.active_menus().and_then(|menus| menus.last_mut()) {
+            menu.on_mouse_motion(p);
+        }
+    }
+
+    pub fn on_key(&mut self, keycode: VirtualKeyCode, key_state: ElementState) {
+        if self.spectating.is_some() {
+            if keycode == VirtualKeyCode::Escape {
+                self.is_quit_requested = true;
+            }
+            return;
+        }
+        let pressed = key_state == ElementState::Pressed;
+        // Rebinding and typing initials want the raw key rather than an
+        // action. Scenes on their way in or out take no presses.
+        if pressed && !self.state.scenes.is_switching() && self.on_raw_key(keycode) {
+            return;
+        }
+        self.input.handle(&self.settings, InputEvent::Key { keycode, pressed });
+    }
+
+    fn on_raw_key(&mut self, keycode: VirtualKeyCode) -> bool {
+        if let Some(control) = self.capturing.take() {
+            if keycode != VirtualKeyCode::Escape {
+                self.settings.toggle_key(control, keycode);
+            }
+            self.on_settings_changed();
+            return true;
+        }
+        match *self.state.scenes.top() {
+            Scene::Editor(_) => self.on_editor_key(keycode),
+            Scene::Finish(_) => self.on_finish_key(keycode),
+            _ => false,
+        }
+    }
+
+    /// Typing initials for a new high score, or any key to go back to the
+    /// title.
+    fn on_finish_key(&mut self, keycode: VirtualKeyCode) -> bool {
+        let finish = match self.state.scenes.base {
+            Scene::Finish(ref mut finish) => finish,
+            _ => return false,
+        };
+        let submitted = match finish.name_entry {
+            Some(ref mut initials) => match keycode {
+                VirtualKeyCode::Back => {
+                    initials.pop();
+                    None
+                },
+                VirtualKeyCode::Return if !initials.is_empty() => Some(initials.clone()),
+                _ => {
+                    if let Some(c) = key_char(keycode) {
+                        if initials.len() < MAX_INITIALS {
+                            initials.push(c);
+                        }
+                    }
+                    None
+                },
+            },
+            None => {
+                let scene = self.title_scene();
+                self.switch_scene(scene, Transition::Fade);
+                return true;
+            },
+        };
+        if let Some(initials) = submitted {
+            finish.name_entry = None;
+            let score = self.state.score.total();
+            self.high_scores.insert(&self.state.pack, self.state.mode.name(), &initials, score, self.state.is_assisted);
+            if let Err(err) = self.high_scores.save() {
+                eprintln!("Warning: cannot save high scores: {}", err);
+            }
+        }
+        true
+    }
+
+    /// Applies the UI side of a frame's actions. Everything is interpreted in
+    /// the context the frame started in, so one key press never both picks a
+    /// menu item and acts on the scene it opens.
+    fn on_actions(&mut self, frame: &InputFrame) {
+        match *self.state.scenes.top() {
+            Scene::Start(_) | Scene::Pause(_) => self.on_menu_actions(frame),
+            Scene::Level(_) | Scene::Versus(_) => self.on_play_actions(frame),
+            Scene::LevelIntro(_) | Scene::LevelClear(_) | Scene::GameOver(_) => {
+                let is_skipped = frame.has(Action::MenuConfirm) || frame.has(Action::Launch);
+                let time = self.state.scenes.top_mut().wait_time().map_or(0., |(&mut time, _)| time);
+                if is_skipped && time >= SKIP_DELAY {
+                    self.move_on();
+                }
+            },
+            Scene::Finish(_) | Scene::Editor(_) => (),
+        }
+    }
+
+    fn on_menu_actions(&mut self, frame: &InputFrame) {
+        // Escape is both pause and back, so in a submenu it only goes back.
+        if let Scene::Pause(ref menus) = *self.state.scenes.top() {
+            if frame.has(Action::Pause) && menus.len() == 1 {
+                return self.on_menu_action(MenuAction::Resume);
+            }
+        }
+        for &action in frame.actions.iter() {
+            let menu = match self.active_menus().and_then(|menus| menus.last_mut()) {
+                Some(menu) => menu,
+                None => return,
+            };
+            let menu_action = match action {
+                Action::MenuPrev => menu.navigate(MenuNav::Prev),
+                Action::MenuNext => menu.navigate(MenuNav::Next),
+                Action::MenuConfirm => menu.navigate(MenuNav::Confirm),
+                Action::MenuBack => Some(MenuAction::Back),
+                _ => None,
+            };
+            if let Some(menu_action) = menu_action {
+                self.on_menu_action(menu_action);
+            }
+        }
+    }
+
+    fn on_play_actions(&mut self, frame: &InputFrame) {
+        let is_versus_over = match self.state.scenes.base {
+            Scene::Versus(ref versus) => versus.outcome.is_some(),
+            _ => false,
+        };
+        if is_versus_over {
+            if frame.has(Action::MenuConfirm) || frame.has(Action::MenuBack) {
+                let scene = self.title_scene();
+                self.switch_scene(scene, Transition::Fade);
+            }
+            return;
+        }
+        if !frame.has(Action::Pause) {
+            return;
+        }
+        if self.state.editor.is_some() {
+            return self.end_play_test();
+        }
+        let menu = if self.is_net_game() { self.net_pause_menu() } else { self.pause_menu() };
+        self.state.scenes.push(Scene::Pause(vec![menu]));
+    }
+
+    pub fn on_viewport_change(&mut self, w: u32, h: u32) {
+        /* We probably want to ignore resize
+        self.viewport.w = w as _;
+        self.viewport.h = h as _;
+        unsafe {
+            gl::Viewport(0, 0, w as _, h as _);
+        }
+        */
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        if let Some(scene) = self.state.scenes.step(dt) {
+            self.exit_scene(scene);
+            self.enter_scene();
+        }
+        // Spectators only watch, their input goes nowhere.
+        if self.spectating.is_some() {
+            self.step_spectating();
+            return self.update_audio(dt);
+        }
+        let mut events = Vec::new();
+        for source in self.gamepads.poll(dt, &mut events) {
+            self.input.remove_source(source);
+        }
+        for event in events {
+            self.input.handle(&self.settings, event);
+        }
+        let frame = self.input.take_frame(&self.settings);
+        // Scenes on their way in or out are frozen, actions meant for them
+        // are dropped, as are those that just changed the scene on top.
+        if !self.state.scenes.is_switching() {
+            let overlays = self.state.scenes.overlays.len();
+            self.on_actions(&frame);
+            if !self.state.scenes.is_switching() && self.state.scenes.overlays.len() == overlays {
+                self.pending.actions.extend(frame.actions);
+            }
+        }
+        // The simulation runs in fixed ticks. Actions from a frame without a
+        // tick wait for the next one.
+        self.pending.move_paddle = frame.move_paddle;
+        self.pending.player2_move_paddle = frame.player2_move_paddle;
+        self.tick_time = (self.tick_time + dt).min(MAX_TICKS_PER_STEP as f32 * TICK_DT);
+        while self.tick_time >= TICK_DT {
+            self.tick_time -= TICK_DT;
+            let frame = InputFrame {
+                move_paddle: self.pending.move_paddle,
+                player2_move_paddle: self.pending.player2_move_paddle,
+                actions: self.pending.actions.drain(..).collect(),
+            };
+            self.tick(&frame);
+        }
+        // Particles are only for show, they run on frame time. Overlays
+        // freeze them with the rest of the game, unless it is networked and
+        // carries on underneath.
+        let is_frozen = !self.state.scenes.overlays.is_empty() && !self.is_net_game();
+        match self.state.scenes.base {
+            Scene::Level(ref mut level) if !is_frozen => level.particles.step(dt),
+            Scene::Versus(ref mut versus) if !is_frozen => {
+                for field in versus.fields.iter_mut() {
+                    field.particles.step(dt);
+                }
+            },
+            _ => (),
+        }
+        if let Some(ref mut server) = self.broadcast {
+            server.accept();
+        }
+        if self.broadcast.as_ref().map_or(false, |server| server.spectator_count() > 0) {
+            let view = self.view();
+            if let Some(ref mut server) = self.broadcast {
+                server.broadcast(&view);
+            }
+        }
+        self.update_audio(dt);
+    }
+
+    /// The track for the current scene, from the level or the pack.
+    fn scene_music(&self) -> Option<&str> {
+        let manifest = &self.pack_manifest;
+        let track = match self.state.scenes.base {
+            Scene::Start(_) => &manifest.title_music,
+            Scene::Level(ref level) => if level.music.is_some() { &level.music } else { &manifest.level_music },
+            Scene::Versus(_) => &manifest.level_music,
+            Scene::Finish(_) => &manifest.finish_music,
+            Scene::Editor(_) => return None,
+            // Overlays never end up as the base.
+            Scene::Pause(_) | Scene::LevelIntro(_) | Scene::LevelClear(_) | Scene::GameOver(_) => return None,
+        };
+        track.as_ref().map(String::as_str)
+    }
+
+    fn update_audio(&mut self, dt: f32) {
+        let track = self.scene_music().map(String::from);
+        self.audio.music.play(track.as_ref().map(String::as_str));
+        self.audio.update(dt);
+    }
+
+    /// Advances the simulation by one tick of `TICK_DT`. Only the topmost
+    /// scene runs.
+    fn tick(&mut self, frame: &InputFrame) {
+        // Neither scene moves while one gives way to the other.
+        if self.state.scenes.is_switching() {
+            return;
+        }
+        self.scene_time += TICK_DT;
+        match *self.state.scenes.top() {
+            Scene::Level(_) => self.tick_level(frame),
+            Scene::Versus(_) => self.tick_versus(frame),
+            // The other side keeps playing, so pausing only stops local input.
+            Scene::Pause(_) if self.is_net_game() => self.tick_versus(&InputFrame::default()),
+            Scene::LevelIntro(_) | Scene::LevelClear(_) | Scene::GameOver(_) => self.tick_waiting(),
+            _ => (),
+        }
+    }
+
+    fn tick_versus(&mut self, frame: &InputFrame) {
+        let versus = match self.state.scenes.base {
+            Scene::Versus(ref mut versus) if versus.outcome.is_none() => versus,
+            _ => return,
+        };
+        for sound in step_versus_game(versus, frame, &self.settings, &self.theme) {
+            self.audio.play(sound);
+        }
+    }
+
+    fn tick_level(&mut self, frame: &InputFrame) {
+        let dt = TICK_DT;
+        let mut cleared = None;
+        let mut is_game_over = false;
+        let mut is_play_test_over = false;
+        if let Scene::Level(ref mut level) = self.state.scenes.base {
+            if let Some(ref mut run) = self.state.run {
+                run.level_ticks += 1;
+            }
+            apply_settings(level, &self.settings);
+            self.state.is_assisted |= self.settings.is_assisted();
+            if frame.has(Action::Launch) && level.is_launching_ball {
+                launch_ball(level);
+            }
+            if self.state.editor.is_some() {
+                is_play_test_over = is_level_cleared(level) || self.state.balls_left == 0;
+            } else if level.endless.is_some() {
+                is_game_over = step_endless(level, dt) || self.state.balls_left == 0;
+            } else if is_level_cleared(level) {
+                let bonus = level.par_time.map_or(0, |par_time| time_bonus(par_time, level.time));
+                let no_miss_bonus = if level.balls_lost == 0 { NO_MISS_BONUS } else { 0 };
+                self.state.score.time += bonus;
+                self.state.score.no_miss += no_miss_bonus;
+                if let Some(ref mut run) = self.state.run {
+                    run.split();
+                }
+                self.audio.play(Sound::LevelClear);
+                self.state.current_level += 1;
+                cleared = Some(LevelClearState {
+                    time: 0.,
+                    level_time: level.time,
+                    blocks_broken: level.blocks_broken,
+                    best_combo: level.best_combo,
+                    time_bonus: bonus,
+                    no_miss_bonus,
+                });
+            } else if self.state.balls_left == 0 {
+                is_game_over = true;
+            }
+            if cleared.is_none() && !is_game_over {
+                let events = step_level(level, &mut self.state.score, frame.move_paddle, &self.theme);
+                if events.is_ball_lost {
+                    self.state.balls_left -= 1;
+                }
+                for sound in events.sounds {
+                    self.audio.play(sound);
+                }
+            }
+        }
+        if is_play_test_over {
+            return self.end_play_test();
+        }
+        if let Some(cleared) = cleared {
+            self.state.scenes.push(Scene::LevelClear(cleared));
+        }
+        if is_game_over {
+            self.state.scenes.push(Scene::GameOver(GameOverState { time: 0. }));
+        }
+    }
+
+    /// Counts down the waiting screen on top.
+    fn tick_waiting(&mut self) {
+        let is_over = match self.state.scenes.top_mut().wait_time() {
+            Some((time, timeout)) => {
+                *time += TICK_DT;
+                *time >= timeout
+            },
+            None => false,
+        };
+        if is_over {
+            self.move_on();
+        }
+    }
+
+    /// Leaves the waiting screen on top: the level starts after its intro,
+    /// the next one comes in after a clear and the final score after a game
+    /// over or the last level.
+    fn move_on(&mut self) {
+        let is_cleared = match *self.state.scenes.top() {
+            Scene::LevelIntro(_) => {
+                self.state.scenes.pop();
+                return;
+            },
+            Scene::LevelClear(_) => true,
+            Scene::GameOver(_) => false,
+            _ => return,
+        };
+        if is_cleared && self.state.current_level < self.state.levels.len() {
+            let scene = load_level(&self.state.levels[self.state.current_level]);
+            self.switch_scene(scene, Transition::Iris);
+        } else {
+            let scene = self.finish_scene(is_cleared);
+            self.switch_scene(scene, Transition::Wipe);
+        }
+    }
+
+    pub fn render(&mut self) {
+        unsafe {
+            let background = self.theme.background;
+            gl::ClearColor(background.r, background.g, background.b, 0.);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        if let Some(sprite) = self.assets.art.background {
+            let (w, h) = (self.viewport.w, self.viewport.h);
+            self.renderer.begin_batch();
+            self.renderer.draw_sprite(self.assets.sprites.get(sprite), Vec2::default(), w, h, 0., Color::new(1., 1., 1., 1.));
+            self.renderer.end_batch(&self.viewport);
+        }
+        // Scenes draw with the rest of the game at hand, so the stack is lent
+        // out of it meanwhile. Overlays go over the scenes they freeze.
+        let scenes = mem::replace(&mut self.state.scenes, SceneStack::new(Scene::Start(Vec::new())));
+        for scene in scenes.iter() {
+            match *scene {
+                Scene::Start(ref menus) => self.render_title(menus),
+                Scene::Level(ref level) => self.render_level(level),
+                Scene::Versus(ref versus) => self.render_versus(versus),
+                Scene::Finish(ref finish) => self.render_finish(finish),
+                Scene::Editor(ref editor) => self.render_editor(editor),
+                Scene::Pause(ref menus) => {
+                    if let Some(menu) = menus.last() {
+                        draw_pause(&mut self.renderer, &self.font_lib, &self.assets, &self.viewport, menu);
+                    }
+                },
+                Scene::LevelIntro(ref intro) => self.render_level_intro(intro),
+                Scene::LevelClear(ref clear) => self.render_level_clear(clear),
+                Scene::GameOver(_) => self.render_game_over(),
+            }
+        }
+        scenes.draw_transition(&mut self.renderer, &self.viewport);
+        self.state.scenes = scenes;
+        if let Some(ref client) = self.spectating {
+            let status = if !client.is_connected {
+                format!("Lost the game at {}, press Esc to quit", client.addr)
+            } else if let Scene::Start(_) = self.state.scenes.base {
+                format!("Watching {}, waiting for a game to start...", client.addr)
+            } else {
+                format!("Watching {}", client.addr)
+            };
+            self.renderer.draw_text(
+                &self.viewport,
+                &status,
+                Vec2::new(24., self.viewport.h - 32.),
+                Color::new(1., 1., 1., 1.),
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+    }
+
+    fn render_title(&mut self, menus: &[Menu<MenuAction>]) {
+        let (title_offset, subtitle_offset, glow) = if self.settings.reduced_motion {
+            (Vec2::default(), Vec2::default(), 0.)
+        } else {
+            (
+                self.title_slide.value(TitleFx::Title, self.scene_time).unwrap_or_default(),
+                self.title_slide.value(TitleFx::Subtitle, self.scene_time).unwrap_or_default(),
+                self.title_pulse.looped_value((), self.scene_time).unwrap_or(0.),
+            )
+        };
+        let title_color = self.theme.title.lerp(self.theme.title_glow, glow);
+        self.renderer.draw_text(
+            &self.viewport,
+            "B R E A K O U T ! !",
+            Vec2::new(24., 760.) + title_offset,
+            title_color,
+            self.font_lib.get(self.assets.primary_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            "A small game written by Eugene Che ~~",
+            Vec2::new(24., 716.) + subtitle_offset,
+            Color::new(1., 1., 0., 1.), 
+            self.font_lib.get(self.assets.secondary_font)
+        );
+        if let Some(menu) = menus.last() {
+            draw_menu(&mut self.renderer, &self.font_lib, &self.assets, &self.viewport, menu);
+        }
+        self.renderer.draw_text(
+            &self.viewport,
+            "Use the arrow keys or mouse to choose, Enter to confirm...",
+            Vec2::new(24., 64.),
+            self.theme.prompt,
+            self.font_lib.get(self.assets.default_font)
+        );
+    }
+
+    fn render_finish(&mut self, finish: &FinishState) {
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Your final score is {}", self.state.score.total()),
+            Vec2::new(24., 256.),
+            self.theme.title,
+            self.font_lib.get(self.assets.primary_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            if finish.is_victory { "N I C E ! !" } else { "Better luck next time..." },
+            Vec2::new(24., 212.),
+            Color::new(1., 1., 0., 1.), 
+            self.font_lib.get(self.assets.secondary_font)
+        );
+        let breakdown = match self.state.run {
+            Some(ref run) => {
+                let mut text = split_table(&run.splits, finish.best_before.as_ref().map(|best| &best[..]));
+                text.push_str(&format!("\nTotal {}", format_ticks(run.total())));
+                if finish.is_new_best {
+                    text.push_str("\nNew personal best!");
+                }
+                text
+            },
+            None => self.state.score.breakdown().iter()
+                .map(|&(name, points)| format!("{}: {}", name, points))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        self.renderer.draw_text(
+            &self.viewport,
+            &breakdown,
+            Vec2::new(24., 512.),
+            self.theme.hud,
+            self.font_lib.get(self.assets.default_font)
+        );
+        if let Some(ref initials) = finish.name_entry {
+            let blanks = "_".repeat(MAX_INITIALS - initials.len());
+            self.renderer.draw_text(
+                &self.viewport,
+                &format!(
+                    "New high score{}! Enter your initials: {}{}",
+                    if self.state.is_assisted { " (assisted)" } else { "" },
+                    initials,
+                    blanks,
+                ),
+                Vec2::new(24., 128.),
+                Color::new(1., 1., 1., 1.), 
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+        self.renderer.draw_text(
+            &self.viewport,
+            if finish.name_entry.is_some() { "Press Enter to save..." } else { "Press any key to continue..." },
+            Vec2::new(24., 64.),
+            self.theme.prompt,
+            self.font_lib.get(self.assets.default_font)
+        );
+    }
+
+    /// Draws a field with its bottom left corner at `vp`.
+    fn draw_level(&mut self, level: &LevelState, vp: Vec2) {
+        // Sprites go under the shapes of a batch, so the field gets one of its own.
+        self.renderer.begin_batch();
+        draw_art(&mut self.renderer, &self.assets, self.assets.art.playfield, vp, level.w, level.h, self.theme.playfield);
+        self.renderer.end_batch(&self.viewport);
+        self.renderer.begin_batch();
+        for block in level.blocks[..level.invalid_block_start].iter() {
+            let art = block_art(block, &self.assets.art);
+            draw_art(&mut self.renderer, &self.assets, art, vp + block.r.p, block.r.w, block.r.h, block_fill(block, &self.theme));
+        }
+        for dying in level.dying_blocks.iter() {
+            let scale = dying.fx.value(BlockFx::Scale, level.time).unwrap_or(0.);
+            let alpha = dying.fx.value(BlockFx::Alpha, level.time).unwrap_or(0.);
+            let (w, h) = (scale * dying.r.w, scale * dying.r.h);
+            let p = dying.r.p + Vec2::new((dying.r.w - w) / 2., (dying.r.h - h) / 2.);
+            let color = Color::new(dying.color.r, dying.color.g, dying.color.b, alpha * dying.color.a);
+            self.renderer.draw_rectangle(vp + p, w, h, color);
+        }
+        level.particles.draw(&mut self.renderer, vp);
+        // The squash is only drawn, the paddle the ball bounces off keeps its size.
+        let squash = level.paddle_squash.as_ref()
+            .filter(|_| !self.settings.reduced_motion)
+            .and_then(|fx| fx.value((), level.time))
+            .unwrap_or(Vec2::new(1., 1.));
+        let (paddle_w, paddle_h) = (squash.x * level.paddle.r.w, squash.y * level.paddle.r.h);
+        draw_art(
+            &mut self.renderer,
+            &self.assets,
+            self.assets.art.paddle,
+            vp + level.paddle.r.p + Vec2::new((level.paddle.r.w - paddle_w) / 2., 0.),
+            paddle_w,
+            paddle_h,
+            self.theme.paddle,
+        );
+        let mut balls: Vec<Circle> = level.active_balls.iter().map(|ball| ball.c).collect();
+        if level.is_launching_ball {
+            balls.push(ball_on_paddle(level).0);
+        }
+        for c in balls {
+            match self.assets.art.ball {
+                // Rolls as it goes across.
+                Some(sprite) => self.renderer.draw_sprite(
+                    self.assets.sprites.get(sprite),
+                    vp + c.p + Vec2::new(-c.r, -c.r),
+                    2. * c.r,
+                    2. * c.r,
+                    -c.p.x / c.r,
+                    Color::new(1., 1., 1., 1.),
+                ),
+                None => self.renderer.draw_circle(vp + c.p, c.r, self.theme.ball),
+            }
+        }
+        self.renderer.end_batch(&self.viewport);
+
+        for popup in level.popups.iter() {
+            let font = self.font_lib.get(self.assets.default_font);
+            let measure = font.measure(&popup.text);
+            let offset = Vec2::new((measure.min.x - measure.max.x) / 2., 0.);
+            self.renderer.draw_text(
+                &self.viewport,
+                &popup.text,
+                vp + popup.p + offset,
+                Color::new(1., 1., 1., popup.alpha()),
+                font,
+            );
+        }
+    }
+
+    /// How far screen shake moves `level` as drawn, toned down or off as
+    /// the player has set it.
+    fn shake(&self, level: &LevelState) -> Vec2 {
+        if self.settings.reduced_motion {
+            Vec2::default()
+        } else {
+            self.settings.screen_shake * level.screen_p
+        }
+    }
+
+    fn render_level(&mut self, level: &LevelState) {
+        let vp = self.field_origin(level.w, level.h) + self.shake(level);
+        self.draw_level(level, vp);
+        if self.settings.aim_assist {
+            draw_aim(&mut self.renderer, &self.viewport, &self.theme, level, vp);
+        }
+
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Score: {}", self.state.score.total()),
+            Vec2::new(24., 64.),
+            self.theme.hud,
+            self.font_lib.get(self.assets.default_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Lives: {}", self.state.balls_left),
+            Vec2::new(24., 32.),
+            self.theme.hud,
+            self.font_lib.get(self.assets.default_font)
+        );
+
+        if let Some(ref run) = self.state.run {
+            let mut text = format!("Time {}\nLevel {}", format_ticks(run.total()), format_ticks(run.level_ticks));
+            let best = self.personal_bests.best(&self.state.pack);
+            if let (Some(&last), Some(best)) = (run.splits.last(), best) {
+                if let Some(&best_split) = best.get(run.splits.len() - 1) {
+                    text.push_str(&format!("\nLast split {}", format_delta(last, best_split)));
+                }
+            }
+            self.renderer.draw_text(
+                &self.viewport,
+                &text,
+                Vec2::new(self.viewport.w - 280., self.viewport.h - 40.),
+                self.theme.hud,
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+        if let Some(ref endless) = level.endless {
+            self.renderer.draw_text(
+                &self.viewport,
+                &format!("Rows: {}", endless.rows_added),
+                Vec2::new(24., 96.),
+                self.theme.hud,
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+        if self.state.editor.is_some() {
+            self.renderer.draw_text(
+                &self.viewport,
+                "Play-testing, press pause to return to the editor",
+                Vec2::new(24., self.viewport.h - 32.),
+                Color::new(1., 1., 1., 1.),
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+    }
+
+    fn render_level_intro(&mut self, intro: &LevelIntroState) {
+        draw_dim(&mut self.renderer, &self.viewport, 0.5);
+        let y = self.viewport.h / 2.;
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Level {}", intro.number),
+            Vec2::new(24., y + 48.),
+            Color::new(1., 1., 0., 1.),
+            self.font_lib.get(self.assets.primary_font)
+        );
+        if let Some(ref name) = intro.name {
+            self.renderer.draw_text(
+                &self.viewport,
+                name,
+                Vec2::new(24., y),
+                Color::new(1., 1., 1., 1.),
+                self.font_lib.get(self.assets.secondary_font)
+            );
+        }
+        if let Some(ref author) = intro.author {
+            self.renderer.draw_text(
+                &self.viewport,
+                &format!("by {}", author),
+                Vec2::new(24., y - 36.),
+                Color::new(0.6, 0.6, 0.6, 1.),
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+        self.renderer.draw_text(
+            &self.viewport,
+            "Press Enter to start...",
+            Vec2::new(24., y - 96.),
+            self.theme.prompt,
+            self.font_lib.get(self.assets.default_font)
+        );
+    }
+
+    /// The tally over the frozen level, with the score so far.
+    fn render_level_clear(&mut self, clear: &LevelClearState) {
+        draw_dim(&mut self.renderer, &self.viewport, 0.5);
+        let y = self.viewport.h / 2.;
+        self.renderer.draw_text(
+            &self.viewport,
+            "Level Clear!",
+            Vec2::new(24., y + 160.),
+            Color::new(1., 1., 0., 1.),
+            self.font_lib.get(self.assets.primary_font)
+        );
+        let mut tally = vec![
+            format!("Time: {}", format_ticks((clear.level_time * TICK_RATE as f32).round() as u32)),
+            format!("Blocks broken: {}", clear.blocks_broken),
+            format!("Best combo: {}", clear.best_combo),
+        ];
+        if clear.time_bonus > 0 {
+            tally.push(format!("Time bonus: +{}", clear.time_bonus));
+        }
+        if clear.no_miss_bonus > 0 {
+            tally.push(format!("No miss bonus: +{}", clear.no_miss_bonus));
+        }
+        self.renderer.draw_text(
+            &self.viewport,
+            &tally.join("\n"),
+            Vec2::new(24., y + 96.),
+            Color::new(1., 1., 1., 1.),
+            self.font_lib.get(self.assets.default_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Score: {}", self.state.score.total()),
+            Vec2::new(24., y - 96.),
+            self.theme.hud,
+            self.font_lib.get(self.assets.secondary_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            "Press Enter to continue...",
+            Vec2::new(24., y - 144.),
+            self.theme.prompt,
+            self.font_lib.get(self.assets.default_font)
+        );
+    }
+
+    fn render_game_over(&mut self) {
+        draw_dim(&mut self.renderer, &self.viewport, 0.7);
+        let y = self.viewport.h / 2.;
+        self.renderer.draw_text(
+            &self.viewport,
+            "G A M E   O V E R",
+            Vec2::new(24., y + 32.),
+            self.theme.title,
+            self.font_lib.get(self.assets.primary_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Score: {}", self.state.score.total()),
+            Vec2::new(24., y - 16.),
+            self.theme.hud,
+            self.font_lib.get(self.assets.secondary_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            "Press Enter to continue...",
+            Vec2::new(24., y - 64.),
+            self.theme.prompt,
+            self.font_lib.get(self.assets.default_font)
+        );
+    }
+
+    fn render_versus(&mut self, versus: &VersusState) {
+        let local = versus.net.as_ref().map(|net| net.local);
+        for (i, (field, player)) in versus.fields.iter().zip(versus.players.iter()).enumerate() {
+            let vp = self.versus_origin(i, field.w, field.h) + self.shake(field);
+            self.draw_level(field, vp);
+            // Networked opponents play without one, so neither side gets it.
+            if self.settings.aim_assist && versus.net.is_none() {
+                draw_aim(&mut self.renderer, &self.viewport, &self.theme, field, vp);
+            }
+            let name = if local == Some(i) { "You".into() } else { format!("Player {}", i + 1) };
+            self.renderer.draw_text(
+                &self.viewport,
+                &format!("{}  Score: {}  Lives: {}", name, player.score.total(), player.balls_left),
+                Vec2::new(vp.x, vp.y - 32.),
+                self.theme.hud,
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+        if let Some(ref outcome) = versus.outcome {
+            let result = match *outcome {
+                VersusOutcome::Won(i) if local == Some(i) => "You win!".into(),
+                VersusOutcome::Won(_) if local.is_some() => "You lose!".into(),
+                VersusOutcome::Won(i) => format!("Player {} wins!", i + 1),
+                VersusOutcome::Draw => "It's a draw!".into(),
+                VersusOutcome::Cleared => "Both fields cleared!".into(),
+                VersusOutcome::Defeated => "Game over!".into(),
+                VersusOutcome::NetError(ref err) => format!("Network game over, {}", err),
+            };
+            self.renderer.draw_text(
+                &self.viewport,
+                &result,
+                Vec2::new(24., self.viewport.h - 64.),
+                self.theme.title,
+                self.font_lib.get(self.assets.secondary_font)
+            );
+            self.renderer.draw_text(
+                &self.viewport,
+                "Press Enter to continue...",
+                Vec2::new(24., 32.),
+                self.theme.prompt,
+                self.font_lib.get(self.assets.default_font)
+            );
+        }
+    }
+
+    fn render_editor(&mut self, editor: &Editor) {
+        let (w, h) = editor.file.size();
+        let vp = self.field_origin(w, h);
+        let (grid_w, grid_h) = (editor.file.width(), editor.file.height());
+        self.renderer.begin_batch();
+        self.renderer.draw_rectangle(vp, w, h, self.theme.playfield);
+        for j in 0..grid_h {
+            for i in 0..grid_w {
+                let c = editor.file.cell(i, j);
+                if is_block(c) {
+                    let (x, y) = editor.file.cell_origin(i, j);
+                    self.renderer.draw_rectangle(vp + Vec2::new(x, y), BLOCK_W, BLOCK_H, block_color(c, &self.theme));
+                }
+            }
+        }
+        // Where the paddle starts, to judge the gap below the blocks.
+        self.renderer.draw_rectangle(
+            vp + Vec2::new(w / 2. - DEFAULT_PADDLE_RADIUS, BOTTOM_TO_PADDLE_BOTTOM),
+            DEFAULT_PADDLE_RADIUS * 2.,
+            DEFAULT_PADDLE_HEIGHT,
+            Color::new(0.5, 0., 0., 1.),
+        );
+        self.renderer.draw_rectangle(
+            Vec2::new(24., self.viewport.h - 64.),
+            BLOCK_W,
+            BLOCK_H,
+            block_color(editor.brush, &self.theme),
+        );
+        self.renderer.end_batch(&self.viewport);
+
+        self.renderer.begin_batch();
+        let grid_color = Color::new(0.2, 0.2, 0.2, 1.);
+        let (_, bottom) = editor.file.cell_origin(0, grid_h.saturating_sub(1));
+        for i in 0..grid_w + 1 {
+            let x = i as f32 * BLOCK_W;
+            self.renderer.draw_line(vp + Vec2::new(x, bottom), vp + Vec2::new(x, h), grid_color);
+        }
+        for j in 0..grid_h + 1 {
+            let y = bottom + j as f32 * BLOCK_H;
+            self.renderer.draw_line(vp + Vec2::new(0., y), vp + Vec2::new(w, y), grid_color);
+        }
+        self.renderer.end_batch(&self.viewport);
+
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("Brush: {}    Grid: {} x {}", editor.brush_label(), grid_w, grid_h),
+            Vec2::new(64., self.viewport.h - 64.),
+            Color::new(1., 1., 1., 1.),
+            self.font_lib.get(self.assets.default_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            &format!("{}{}", editor.status, if editor.is_dirty { " (unsaved)" } else { "" }),
+            Vec2::new(24., self.viewport.h - 32.),
+            Color::new(1., 1., 0., 1.),
+            self.font_lib.get(self.assets.default_font)
+        );
+        self.renderer.draw_text(
+            &self.viewport,
+            "Left click paints, right click erases, Tab/V brush, arrows resize\nZ undo, Y redo, P play-test, S save, Esc exit",
+            Vec2::new(24., 48.),
+            self.theme.prompt,
+            self.font_lib.get(self.assets.default_font)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use netplay::{CHECKSUM_INTERVAL, DEFAULT_INPUT_DELAY};
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn net_games_stay_in_sync() {
+        let settings = Settings::new("");
+        let result = net_check(5_000, DEFAULT_INPUT_DELAY, None, [&settings, &settings]);
+        assert!(result.is_ok(), "{}", result.err().unwrap());
+    }
+
+    #[test]
+    fn net_check_catches_a_desync() {
+        let settings = Settings::new("");
+        match net_check(1_000, DEFAULT_INPUT_DELAY, Some(100), [&settings, &settings]) {
+            Err(NetError::Desync { tick }) => assert!((100..=100 + CHECKSUM_INTERVAL).contains(&tick), "tick {}", tick),
+            Err(err) => panic!("expected a desync, got {}", err),
+            Ok(summary) => panic!("expected a desync, got {}", summary),
+        }
+    }
+
+    #[test]
+    fn local_settings_stay_out_of_net_games() {
+        let host = Settings::new("");
+        let mut client = Settings::new("");
+        client.paddle_acceleration = 160.;
+        client.reduced_motion = true;
+        client.screen_shake = 0.;
+        client.hit_stop = 0.;
+        client.ball_speed_up = 0.;
+        client.game_speed = 0.5;
+        client.aim_assist = true;
+        let result = net_check(5_000, DEFAULT_INPUT_DELAY, None, [&host, &client]);
+        assert!(result.is_ok(), "{}", result.err().unwrap());
+    }
+
+    fn game_state(mode: GameMode, base: Scene) -> State {
+        State {
+            score: Score::default(),
+            scenes: SceneStack::new(base),
+            balls_left: 2,
+            current_level: 1,
+            pack: "default".into(),
+            mode,
+            levels: Vec::new(),
+            editor: None,
+            run: None,
+            is_assisted: false,
+        }
+    }
+
+    fn finish_scene() -> Scene {
+        Scene::Finish(FinishState { name_entry: None, best_before: None, is_new_best: false, is_victory: false })
+    }
+
+    #[test]
+    fn restored_levels_keep_block_damage() {
+        let mut level = seeded_field(5, VERSUS_GRID_W, VERSUS_GRID_H, VERSUS_START_ROWS);
+        level.blocks[1].hit_points = 3;
+        level.blocks[1].hits = 2;
+        let snapshot = snapshot_level(&level);
+        let mut restored = seeded_field(5, VERSUS_GRID_W, VERSUS_GRID_H, VERSUS_START_ROWS);
+        restored.blocks[1].hit_points = 3;
+        restore_snapshot(&mut restored, &snapshot);
+        let hits = |level: &LevelState| level.blocks[..level.invalid_block_start].iter().map(|block| (block.id, block.hits)).collect::<Vec<_>>();
+        assert_eq!(hits(&restored), hits(&level));
+        assert!(hits(&restored).contains(&(level.blocks[1].id, 2)));
+    }
+
+    #[test]
+    fn only_the_campaign_clears_its_save() {
+        let path = env::temp_dir().join(format!("breakout-{}-save.txt", process::id()));
+        let campaign = game_state(GameMode::Campaign, Scene::Start(Vec::new()));
+        assert!(write_progress(&campaign, &path).is_some());
+        assert!(write_progress(&game_state(GameMode::Endless, Scene::Start(Vec::new())), &path).is_none());
+
+        for &mode in [GameMode::Endless, GameMode::TimeAttack].iter() {
+            assert!(!delete_progress(&game_state(mode, finish_scene()), &path));
+            let save = SaveGame::load(&path).expect("The campaign save survives");
+            assert_eq!((save.mode.as_str(), save.level, save.balls_left), ("campaign", 1, 2));
+        }
+        assert!(delete_progress(&game_state(GameMode::Campaign, finish_scene()), &path));
+        assert!(SaveGame::load(&path).is_none());
+    }
+
+    /// Asserts that a field read back from a view shows what `expected` does.
+    fn assert_same_view(read: &LevelState, expected: &LevelState) {
+        assert_eq!((read.w, read.h, read.is_launching_ball), (expected.w, expected.h, expected.is_launching_ball));
+        assert_eq!(read.screen_p, expected.screen_p);
+        assert_eq!(read.paddle.r, expected.paddle.r);
+        let circles = |level: &LevelState| level.active_balls.iter().map(|ball| ball.c).collect::<Vec<_>>();
+        assert_eq!(circles(read), circles(expected));
+        assert_eq!(read.invalid_block_start, expected.invalid_block_start);
+        for (a, b) in read.blocks.iter().zip(expected.blocks[..expected.invalid_block_start].iter()) {
+            assert_eq!(a.r, b.r);
+            assert_eq!(a.is_indestructible, b.is_indestructible);
+            if !b.is_indestructible {
+                assert_eq!(a.hit_points - a.hits, b.hit_points - b.hits);
+            }
+        }
+        let popups = |level: &LevelState| level.popups.iter().map(|popup| (popup.p, popup.age, popup.text.clone())).collect::<Vec<_>>();
+        assert_eq!(popups(read), popups(expected));
+    }
+
+    /// A field part way through a game, with a ball in flight, a damaged
+    /// block and popups with spaces in their text.
+    fn played_field(seed: u64) -> LevelState {
+        let mut level = seeded_field(seed, VERSUS_GRID_W, VERSUS_GRID_H, VERSUS_START_ROWS);
+        launch_ball(&mut level);
+        level.active_balls[0].c.p = Vec2::new(101.25, 57.5);
+        level.screen_p = Vec2::new(0.75, -1.5);
+        level.blocks[0].hits = level.blocks[0].hit_points - 1;
+        let mut popup = ScorePopup::new(Vec2::new(40., 300.5), "+30 x3 combo".into());
+        popup.age = 0.25;
+        level.popups.push(popup);
+        level.popups.push(ScorePopup::new(Vec2::new(8., 16.), "ROW!".into()));
+        level
+    }
+
+    #[test]
+    fn level_view_round_trips() {
+        let level = played_field(3);
+        let score = Score { blocks: 120, combo: 30, ..Score::default() };
+        let view = write_view(&Scene::Level(played_field(3)), &score, 2);
+        match read_view(&view) {
+            Ok((Scene::Level(ref read), ref read_score, 2)) => {
+                assert_eq!(read_score.total(), 150);
+                assert_same_view(read, &level);
+            },
+            Ok(_) => panic!("wrong scene from {}", view),
+            Err(err) => panic!("{} in {}", err, view),
+        }
+    }
+
+    #[test]
+    fn versus_view_round_trips() {
+        let mut versus = versus_state(5, false);
+        versus.fields = vec![played_field(5), played_field(6)];
+        versus.players[0].score.blocks = 40;
+        versus.players[1].balls_left = 1;
+        versus.outcome = Some(VersusOutcome::Won(1));
+        let view = write_view(&Scene::Versus(versus), &Score::default(), 0);
+        assert!(view.contains("outcome won 1\n"), "{}", view);
+        let read = match read_view(&view) {
+            Ok((Scene::Versus(read), _, _)) => read,
+            Ok(_) => panic!("wrong scene from {}", view),
+            Err(err) => panic!("{} in {}", err, view),
+        };
+        assert_same_view(&read.fields[0], &played_field(5));
+        assert_same_view(&read.fields[1], &played_field(6));
+        assert_eq!((read.players[0].score.total(), read.players[0].balls_left), (40, VERSUS_LIVES));
+        assert_eq!((read.players[1].score.total(), read.players[1].balls_left), (0, 1));
+        match read.outcome {
+            Some(VersusOutcome::Won(1)) => (),
+            _ => panic!("outcome lost in {}", view),
         }
     }
 }
@@ -9,13 +9,13 @@ pub struct LineSegment {
     pub to: Vec2,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Circle {
     pub p: Vec2,
     pub r: f32,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Rectangle {
     pub p: Vec2,
     pub w: f32,
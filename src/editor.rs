@@ -0,0 +1,200 @@
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use level::{
+    hit_points,
+    is_block,
+    LevelError,
+    LevelFile,
+    BLOCK_H,
+    BLOCK_W,
+    BOTTOM_TO_BLOCK_BOTTOM,
+    INDESTRUCTIBLE_BLOCK,
+    MAX_GRID_H,
+    MAX_GRID_W,
+    STATIC_BLOCK,
+};
+use math::Vec2;
+
+const MAX_UNDO: usize = 100;
+const NEW_LEVEL_W: usize = 24;
+const NEW_LEVEL_H: usize = 12;
+
+/// Level being edited, with its undo history.
+pub struct Editor {
+    pub path: PathBuf,
+    pub file: LevelFile,
+    /// Block painted by the left mouse button.
+    pub brush: char,
+    pub is_dirty: bool,
+    pub status: String,
+    undo: Vec<Vec<Vec<char>>>,
+    redo: Vec<Vec<Vec<char>>>,
+    /// Cell painted by the stroke in progress, and whether the grid was
+    /// saved for undo since it started.
+    stroke: Option<(char, bool)>,
+    /// Set after Escape with unsaved changes, so a second Escape discards.
+    pub is_exit_pending: bool,
+}
+
+/// Blocks the brush cycles through: static ones first, then every motion tag.
+fn brushes() -> Vec<char> {
+    let mut brushes = vec![STATIC_BLOCK, INDESTRUCTIBLE_BLOCK];
+    brushes.extend((b'2'..=b'9').map(|c| c as char));
+    brushes.extend((b'a'..=b'z').map(|c| c as char));
+    brushes
+}
+
+impl Editor {
+    /// Opens `path`, or starts a blank level there if it does not exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Editor, LevelError> {
+        let path = path.as_ref().to_path_buf();
+        let (file, status) = if path.exists() {
+            (LevelFile::load(&path)?, format!("Editing {}", path.display()))
+        } else {
+            (LevelFile::new(NEW_LEVEL_W, NEW_LEVEL_H), format!("New level {}", path.display()))
+        };
+        Ok(Editor {
+            path,
+            file,
+            brush: STATIC_BLOCK,
+            is_dirty: false,
+            status,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            stroke: None,
+            is_exit_pending: false,
+        })
+    }
+
+    pub fn cycle_brush(&mut self, forward: bool) {
+        let brushes = brushes();
+        let i = brushes.iter().position(|&c| c == self.brush).unwrap_or(0);
+        let len = brushes.len();
+        self.brush = brushes[if forward { (i + 1) % len } else { (i + len - 1) % len }];
+    }
+
+    /// Describes the brush, including the motion its tag has in this level.
+    pub fn brush_label(&self) -> String {
+        if self.brush == STATIC_BLOCK {
+            return "# (static)".into();
+        }
+        if self.brush == INDESTRUCTIBLE_BLOCK {
+            return "@ (indestructible)".into();
+        }
+        if self.brush.is_ascii_digit() {
+            return format!("{} ({} hits)", self.brush, hit_points(self.brush));
+        }
+        match self.file.moves.iter().find(|directive| directive.tag == self.brush) {
+            Some(directive) => format!("{} ({})", self.brush, directive.spec),
+            None => format!("{} (no motion)", self.brush),
+        }
+    }
+
+    /// The grid cell under `p`, given in level space.
+    pub fn cell_at(&self, p: Vec2) -> Option<(usize, usize)> {
+        let (w, h) = (self.file.width(), self.file.height());
+        let y = p.y - BOTTOM_TO_BLOCK_BOTTOM;
+        if p.x < 0. || y < 0. {
+            return None;
+        }
+        let i = (p.x / BLOCK_W) as usize;
+        let from_bottom = (y / BLOCK_H) as usize;
+        if i >= w || from_bottom >= h {
+            return None;
+        }
+        Some((i, h - from_bottom - 1))
+    }
+
+    fn record(&mut self) {
+        self.undo.push(self.file.grid.clone());
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.is_dirty = true;
+        self.is_exit_pending = false;
+    }
+
+    /// Starts painting `c`, which may be `EMPTY` to erase. The whole stroke
+    /// is undone in one step.
+    pub fn begin_stroke(&mut self, c: char) {
+        self.stroke = Some((c, false));
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.stroke = None;
+    }
+
+    pub fn is_painting(&self) -> bool {
+        self.stroke.is_some()
+    }
+
+    pub fn paint(&mut self, p: Vec2) {
+        let (c, is_recorded) = match self.stroke {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        let (i, j) = match self.cell_at(p) {
+            Some(cell) => cell,
+            None => return,
+        };
+        if self.file.cell(i, j) == c {
+            return;
+        }
+        if !is_recorded {
+            self.record();
+            self.stroke = Some((c, true));
+        }
+        self.file.set_cell(i, j, c);
+    }
+
+    /// Grows or shrinks the grid, keeping it between one cell and what fits
+    /// on screen.
+    pub fn resize(&mut self, dw: isize, dh: isize) {
+        let clamp = |n: usize, d: isize, max: usize| (n as isize + d).max(1).min(max as isize) as usize;
+        let w = clamp(self.file.width(), dw, MAX_GRID_W);
+        let h = clamp(self.file.height(), dh, MAX_GRID_H);
+        if (w, h) == (self.file.width(), self.file.height()) {
+            return;
+        }
+        self.record();
+        self.file.resize(w, h);
+        self.status = format!("Grid is {} x {}", w, h);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(grid) = self.undo.pop() {
+            let current = mem::replace(&mut self.file.grid, grid);
+            self.redo.push(current);
+            self.is_dirty = true;
+            self.status = "Undo".into();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(grid) = self.redo.pop() {
+            let current = mem::replace(&mut self.file.grid, grid);
+            self.undo.push(current);
+            self.is_dirty = true;
+            self.status = "Redo".into();
+        }
+    }
+
+    pub fn save(&mut self) {
+        match self.file.save(&self.path) {
+            Ok(()) => {
+                self.is_dirty = false;
+                self.status = format!("Saved {}", self.path.display());
+            },
+            Err(err) => {
+                eprintln!("Warning: cannot save {}: {}", self.path.display(), err);
+                self.status = format!("Cannot save: {}", err);
+            },
+        }
+    }
+
+    pub fn has_blocks(&self) -> bool {
+        self.file.grid.iter().any(|row| row.iter().any(|&c| is_block(c)))
+    }
+}
@@ -53,16 +53,16 @@ impl Font {
             let ci = c as u8;
             let ref glyph_info = self.glyph_infos[ci as usize];
             if glyph_info.has_bitmap {
-                if measure.min.x < cursor.x + glyph_info.x as f32 {
+                if measure.min.x > cursor.x + glyph_info.x as f32 {
                     measure.min.x = cursor.x + glyph_info.x as f32;
                 }
-                if measure.min.y < cursor.y + glyph_info.y as f32 - glyph_info.h as f32 {
+                if measure.min.y > cursor.y + glyph_info.y as f32 - glyph_info.h as f32 {
                     measure.min.y = cursor.y + glyph_info.y as f32 - glyph_info.h as f32;
                 }
-                if measure.max.x > cursor.x + glyph_info.x as f32 + glyph_info.w as f32 {
+                if measure.max.x < cursor.x + glyph_info.x as f32 + glyph_info.w as f32 {
                     measure.max.x = cursor.x + glyph_info.x as f32 + glyph_info.w as f32;
                 }
-                if measure.max.y > cursor.y + glyph_info.y as f32 {
+                if measure.max.y < cursor.y + glyph_info.y as f32 {
                     measure.max.y = cursor.y + glyph_info.y as f32;
                 }
             }
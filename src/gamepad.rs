@@ -0,0 +1,368 @@
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use input::{Action, Axis, InputEvent};
+
+// Event types and codes from linux/input-event-codes.h.
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const ABS_X: u16 = 0x00;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+const BTN_SOUTH: u16 = 0x130;
+const BTN_EAST: u16 = 0x131;
+const BTN_NORTH: u16 = 0x133;
+const BTN_START: u16 = 0x13b;
+
+/// Size of `struct input_event`: a `struct timeval` followed by type, code
+/// and value.
+pub const EVENT_SIZE: usize = 2 * mem::size_of::<usize>() + 8;
+
+const SCAN_INTERVAL: f32 = 2.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RawEvent {
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+/// Range reported by the kernel for an absolute axis. Values within `flat`
+/// of the center are treated as resting.
+#[derive(Clone, Copy, Debug)]
+pub struct AbsRange {
+    pub min: i32,
+    pub max: i32,
+    pub flat: i32,
+}
+
+/// One controller, read from any byte stream in the kernel's evdev format.
+/// Devices under `/dev/input` are the usual source, but a recorded dump
+/// (`cat /dev/input/eventN > pad.dump`) or a pipe works the same way.
+pub struct Gamepad<R> {
+    source: u32,
+    reader: R,
+    stick_range: AbsRange,
+    buffer: Vec<u8>,
+    hat_y: i32,
+}
+
+pub struct Gamepads {
+    devices: Vec<(PathBuf, Gamepad<File>)>,
+    next_source: u32,
+    since_scan: f32,
+}
+
+impl RawEvent {
+    pub fn parse(bytes: &[u8]) -> RawEvent {
+        let at = EVENT_SIZE - 8;
+        let u16_at = |i: usize| u16::from_ne_bytes([bytes[i], bytes[i + 1]]);
+        RawEvent {
+            kind: u16_at(at),
+            code: u16_at(at + 2),
+            value: i32::from_ne_bytes([bytes[at + 4], bytes[at + 5], bytes[at + 6], bytes[at + 7]]),
+        }
+    }
+}
+
+impl Default for AbsRange {
+    fn default() -> Self {
+        AbsRange { min: -32768, max: 32767, flat: 4096 }
+    }
+}
+
+impl AbsRange {
+    pub fn normalize(&self, value: i32) -> f32 {
+        let center = (self.min as f32 + self.max as f32) / 2.;
+        let half = (self.max as f32 - self.min as f32) / 2.;
+        let offset = value as f32 - center;
+        if half <= 0. || offset.abs() <= self.flat as f32 {
+            0.
+        } else {
+            (offset / half).max(-1.).min(1.)
+        }
+    }
+}
+
+impl<R: Read> Gamepad<R> {
+    pub fn new(source: u32, reader: R, stick_range: AbsRange) -> Self {
+        Gamepad { source, reader, stick_range, buffer: Vec::new(), hat_y: 0 }
+    }
+
+    /// The stick and the d-pad are separate sources so they add up rather
+    /// than overwrite each other.
+    fn hat_source(&self) -> u32 {
+        self.source | 1
+    }
+
+    pub fn translate(&mut self, raw: RawEvent, events: &mut Vec<InputEvent>) {
+        match (raw.kind, raw.code) {
+            (EV_ABS, ABS_X) => events.push(InputEvent::Analog {
+                source: self.source,
+                axis: Axis::MovePaddle,
+                value: self.stick_range.normalize(raw.value),
+            }),
+            (EV_ABS, ABS_HAT0X) => events.push(InputEvent::Analog {
+                source: self.hat_source(),
+                axis: Axis::MovePaddle,
+                value: raw.value.signum() as f32,
+            }),
+            (EV_ABS, ABS_HAT0Y) => {
+                let hat_y = raw.value.signum();
+                if hat_y != self.hat_y {
+                    match hat_y {
+                        -1 => events.push(InputEvent::Action(Action::MenuPrev)),
+                        1 => events.push(InputEvent::Action(Action::MenuNext)),
+                        _ => (),
+                    }
+                    self.hat_y = hat_y;
+                }
+            },
+            // Only presses, not releases (0) or autorepeat (2).
+            (EV_KEY, code) if raw.value == 1 => match code {
+                BTN_SOUTH => {
+                    events.push(InputEvent::Action(Action::Launch));
+                    events.push(InputEvent::Action(Action::MenuConfirm));
+                },
+                BTN_EAST => events.push(InputEvent::Action(Action::MenuBack)),
+                BTN_NORTH | BTN_START => events.push(InputEvent::Action(Action::Pause)),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    /// Reads everything available without blocking. An error means the
+    /// device is gone; running out of data, including end of file, is fine.
+    pub fn poll(&mut self, events: &mut Vec<InputEvent>) -> io::Result<()> {
+        let mut chunk = [0u8; EVENT_SIZE * 16];
+        loop {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        let whole = self.buffer.len() / EVENT_SIZE * EVENT_SIZE;
+        let bytes: Vec<u8> = self.buffer.drain(..whole).collect();
+        for raw in bytes.chunks(EVENT_SIZE) {
+            let raw = RawEvent::parse(raw);
+            self.translate(raw, events);
+        }
+        Ok(())
+    }
+}
+
+/// Whether sysfs says the device has gamepad buttons. The `key` capability
+/// is a bitmask printed as hex words, most significant first.
+fn is_gamepad(event_name: &str) -> bool {
+    let path = Path::new("/sys/class/input").join(event_name).join("device/capabilities/key");
+    let caps = match fs::read_to_string(path) {
+        Ok(caps) => caps,
+        Err(_) => return false,
+    };
+    let word_bits = mem::size_of::<usize>() * 8;
+    let words: Vec<&str> = caps.split_whitespace().rev().collect();
+    let bit = BTN_SOUTH as usize;
+    words.get(bit / word_bits)
+        .and_then(|word| u64::from_str_radix(word, 16).ok())
+        .map_or(false, |word| word & (1 << (bit % word_bits)) != 0)
+}
+
+#[cfg(target_os = "linux")]
+fn open_device(path: &Path) -> io::Result<(File, AbsRange)> {
+    use libc;
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let file = OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+    // EVIOCGABS(ABS_X) fills a struct input_absinfo:
+    // value, minimum, maximum, fuzz, flat, resolution.
+    let mut info = [0i32; 6];
+    let request = (2 << 30) | ((mem::size_of_val(&info) as u64) << 16) | (('E' as u64) << 8) | (0x40 + ABS_X as u64);
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), request as _, info.as_mut_ptr()) };
+    let range = if result == 0 && info[1] < info[2] {
+        AbsRange { min: info[1], max: info[2], flat: info[4] }
+    } else {
+        // Pipes and dumps have no axis info.
+        AbsRange::default()
+    };
+    Ok((file, range))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_device(path: &Path) -> io::Result<(File, AbsRange)> {
+    Ok((File::open(path)?, AbsRange::default()))
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Gamepads {
+            devices: Vec::new(),
+            next_source: 0,
+            // Scan on the first poll.
+            since_scan: SCAN_INTERVAL,
+        }
+    }
+
+    /// Adds a device or fake event stream regardless of what it looks like.
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let (file, range) = open_device(path)?;
+        // Sources are even so each pad has a spare odd id for its d-pad.
+        let source = self.next_source;
+        self.next_source += 2;
+        self.devices.push((path.to_path_buf(), Gamepad::new(source, file, range)));
+        Ok(())
+    }
+
+    /// Looks for gamepads plugged in since the last scan. Devices we are not
+    /// allowed to read are skipped quietly, they will be retried next time.
+    fn scan(&mut self) {
+        let entries = match fs::read_dir("/dev/input") {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            if !name.starts_with("event") || self.devices.iter().any(|&(ref p, _)| *p == path) {
+                continue;
+            }
+            if is_gamepad(&name) && self.open(&path).is_ok() {
+                eprintln!("Gamepad connected: {}", path.display());
+            }
+        }
+    }
+
+    /// Reads all connected pads, returning their input and the sources of any
+    /// pads that went away.
+    pub fn poll(&mut self, dt: f32, events: &mut Vec<InputEvent>) -> Vec<u32> {
+        self.since_scan += dt;
+        if self.since_scan >= SCAN_INTERVAL {
+            self.since_scan = 0.;
+            self.scan();
+        }
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.devices.len() {
+            if let Err(err) = self.devices[i].1.poll(events) {
+                let (path, pad) = self.devices.remove(i);
+                eprintln!("Gamepad disconnected: {} ({})", path.display(), err);
+                removed.push(pad.source);
+                removed.push(pad.hat_source());
+            } else {
+                i += 1;
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// `struct input_event` as the kernel writes it, with a zero timestamp.
+    fn event_bytes(kind: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut bytes = vec![0u8; EVENT_SIZE - 8];
+        bytes.extend_from_slice(&kind.to_ne_bytes());
+        bytes.extend_from_slice(&code.to_ne_bytes());
+        bytes.extend_from_slice(&value.to_ne_bytes());
+        bytes
+    }
+
+    fn stream(events: &[(u16, u16, i32)]) -> Vec<u8> {
+        events.iter().flat_map(|&(kind, code, value)| event_bytes(kind, code, value)).collect()
+    }
+
+    fn poll(bytes: Vec<u8>) -> Vec<InputEvent> {
+        let mut gamepad = Gamepad::new(4, Cursor::new(bytes), AbsRange::default());
+        let mut events = Vec::new();
+        gamepad.poll(&mut events).unwrap();
+        events
+    }
+
+    fn analog(source: u32, value: f32) -> InputEvent {
+        InputEvent::Analog { source, axis: Axis::MovePaddle, value }
+    }
+
+    #[test]
+    fn translates_buttons() {
+        let events = poll(stream(&[
+            (EV_KEY, BTN_SOUTH, 1),
+            (EV_KEY, BTN_SOUTH, 2),
+            (EV_KEY, BTN_SOUTH, 0),
+            (EV_KEY, BTN_EAST, 1),
+            (EV_KEY, BTN_START, 1),
+            (EV_KEY, BTN_NORTH, 1),
+        ]));
+        assert_eq!(events, vec![
+            InputEvent::Action(Action::Launch),
+            InputEvent::Action(Action::MenuConfirm),
+            InputEvent::Action(Action::MenuBack),
+            InputEvent::Action(Action::Pause),
+            InputEvent::Action(Action::Pause),
+        ]);
+    }
+
+    #[test]
+    fn translates_stick_and_d_pad() {
+        let events = poll(stream(&[
+            (EV_ABS, ABS_X, 32767),
+            (EV_ABS, ABS_X, -32768),
+            (EV_ABS, ABS_HAT0X, -1),
+            (EV_ABS, ABS_HAT0X, 0),
+            (EV_ABS, ABS_HAT0Y, 1),
+            (EV_ABS, ABS_HAT0Y, 1),
+            (EV_ABS, ABS_HAT0Y, 0),
+            (EV_ABS, ABS_HAT0Y, -1),
+        ]));
+        assert_eq!(events, vec![
+            analog(4, 1.),
+            analog(4, -1.),
+            analog(5, -1.),
+            analog(5, 0.),
+            InputEvent::Action(Action::MenuNext),
+            InputEvent::Action(Action::MenuPrev),
+        ]);
+    }
+
+    #[test]
+    fn stick_rests_in_the_dead_zone() {
+        let range = AbsRange::default();
+        assert_eq!(range.normalize(0), 0.);
+        assert_eq!(range.normalize(range.flat - 1), 0.);
+        assert_eq!(range.normalize(-range.flat), 0.);
+        assert!(range.normalize(range.flat + 100) > 0.);
+        assert!(range.normalize(-range.flat - 100) < 0.);
+        let events = poll(stream(&[(EV_ABS, ABS_X, 2000), (EV_ABS, ABS_X, 16384)]));
+        assert_eq!(events[0], analog(4, 0.));
+        match events[1] {
+            InputEvent::Analog { value, .. } => assert!((value - 0.5).abs() < 0.01, "{}", value),
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn buffers_events_split_across_reads() {
+        let bytes = stream(&[(EV_KEY, BTN_EAST, 1), (EV_KEY, BTN_SOUTH, 1)]);
+        let split = EVENT_SIZE + 5;
+        let mut gamepad = Gamepad::new(0, Cursor::new(bytes[..split].to_vec()), AbsRange::default());
+        let mut events = Vec::new();
+        gamepad.poll(&mut events).unwrap();
+        assert_eq!(events, vec![InputEvent::Action(Action::MenuBack)]);
+        gamepad.reader.get_mut().extend_from_slice(&bytes[split..]);
+        events.clear();
+        gamepad.poll(&mut events).unwrap();
+        assert_eq!(events, vec![InputEvent::Action(Action::Launch), InputEvent::Action(Action::MenuConfirm)]);
+        assert!(gamepad.buffer.is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+use level::{block_with_hit_points, LevelFile, EMPTY, INDESTRUCTIBLE_BLOCK, MAX_GRID_H, MAX_GRID_W};
+
+/// Par times are rounded up to a multiple of this many seconds.
+const PAR_STEP: f32 = 5.;
+/// Lattice spacing of the noise pattern, in cells.
+const NOISE_SCALE: f32 = 4.;
+
+/// The game itself only lays out symmetric and noise levels, mazes and
+/// picking a pattern by name are for breakout-gen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    Symmetric,
+    Noise,
+    #[allow(dead_code)]
+    Maze,
+}
+
+#[allow(dead_code)]
+pub const PATTERNS: &[Pattern] = &[Pattern::Symmetric, Pattern::Noise, Pattern::Maze];
+
+#[derive(Clone, Debug)]
+pub struct GeneratorParams {
+    pub pattern: Pattern,
+    pub seed: u64,
+    pub w: usize,
+    pub h: usize,
+    /// Share of open cells that get a block, 0..1.
+    pub density: f32,
+    /// Toughest block the level may have.
+    pub max_hit_points: i32,
+}
+
+/// Small xorshift generator, so levels come out the same for a seed on
+/// every platform.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+#[allow(dead_code)]
+impl Pattern {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pattern::Symmetric => "symmetric",
+            Pattern::Noise => "noise",
+            Pattern::Maze => "maze",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Pattern> {
+        PATTERNS.iter().cloned().find(|pattern| pattern.name() == name)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Zero would get stuck, and nearby seeds should not start alike.
+        let mut rng = Rng { state: seed ^ 0x9e37_79b9_7f4a_7c15 };
+        if rng.state == 0 {
+            rng.state = 1;
+        }
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Uniform in 0..1.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in 0..n.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+}
+
+impl GeneratorParams {
+    /// Parameters for `difficulty` in 0..1: harder levels are denser and
+    /// have tougher blocks.
+    pub fn for_difficulty(pattern: Pattern, seed: u64, difficulty: f32) -> Self {
+        let difficulty = difficulty.clamp(0., 1.);
+        GeneratorParams {
+            pattern,
+            seed,
+            w: 20,
+            h: 10,
+            density: 0.2 + 0.4 * difficulty,
+            max_hit_points: 1 + (difficulty * 3.).round() as i32,
+        }
+    }
+}
+
+/// Picks hit points for a block, with tougher blocks more likely towards the
+/// top where they are harder to reach.
+fn roll_hit_points(rng: &mut Rng, params: &GeneratorParams, j: usize) -> i32 {
+    let height_bias = 1. - j as f32 / params.h as f32;
+    let roll = rng.next_f32() * (0.5 + 0.5 * height_bias);
+    1 + (roll * params.max_hit_points as f32) as i32
+}
+
+/// Blocks mirrored left to right, and sometimes top to bottom as well.
+fn symmetric(rng: &mut Rng, params: &GeneratorParams, file: &mut LevelFile) {
+    let (w, h) = (params.w, params.h);
+    let mirror_v = rng.chance(0.3);
+    let rows = if mirror_v { h.div_ceil(2) } else { h };
+    for j in 0..rows {
+        for i in 0..w.div_ceil(2) {
+            if !rng.chance(params.density) {
+                continue;
+            }
+            let c = block_with_hit_points(roll_hit_points(rng, params, j));
+            for &(ci, cj) in [(i, j), (w - 1 - i, j)].iter() {
+                file.set_cell(ci, cj, c);
+                if mirror_v {
+                    file.set_cell(ci, h - 1 - cj, c);
+                }
+            }
+        }
+    }
+}
+
+/// Value noise: random values on a coarse lattice, smoothly interpolated,
+/// with blocks wherever the result falls under the density.
+fn noise(rng: &mut Rng, params: &GeneratorParams, file: &mut LevelFile) {
+    let lattice_w = (params.w as f32 / NOISE_SCALE) as usize + 2;
+    let lattice_h = (params.h as f32 / NOISE_SCALE) as usize + 2;
+    let lattice: Vec<Vec<f32>> = (0..lattice_h)
+        .map(|_| (0..lattice_w).map(|_| rng.next_f32()).collect())
+        .collect();
+    let smooth = |t: f32| t * t * (3. - 2. * t);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    for j in 0..params.h {
+        for i in 0..params.w {
+            let (x, y) = (i as f32 / NOISE_SCALE, j as f32 / NOISE_SCALE);
+            let (li, lj) = (x as usize, y as usize);
+            let (tx, ty) = (smooth(x - li as f32), smooth(y - lj as f32));
+            let top = lerp(lattice[lj][li], lattice[lj][li + 1], tx);
+            let bottom = lerp(lattice[lj + 1][li], lattice[lj + 1][li + 1], tx);
+            if lerp(top, bottom, ty) < params.density {
+                let c = block_with_hit_points(roll_hit_points(rng, params, j));
+                file.set_cell(i, j, c);
+            }
+        }
+    }
+}
+
+/// A maze carved by a randomized depth first search. Walls are one cell of
+/// indestructible blocks, corridors are two cells wide so the ball fits, and
+/// the bottom wall is left open so every corridor can be reached.
+fn maze(rng: &mut Rng, params: &GeneratorParams, file: &mut LevelFile) {
+    const STRIDE: usize = 3;
+    let (maze_w, maze_h) = ((params.w - 1) / STRIDE, (params.h - 1) / STRIDE);
+    if maze_w == 0 || maze_h == 0 {
+        return noise(rng, params, file);
+    }
+    let mut open = vec![vec![false; params.w]; params.h];
+    let mut visited = vec![vec![false; maze_w]; maze_h];
+    let carve = |open: &mut Vec<Vec<bool>>, i0: usize, j0: usize, w: usize, h: usize| {
+        for row in open[j0..j0 + h].iter_mut() {
+            for cell in row[i0..i0 + w].iter_mut() {
+                *cell = true;
+            }
+        }
+    };
+    let start = (rng.below(maze_w), maze_h - 1);
+    visited[start.1][start.0] = true;
+    carve(&mut open, start.0 * STRIDE + 1, start.1 * STRIDE + 1, 2, 2);
+    let mut stack = vec![start];
+    while let Some(&(mi, mj)) = stack.last() {
+        let mut neighbors = Vec::new();
+        if mi > 0 && !visited[mj][mi - 1] { neighbors.push((mi - 1, mj)); }
+        if mi + 1 < maze_w && !visited[mj][mi + 1] { neighbors.push((mi + 1, mj)); }
+        if mj > 0 && !visited[mj - 1][mi] { neighbors.push((mi, mj - 1)); }
+        if mj + 1 < maze_h && !visited[mj + 1][mi] { neighbors.push((mi, mj + 1)); }
+        if neighbors.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (ni, nj) = neighbors[rng.below(neighbors.len())];
+        visited[nj][ni] = true;
+        carve(&mut open, ni * STRIDE + 1, nj * STRIDE + 1, 2, 2);
+        // Knock down the wall between the two cells.
+        let (wi, wj) = (mi.min(ni) * STRIDE + 1, mj.min(nj) * STRIDE + 1);
+        if ni != mi {
+            carve(&mut open, wi + 2, wj, 1, 2);
+        } else {
+            carve(&mut open, wi, wj + 2, 2, 1);
+        }
+        stack.push((ni, nj));
+    }
+    let bottom = maze_h * STRIDE;
+    for cell in open[bottom].iter_mut() {
+        *cell = true;
+    }
+    for (j, row) in open.iter().enumerate() {
+        for (i, &is_open) in row.iter().enumerate() {
+            let c = if j > bottom {
+                EMPTY
+            } else if !is_open {
+                INDESTRUCTIBLE_BLOCK
+            } else if j < bottom && rng.chance(params.density) {
+                block_with_hit_points(roll_hit_points(rng, params, j))
+            } else {
+                EMPTY
+            };
+            file.set_cell(i, j, c);
+        }
+    }
+}
+
+/// Makes a level from `params`. The same parameters always give the same
+/// level. `build_level` turns the result into something playable, and its
+/// `Display` gives the `.level` text.
+pub fn generate(params: &GeneratorParams) -> LevelFile {
+    let mut params = params.clone();
+    params.w = params.w.clamp(3, MAX_GRID_W);
+    params.h = params.h.clamp(3, MAX_GRID_H);
+    params.density = params.density.clamp(0., 1.);
+    params.max_hit_points = params.max_hit_points.clamp(1, 9);
+    let mut rng = Rng::new(params.seed);
+    let mut file = LevelFile::new(params.w, params.h);
+    match params.pattern {
+        Pattern::Symmetric => symmetric(&mut rng, &params, &mut file),
+        Pattern::Noise => noise(&mut rng, &params, &mut file),
+        Pattern::Maze => maze(&mut rng, &params, &mut file),
+    }
+    // A level needs something to break.
+    if file.estimated_clear_time() == 0. {
+        let c = block_with_hit_points(1);
+        file.set_cell(params.w / 2, params.h - 1, c);
+    }
+    let par = file.estimated_clear_time();
+    file.par_time = Some((par / PAR_STEP).ceil().max(1.) * PAR_STEP);
+    file
+}
+
+/// One row of blocks, mirrored left to right, for modes that keep adding
+/// rows. Never empty, so every new row adds something to break.
+pub fn generate_row(rng: &mut Rng, w: usize, density: f32, max_hit_points: i32) -> Vec<char> {
+    let mut row = vec![EMPTY; w];
+    for i in 0..w.div_ceil(2) {
+        if rng.chance(density) {
+            let c = block_with_hit_points(1 + rng.below(max_hit_points.max(1) as usize) as i32);
+            row[i] = c;
+            row[w - 1 - i] = c;
+        }
+    }
+    if row.iter().all(|&c| c == EMPTY) {
+        let i = rng.below(w.div_ceil(2));
+        row[i] = block_with_hit_points(1);
+        row[w - 1 - i] = row[i];
+    }
+    row
+}
+
+/// A full row of single-hit blocks with `gaps` holes, sent to the other
+/// player in versus.
+pub fn generate_garbage_row(rng: &mut Rng, w: usize, gaps: usize) -> Vec<char> {
+    let mut row = vec![block_with_hit_points(1); w];
+    for _ in 0..gaps.min(w) {
+        row[rng.below(w)] = EMPTY;
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use level::is_destructible;
+
+    fn params(pattern: Pattern, seed: u64) -> GeneratorParams {
+        GeneratorParams::for_difficulty(pattern, seed, 0.5)
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_level() {
+        for &pattern in PATTERNS.iter() {
+            let (a, b) = (generate(&params(pattern, 7)), generate(&params(pattern, 7)));
+            assert_eq!(a.grid, b.grid, "{}", pattern.name());
+            assert_eq!(a.par_time, b.par_time, "{}", pattern.name());
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_levels() {
+        for &pattern in PATTERNS.iter() {
+            assert_ne!(generate(&params(pattern, 1)).grid, generate(&params(pattern, 2)).grid, "{}", pattern.name());
+        }
+    }
+
+    #[test]
+    fn mazes_can_be_cleared() {
+        for seed in 0..20 {
+            let file = generate(&params(Pattern::Maze, seed));
+            let (w, h) = (file.width(), file.height());
+            // The ball comes in from below and breaks through anything
+            // that is not indestructible.
+            let mut reached = vec![vec![false; w]; h];
+            let mut stack: Vec<(usize, usize)> = (0..w).map(|i| (i, h)).collect();
+            while let Some((i, j)) = stack.pop() {
+                for &(ni, nj) in [(i.wrapping_sub(1), j), (i + 1, j), (i, j.wrapping_sub(1)), (i, j + 1)].iter() {
+                    if ni < w && nj < h && !reached[nj][ni] && file.cell(ni, nj) != INDESTRUCTIBLE_BLOCK {
+                        reached[nj][ni] = true;
+                        stack.push((ni, nj));
+                    }
+                }
+            }
+            let mut blocks = 0;
+            for (j, row) in reached.iter().enumerate() {
+                for (i, &is_reached) in row.iter().enumerate() {
+                    if is_destructible(file.cell(i, j)) {
+                        blocks += 1;
+                        assert!(is_reached, "block at {}, {} sealed in with seed {}", i, j, seed);
+                    }
+                }
+            }
+            assert!(blocks > 0, "nothing to break with seed {}", seed);
+        }
+    }
+}
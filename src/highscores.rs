@@ -0,0 +1,174 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use storage::{read_records, write_atomic};
+
+const MAGIC: &str = "breakout-highscores";
+const VERSION: u32 = 2;
+pub const TABLE_SIZE: usize = 10;
+pub const MAX_INITIALS: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct HighScore {
+    pub pack: String,
+    pub mode: String,
+    pub initials: String,
+    pub score: i32,
+    /// Made with assists on, shown as such in the table.
+    pub is_assisted: bool,
+}
+
+/// High-score tables for every level pack and game mode, stored together in
+/// one file.
+pub struct HighScores {
+    path: PathBuf,
+    entries: Vec<HighScore>,
+    read_only: bool,
+}
+
+fn parse_entry(line: &str) -> Option<HighScore> {
+    let mut fields = line.split('\t');
+    let pack = fields.next()?;
+    let mode = fields.next()?;
+    let initials = fields.next()?;
+    let score = fields.next()?.parse().ok()?;
+    // Version 1 has no flag.
+    let is_assisted = match fields.next() {
+        None => false,
+        Some("assisted") => true,
+        Some(_) => return None,
+    };
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(HighScore {
+        pack: pack.into(),
+        mode: mode.into(),
+        initials: initials.into(),
+        score,
+        is_assisted,
+    })
+}
+
+pub fn sanitize_initials(initials: &str) -> String {
+    initials.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(MAX_INITIALS)
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+impl HighScores {
+    /// Loads the table at `path`. A missing file is an empty table; a file
+    /// written by a newer version is kept read-only so it is not clobbered.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let (mut entries, read_only) = read_records(&path, MAGIC, VERSION, "high score entry", parse_entry);
+        entries.sort_by(|a, b| b.score.cmp(&a.score));
+        HighScores { path, entries, read_only }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let mut contents = format!("{} {}\n", MAGIC, VERSION);
+        for entry in self.entries.iter() {
+            contents.push_str(&format!("{}\t{}\t{}\t{}", entry.pack, entry.mode, entry.initials, entry.score));
+            contents.push_str(if entry.is_assisted { "\tassisted\n" } else { "\n" });
+        }
+        write_atomic(&self.path, contents.as_bytes())
+    }
+
+    pub fn top(&self, pack: &str, mode: &str) -> Vec<&HighScore> {
+        self.entries.iter()
+            .filter(|entry| entry.pack == pack && entry.mode == mode)
+            .take(TABLE_SIZE)
+            .collect()
+    }
+
+    pub fn qualifies(&self, pack: &str, mode: &str, score: i32) -> bool {
+        let top = self.top(pack, mode);
+        score > 0 && (top.len() < TABLE_SIZE || top.iter().any(|entry| score > entry.score))
+    }
+
+    /// Adds a score, dropping whatever falls off the bottom of its table.
+    pub fn insert(&mut self, pack: &str, mode: &str, initials: &str, score: i32, is_assisted: bool) {
+        let i = self.entries.iter().position(|entry| entry.score < score).unwrap_or(self.entries.len());
+        self.entries.insert(i, HighScore {
+            pack: pack.into(),
+            mode: mode.into(),
+            initials: sanitize_initials(initials),
+            score,
+            is_assisted,
+        });
+        let mut count = 0;
+        self.entries.retain(|entry| {
+            if entry.pack != pack || entry.mode != mode {
+                return true;
+            }
+            count += 1;
+            count <= TABLE_SIZE
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("breakout-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn parses_version_1_lines() {
+        let entry = parse_entry("default\tcampaign\tABC\t1200").unwrap();
+        assert_eq!((entry.pack.as_str(), entry.mode.as_str(), entry.initials.as_str()), ("default", "campaign", "ABC"));
+        assert_eq!(entry.score, 1200);
+        assert!(!entry.is_assisted);
+        assert!(parse_entry("default\tcampaign\tABC\t1200\tassisted").unwrap().is_assisted);
+        assert!(parse_entry("default\tcampaign\tABC\t1200\tcheated").is_none());
+        assert!(parse_entry("default\tcampaign\tABC").is_none());
+    }
+
+    #[test]
+    fn assisted_entries_round_trip() {
+        let path = temp_path("highscores.txt");
+        let mut high_scores = HighScores::load(&path);
+        high_scores.insert("default", "endless", "abc", 500, true);
+        high_scores.insert("default", "endless", "xyz", 700, false);
+        high_scores.save().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let loaded = HighScores::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(contents.starts_with(&format!("{} {}\n", MAGIC, VERSION)));
+        let top = loaded.top("default", "endless");
+        assert_eq!(top.iter().map(|entry| (entry.initials.as_str(), entry.score, entry.is_assisted)).collect::<Vec<_>>(),
+                   vec![("XYZ", 700, false), ("ABC", 500, true)]);
+    }
+
+    #[test]
+    fn reads_older_versions_and_leaves_newer_ones_alone() {
+        let path = temp_path("old-highscores.txt");
+        fs::write(&path, format!("{} 1\ndefault\tcampaign\tABC\t300\n", MAGIC)).unwrap();
+        let mut high_scores = HighScores::load(&path);
+        assert_eq!(high_scores.top("default", "campaign").len(), 1);
+        high_scores.insert("default", "campaign", "DEF", 400, true);
+        high_scores.save().unwrap();
+        assert!(fs::read_to_string(&path).unwrap().starts_with(&format!("{} {}\n", MAGIC, VERSION)));
+
+        let newer = format!("{} {}\ndefault\tcampaign\tABC\t300\tassisted\tnew\n", MAGIC, VERSION + 1);
+        fs::write(&path, &newer).unwrap();
+        let mut high_scores = HighScores::load(&path);
+        assert!(high_scores.top("default", "campaign").is_empty());
+        high_scores.insert("default", "campaign", "DEF", 400, false);
+        high_scores.save().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents, newer);
+    }
+}
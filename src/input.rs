@@ -0,0 +1,153 @@
+use glutin::VirtualKeyCode;
+use std::collections::{HashMap, HashSet};
+
+use settings::{Control, Settings};
+
+/// Mouse travel in one frame, in pixels, that moves the paddle at full speed.
+const MOUSE_FULL_SCALE: f32 = 24.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Launch,
+    Player2Launch,
+    Pause,
+    MenuPrev,
+    MenuNext,
+    MenuConfirm,
+    MenuBack,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Axis {
+    MovePaddle,
+    Player2MovePaddle,
+}
+
+/// Raw input from any device, before it is mapped to actions and axes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Key { keycode: VirtualKeyCode, pressed: bool },
+    MouseMotion { dx: f32 },
+    /// Position of an analog control, identified by `source`, in -1..1.
+    Analog { source: u32, axis: Axis, value: f32 },
+    /// A device that produces actions directly.
+    Action(Action),
+}
+
+/// Everything the simulation needs from the player for one step.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    pub move_paddle: f32,
+    /// Second paddle, for local versus. Only keys drive it.
+    pub player2_move_paddle: f32,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Default)]
+pub struct InputState {
+    held_keys: HashSet<VirtualKeyCode>,
+    analog: HashMap<(u32, Axis), f32>,
+    mouse_dx: f32,
+    actions: Vec<Action>,
+}
+
+fn clamp_axis(value: f32) -> f32 {
+    value.max(-1.).min(1.)
+}
+
+impl InputFrame {
+    pub fn has(&self, action: Action) -> bool {
+        self.actions.contains(&action)
+    }
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState::default()
+    }
+
+    pub fn handle(&mut self, settings: &Settings, event: InputEvent) {
+        match event {
+            InputEvent::Key { keycode, pressed: false } => {
+                self.held_keys.remove(&keycode);
+            },
+            InputEvent::Key { keycode, pressed: true } => {
+                // Ignore key repeat, actions fire once per press.
+                if !self.held_keys.insert(keycode) {
+                    return;
+                }
+                if settings.is_bound(Control::Launch, keycode) {
+                    self.actions.push(Action::Launch);
+                }
+                if settings.is_bound(Control::Player2Launch, keycode) {
+                    self.actions.push(Action::Player2Launch);
+                }
+                if settings.is_bound(Control::Pause, keycode) {
+                    self.actions.push(Action::Pause);
+                }
+                match keycode {
+                    VirtualKeyCode::Up => self.actions.push(Action::MenuPrev),
+                    VirtualKeyCode::Down => self.actions.push(Action::MenuNext),
+                    VirtualKeyCode::Return | VirtualKeyCode::Space => self.actions.push(Action::MenuConfirm),
+                    VirtualKeyCode::Escape => self.actions.push(Action::MenuBack),
+                    _ => (),
+                }
+            },
+            InputEvent::MouseMotion { dx } => {
+                self.mouse_dx += dx * settings.mouse_sensitivity;
+            },
+            InputEvent::Analog { source, axis, value } => {
+                self.analog.insert((source, axis), clamp_axis(value));
+            },
+            InputEvent::Action(action) => self.actions.push(action),
+        }
+    }
+
+    /// Forgets an analog source, e.g. when its device is unplugged.
+    pub fn remove_source(&mut self, source: u32) {
+        self.analog.retain(|&(s, _), _| s != source);
+    }
+
+    fn key_axis(&self, settings: &Settings, negative: Control, positive: Control) -> f32 {
+        let is_held = |control| settings.keys(control).iter().any(|keycode| self.held_keys.contains(keycode));
+        let mut value = 0.;
+        if is_held(negative) {
+            value -= 1.;
+        }
+        if is_held(positive) {
+            value += 1.;
+        }
+        value
+    }
+
+    /// Every device's contribution added together, so opposite inputs cancel
+    /// out, then clamped to -1..1.
+    pub fn axis(&self, settings: &Settings, axis: Axis) -> f32 {
+        let mut value = self.analog.iter()
+            .filter(|&(&(_, a), _)| a == axis)
+            .map(|(_, &v)| v)
+            .sum::<f32>();
+        match axis {
+            Axis::MovePaddle => {
+                value += self.key_axis(settings, Control::MoveLeft, Control::MoveRight);
+                value += self.mouse_dx / MOUSE_FULL_SCALE;
+            },
+            Axis::Player2MovePaddle => {
+                value += self.key_axis(settings, Control::Player2MoveLeft, Control::Player2MoveRight);
+            },
+        }
+        clamp_axis(value)
+    }
+
+    /// Collects input for the next step, consuming queued actions and mouse
+    /// motion.
+    pub fn take_frame(&mut self, settings: &Settings) -> InputFrame {
+        let frame = InputFrame {
+            move_paddle: self.axis(settings, Axis::MovePaddle),
+            player2_move_paddle: self.axis(settings, Axis::Player2MovePaddle),
+            actions: self.actions.drain(..).collect(),
+        };
+        self.mouse_dx = 0.;
+        frame
+    }
+}
@@ -0,0 +1,35 @@
+use glutin::VirtualKeyCode;
+use glutin::VirtualKeyCode::*;
+
+/// Keys that can be named in the settings file, by their glutin names.
+const NAMED_KEYS: &[VirtualKeyCode] = &[
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Insert, Home, Delete, End, PageDown, PageUp,
+    Left, Up, Right, Down,
+    Back, Return, Space, Tab,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadEnter, Add, Subtract, Multiply, Divide, Decimal,
+    Apostrophe, Backslash, Comma, Equals, Grave, LBracket, Minus, Period, RBracket, Semicolon, Slash,
+    LAlt, LControl, LShift, RAlt, RControl, RShift,
+];
+
+pub fn key_name(keycode: VirtualKeyCode) -> String {
+    format!("{:?}", keycode)
+}
+
+pub fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    NAMED_KEYS.iter().cloned().find(|&keycode| key_name(keycode).eq_ignore_ascii_case(name))
+}
+
+/// The letter or digit printed on `keycode`, if any.
+pub fn key_char(keycode: VirtualKeyCode) -> Option<char> {
+    let name = key_name(keycode);
+    let name = name.trim_start_matches("Key");
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => Some(c),
+        _ => None,
+    }
+}
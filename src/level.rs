@@ -0,0 +1,269 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use motion::Motion;
+use storage::write_atomic;
+
+pub const BLOCK_W: f32 = 24.;
+pub const BLOCK_H: f32 = 16.;
+pub const BOTTOM_TO_PADDLE_BOTTOM: f32 = 32.;
+pub const BOTTOM_TO_BLOCK_BOTTOM: f32 = 256.;
+pub const DEFAULT_PADDLE_RADIUS: f32 = 48.;
+pub const DEFAULT_PADDLE_HEIGHT: f32 = 16.;
+pub const BALL_RADIUS: f32 = 8.;
+pub const BALL_SPEED: f32 = 300.;
+/// Size of the window, which the playfield has to fit in.
+pub const MAX_FIELD_W: f32 = 900.;
+pub const MAX_FIELD_H: f32 = 900.;
+pub const MAX_GRID_W: usize = (MAX_FIELD_W / BLOCK_W) as usize;
+pub const MAX_GRID_H: usize = ((MAX_FIELD_H - BOTTOM_TO_BLOCK_BOTTOM) / BLOCK_H) as usize;
+
+/// Grid cell with no block.
+pub const EMPTY: char = ' ';
+/// Grid cell with a block that does not move.
+pub const STATIC_BLOCK: char = '#';
+/// Grid cell with a block the ball bounces off but cannot break.
+pub const INDESTRUCTIBLE_BLOCK: char = '@';
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(PathBuf, io::Error),
+    Parse { line: usize, message: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct MoveDirective {
+    pub tag: char,
+    /// Kept as written so saving does not reformat it.
+    pub spec: String,
+    pub motion: Motion,
+}
+
+/// A level as it is stored on disk: directives followed by the block grid,
+/// top row first.
+#[derive(Clone, Debug, Default)]
+pub struct LevelFile {
+    /// Shown on the card before the level starts.
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub par_time: Option<f32>,
+    /// Track played during the level, a file in `res/music`.
+    pub music: Option<String>,
+    pub moves: Vec<MoveDirective>,
+    pub grid: Vec<Vec<char>>,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LevelError::Io(ref path, ref err) => write!(f, "{}: {}", path.display(), err),
+            LevelError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+/// Whether `c` in the grid is a block. Lowercase letters are blocks that
+/// move with their tag's motion, digits from 2 are blocks that take that many
+/// hits to break.
+pub fn is_block(c: char) -> bool {
+    c == STATIC_BLOCK || c == INDESTRUCTIBLE_BLOCK || c.is_ascii_lowercase() || ('2'..='9').contains(&c)
+}
+
+/// Hits it takes to break a block drawn as `c`, zero if it never breaks.
+pub fn hit_points(c: char) -> i32 {
+    match c {
+        INDESTRUCTIBLE_BLOCK => 0,
+        '2'..='9' => c as i32 - '0' as i32,
+        _ => 1,
+    }
+}
+
+/// The grid cell for a static block with `hit_points`, clamped to what the
+/// format can express.
+pub fn block_with_hit_points(hit_points: i32) -> char {
+    match hit_points {
+        i32::MIN..=1 => STATIC_BLOCK,
+        2..=8 => (b'0' + hit_points as u8) as char,
+        _ => '9',
+    }
+}
+
+/// Whether the ball can break a block drawn as `c`.
+pub fn is_destructible(c: char) -> bool {
+    is_block(c) && c != INDESTRUCTIBLE_BLOCK
+}
+
+impl LevelFile {
+    pub fn new(w: usize, h: usize) -> Self {
+        LevelFile { name: None, author: None, par_time: None, music: None, moves: Vec::new(), grid: vec![vec![EMPTY; w]; h] }
+    }
+
+    pub fn parse(text: &str) -> Result<LevelFile, LevelError> {
+        let mut level = LevelFile::default();
+        for (n, line) in text.lines().enumerate() {
+            let error = |message: String| LevelError::Parse { line: n + 1, message };
+            // Lines starting with '!' are directives, everything else is grid.
+            if !line.starts_with('!') {
+                level.grid.push(line.chars().collect());
+                continue;
+            }
+            let mut parts = line[1..].splitn(2, char::is_whitespace);
+            match (parts.next(), parts.next()) {
+                (Some("move"), Some(rest)) => {
+                    let rest = rest.trim_start();
+                    let tag = rest.chars().next().filter(|c| c.is_ascii_lowercase())
+                        .ok_or_else(|| error("move directive must name a block tag a-z".into()))?;
+                    let spec = rest[1..].trim().to_string();
+                    let motion = Motion::parse(&spec).map_err(|err| error(format!("bad motion: {}", err)))?;
+                    if level.motion_index(tag).is_some() {
+                        return Err(error(format!("tag '{}' already has a motion", tag)));
+                    }
+                    level.moves.push(MoveDirective { tag, spec, motion });
+                },
+                (Some("par"), Some(rest)) => {
+                    let par = rest.trim().parse::<f32>().ok().filter(|&par| par > 0.)
+                        .ok_or_else(|| error(format!("invalid par time '{}'", rest.trim())))?;
+                    level.par_time = Some(par);
+                },
+                (Some("music"), Some(rest)) if !rest.trim().is_empty() => {
+                    level.music = Some(rest.trim().to_string());
+                },
+                (Some("name"), Some(rest)) if !rest.trim().is_empty() => {
+                    level.name = Some(rest.trim().to_string());
+                },
+                (Some("author"), Some(rest)) if !rest.trim().is_empty() => {
+                    level.author = Some(rest.trim().to_string());
+                },
+                _ => return Err(error(format!("unknown directive '{}'", line))),
+            }
+        }
+        Ok(level)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<LevelFile, LevelError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| LevelError::Io(path.to_path_buf(), err))?;
+        LevelFile::parse(&text)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_atomic(path, self.to_string().as_bytes())
+    }
+
+    fn motion_index(&self, tag: char) -> Option<usize> {
+        self.moves.iter().position(|directive| directive.tag == tag)
+    }
+
+    /// The motion for blocks tagged `tag`. Tags without a directive stay put.
+    pub fn motion(&self, tag: char) -> Motion {
+        self.motion_index(tag).map(|i| self.moves[i].motion.clone()).unwrap_or_default()
+    }
+
+    /// Width of the widest row, which is the width of the playfield.
+    pub fn width(&self) -> usize {
+        self.grid.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    pub fn cell(&self, i: usize, j: usize) -> char {
+        self.grid.get(j).and_then(|row| row.get(i)).cloned().unwrap_or(EMPTY)
+    }
+
+    /// Sets a cell, padding its row out to reach it. Returns whether
+    /// anything changed.
+    pub fn set_cell(&mut self, i: usize, j: usize, c: char) -> bool {
+        if j >= self.grid.len() || self.cell(i, j) == c {
+            return false;
+        }
+        let row = &mut self.grid[j];
+        if row.len() <= i {
+            row.resize(i + 1, EMPTY);
+        }
+        row[i] = c;
+        true
+    }
+
+    /// Pads or cuts every row to `w` columns and adds or removes rows at the
+    /// top, so the bottom rows stay where they are relative to the paddle.
+    pub fn resize(&mut self, w: usize, h: usize) {
+        for row in self.grid.iter_mut() {
+            row.resize(w, EMPTY);
+        }
+        if h < self.grid.len() {
+            let extra = self.grid.len() - h;
+            self.grid.drain(..extra);
+        } else {
+            let extra = h - self.grid.len();
+            for _ in 0..extra {
+                self.grid.insert(0, vec![EMPTY; w]);
+            }
+        }
+    }
+
+    /// Grid position of the bottom left corner of a cell, in level space.
+    pub fn cell_origin(&self, i: usize, j: usize) -> (f32, f32) {
+        (i as f32 * BLOCK_W, (self.height() - j - 1) as f32 * BLOCK_H + BOTTOM_TO_BLOCK_BOTTOM)
+    }
+
+    /// Assumes every hit takes its own trip up from the paddle and back at
+    /// launch speed. Real play clears several blocks a trip but also misses,
+    /// so this is a ballpark figure to compare par times against.
+    pub fn estimated_clear_time(&self) -> f32 {
+        let paddle_top = BOTTOM_TO_PADDLE_BOTTOM + DEFAULT_PADDLE_HEIGHT;
+        let mut time = 0.;
+        for j in 0..self.height() {
+            for i in 0..self.width() {
+                let c = self.cell(i, j);
+                if is_destructible(c) {
+                    let (_, y) = self.cell_origin(i, j);
+                    time += hit_points(c) as f32 * 2. * (y - paddle_top - BALL_RADIUS) / BALL_SPEED;
+                }
+            }
+        }
+        time
+    }
+
+    /// Size of the playfield in level space.
+    pub fn size(&self) -> (f32, f32) {
+        (self.width() as f32 * BLOCK_W, self.height() as f32 * BLOCK_H + BOTTOM_TO_BLOCK_BOTTOM)
+    }
+}
+
+impl fmt::Display for LevelFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref name) = self.name {
+            writeln!(f, "!name {}", name)?;
+        }
+        if let Some(ref author) = self.author {
+            writeln!(f, "!author {}", author)?;
+        }
+        if let Some(par) = self.par_time {
+            writeln!(f, "!par {}", par)?;
+        }
+        if let Some(ref music) = self.music {
+            writeln!(f, "!music {}", music)?;
+        }
+        for directive in self.moves.iter() {
+            writeln!(f, "!move {} {}", directive.tag, directive.spec)?;
+        }
+        let width = self.width();
+        let is_blank = |row: &Vec<char>| row.iter().all(|c| c.is_whitespace());
+        let has_blocks = !self.grid.iter().all(&is_blank);
+        for row in self.grid.iter() {
+            // Blank rows are left empty, the rest are padded to a rectangle.
+            // An empty level keeps its blank rows so its width survives.
+            let line: String = row.iter().collect();
+            if has_blocks && is_blank(row) {
+                writeln!(f)?;
+            } else {
+                writeln!(f, "{:1$}", line, width)?;
+            }
+        }
+        Ok(())
+    }
+}
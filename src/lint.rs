@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use level::{
+    is_block,
+    is_destructible,
+    LevelError,
+    LevelFile,
+    BALL_RADIUS,
+    BLOCK_H,
+    BLOCK_W,
+    BOTTOM_TO_PADDLE_BOTTOM,
+    DEFAULT_PADDLE_HEIGHT,
+    EMPTY,
+    INDESTRUCTIBLE_BLOCK,
+    MAX_GRID_H,
+    MAX_GRID_W,
+};
+use math::Vec2;
+
+/// How long moving blocks are followed when looking for where they go.
+const MOTION_SAMPLE_TIME: f32 = 60.;
+const MOTION_SAMPLE_STEP: f32 = 1. / 30.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Line in the file, for problems tied to one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub width: usize,
+    pub height: usize,
+    pub blocks: usize,
+    pub indestructible: usize,
+    pub moving: usize,
+    pub par_time: Option<f32>,
+    /// Rough time to clear the level, see `LevelFile::estimated_clear_time`.
+    pub estimated_clear_time: f32,
+}
+
+pub struct Report {
+    pub path: PathBuf,
+    pub issues: Vec<Issue>,
+    /// Missing when the level could not be read.
+    pub stats: Option<Stats>,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl Issue {
+    fn error<S: Into<String>>(message: S) -> Self {
+        Issue { severity: Severity::Error, line: None, message: message.into() }
+    }
+
+    fn warning<S: Into<String>>(message: S) -> Self {
+        Issue { severity: Severity::Warning, line: None, message: message.into() }
+    }
+}
+
+/// Lowest and highest offsets of each tag's motion.
+fn motion_extents(file: &LevelFile) -> HashMap<char, (Vec2, Vec2)> {
+    let mut extents = HashMap::new();
+    for directive in file.moves.iter() {
+        let mut min = directive.motion.offset(0.);
+        let mut max = min;
+        let mut t = 0.;
+        while t < MOTION_SAMPLE_TIME {
+            let offset = directive.motion.offset(t);
+            min = Vec2::new(min.x.min(offset.x), min.y.min(offset.y));
+            max = Vec2::new(max.x.max(offset.x), max.y.max(offset.y));
+            t += MOTION_SAMPLE_STEP;
+        }
+        extents.insert(directive.tag, (min, max));
+    }
+    extents
+}
+
+/// Breakable blocks the ball can never reach, walled in by indestructible
+/// blocks. The ball comes in from below the grid and can pass through
+/// anything it is able to break.
+fn sealed_blocks(file: &LevelFile) -> Vec<(usize, usize)> {
+    let (w, h) = (file.width(), file.height());
+    let is_open = |i: usize, j: usize| file.cell(i, j) != INDESTRUCTIBLE_BLOCK;
+    let mut reached = vec![vec![false; w]; h];
+    let mut stack = Vec::new();
+    if h > 0 {
+        for i in (0..w).filter(|&i| is_open(i, h - 1)) {
+            reached[h - 1][i] = true;
+            stack.push((i, h - 1));
+        }
+    }
+    while let Some((i, j)) = stack.pop() {
+        let neighbors = [(i.wrapping_sub(1), j), (i + 1, j), (i, j.wrapping_sub(1)), (i, j + 1)];
+        for &(ni, nj) in neighbors.iter() {
+            if ni < w && nj < h && !reached[nj][ni] && is_open(ni, nj) {
+                reached[nj][ni] = true;
+                stack.push((ni, nj));
+            }
+        }
+    }
+    let mut sealed = Vec::new();
+    for (j, row) in reached.iter().enumerate() {
+        for (i, &is_reached) in row.iter().enumerate() {
+            if is_destructible(file.cell(i, j)) && !is_reached {
+                sealed.push((i, j));
+            }
+        }
+    }
+    sealed
+}
+
+pub fn lint_level(file: &LevelFile) -> (Vec<Issue>, Stats) {
+    let mut issues = Vec::new();
+    let (w, h) = (file.width(), file.height());
+    let mut stats = Stats {
+        width: w,
+        height: h,
+        par_time: file.par_time,
+        estimated_clear_time: file.estimated_clear_time(),
+        ..Stats::default()
+    };
+
+    if w > MAX_GRID_W {
+        issues.push(Issue::error(format!("grid is {} cells wide, the playfield fits {}", w, MAX_GRID_W)));
+    }
+    if h > MAX_GRID_H {
+        issues.push(Issue::error(format!("grid is {} rows tall, the playfield fits {}", h, MAX_GRID_H)));
+    }
+    // Blank rows are spacing, anything else should span the whole grid.
+    for (j, row) in file.grid.iter().enumerate() {
+        if row.iter().any(|c| !c.is_whitespace()) && row.len() != w {
+            issues.push(Issue::warning(format!("row {} is {} cells wide, expected {}", j + 1, row.len(), w)));
+        }
+    }
+
+    let mut unknown = Vec::new();
+    let mut tags = Vec::new();
+    for row in file.grid.iter() {
+        for &c in row.iter() {
+            if is_block(c) {
+                stats.blocks += 1;
+                if c == INDESTRUCTIBLE_BLOCK {
+                    stats.indestructible += 1;
+                }
+                if c.is_ascii_lowercase() {
+                    stats.moving += 1;
+                    if !tags.contains(&c) {
+                        tags.push(c);
+                    }
+                }
+            } else if c != EMPTY && c != '.' && !unknown.contains(&c) {
+                unknown.push(c);
+            }
+        }
+    }
+    for c in unknown {
+        issues.push(Issue::warning(format!("unknown cell '{}' is treated as empty", c)));
+    }
+    for &tag in tags.iter() {
+        if !file.moves.iter().any(|directive| directive.tag == tag) {
+            issues.push(Issue::warning(format!("blocks tagged '{}' have no !move directive and stay put", tag)));
+        }
+    }
+    for directive in file.moves.iter() {
+        if !tags.contains(&directive.tag) {
+            issues.push(Issue::warning(format!("!move {} is not used by any block", directive.tag)));
+        }
+    }
+    if stats.blocks == stats.indestructible {
+        issues.push(Issue::error("level has no blocks to break"));
+    }
+
+    // Moving blocks must stay on the field and out of the paddle's way.
+    let extents = motion_extents(file);
+    let (field_w, field_h) = file.size();
+    let paddle_zone_top = BOTTOM_TO_PADDLE_BOTTOM + DEFAULT_PADDLE_HEIGHT + 2. * BALL_RADIUS;
+    let mut reported = Vec::new();
+    for j in 0..h {
+        for i in 0..w {
+            let c = file.cell(i, j);
+            let (min, max) = match extents.get(&c) {
+                Some(&extent) => extent,
+                None => continue,
+            };
+            if reported.contains(&c) {
+                continue;
+            }
+            let (x, y) = file.cell_origin(i, j);
+            if y + min.y < paddle_zone_top {
+                reported.push(c);
+                issues.push(Issue::error(format!(
+                    "block '{}' at column {}, row {} moves into the paddle zone", c, i + 1, j + 1,
+                )));
+            } else if x + min.x < 0. || x + max.x + BLOCK_W > field_w || y + max.y + BLOCK_H > field_h {
+                reported.push(c);
+                issues.push(Issue::warning(format!(
+                    "block '{}' at column {}, row {} moves off the playfield", c, i + 1, j + 1,
+                )));
+            }
+        }
+    }
+
+    let sealed = sealed_blocks(file);
+    if let Some(&(i, j)) = sealed.first() {
+        issues.push(Issue::error(format!(
+            "{} block(s) are sealed in by indestructible blocks, first at column {}, row {}",
+            sealed.len(), i + 1, j + 1,
+        )));
+    }
+    (issues, stats)
+}
+
+pub fn lint_file<P: AsRef<Path>>(path: P) -> Report {
+    let path = path.as_ref().to_path_buf();
+    match LevelFile::load(&path) {
+        Ok(file) => {
+            let (issues, stats) = lint_level(&file);
+            Report { path, issues, stats: Some(stats) }
+        },
+        Err(LevelError::Parse { line, message }) => Report {
+            path,
+            issues: vec![Issue { severity: Severity::Error, line: Some(line), message }],
+            stats: None,
+        },
+        Err(err) => Report { path, issues: vec![Issue::error(format!("{}", err))], stats: None },
+    }
+}
+
+/// What the tool exits with for `reports`: 1 when a level has errors, or
+/// warnings when `is_strict`, otherwise 0.
+pub fn exit_code(reports: &[Report], is_strict: bool) -> i32 {
+    let errors: usize = reports.iter().map(|report| report.count(Severity::Error)).sum();
+    let warnings: usize = reports.iter().map(|report| report.count(Severity::Warning)).sum();
+    if errors > 0 || (is_strict && warnings > 0) { 1 } else { 0 }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Report {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == severity).count()
+    }
+
+    /// One JSON object describing the level.
+    pub fn to_json(&self) -> String {
+        let issues = self.issues.iter()
+            .map(|issue| format!(
+                "{{\"severity\":{},\"line\":{},\"message\":{}}}",
+                json_string(issue.severity.name()),
+                issue.line.map_or("null".into(), |line| line.to_string()),
+                json_string(&issue.message),
+            ))
+            .collect::<Vec<_>>();
+        let stats = match self.stats {
+            Some(ref stats) => format!(
+                "{{\"width\":{},\"height\":{},\"blocks\":{},\"indestructible\":{},\"moving\":{},\
+                 \"par_time\":{},\"estimated_clear_time\":{:.1}}}",
+                stats.width, stats.height, stats.blocks, stats.indestructible, stats.moving,
+                stats.par_time.map_or("null".into(), |par| par.to_string()),
+                stats.estimated_clear_time,
+            ),
+            None => "null".into(),
+        };
+        format!(
+            "{{\"path\":{},\"errors\":{},\"warnings\":{},\"issues\":[{}],\"stats\":{}}}",
+            json_string(&self.path.to_string_lossy()),
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            issues.join(","),
+            stats,
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.display();
+        for issue in self.issues.iter() {
+            match issue.line {
+                Some(line) => writeln!(f, "{}:{}: {}: {}", path, line, issue.severity.name(), issue.message)?,
+                None => writeln!(f, "{}: {}: {}", path, issue.severity.name(), issue.message)?,
+            }
+        }
+        if let Some(ref stats) = self.stats {
+            let par = stats.par_time.map_or("none".into(), |par| format!("{}s", par));
+            writeln!(
+                f,
+                "{}: {} x {}, {} blocks ({} indestructible, {} moving), par {}, estimated clear time {:.1}s",
+                path, stats.width, stats.height, stats.blocks, stats.indestructible, stats.moving,
+                par, stats.estimated_clear_time,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(text: &str) -> Vec<Issue> {
+        lint_level(&LevelFile::parse(text).unwrap()).0
+    }
+
+    fn report(text: &str) -> Report {
+        let (issues, stats) = lint_level(&LevelFile::parse(text).unwrap());
+        Report { path: PathBuf::from("test.level"), issues, stats: Some(stats) }
+    }
+
+    fn messages(issues: &[Issue], severity: Severity) -> Vec<&str> {
+        issues.iter().filter(|issue| issue.severity == severity).map(|issue| issue.message.as_str()).collect()
+    }
+
+    #[test]
+    fn clean_level_passes() {
+        assert!(lint("##..##\n.####.\n").is_empty());
+    }
+
+    #[test]
+    fn sealed_blocks_are_errors() {
+        let issues = lint("#.....\n@@@...\n@#@...\n@@@...\n");
+        assert_eq!(
+            messages(&issues, Severity::Error),
+            vec!["1 block(s) are sealed in by indestructible blocks, first at column 2, row 3"],
+        );
+        assert!(messages(&issues, Severity::Warning).is_empty());
+    }
+
+    #[test]
+    fn ragged_rows_are_warnings() {
+        let issues = lint("######\n###\n\n######\n");
+        assert_eq!(messages(&issues, Severity::Warning), vec!["row 2 is 3 cells wide, expected 6"]);
+        assert!(messages(&issues, Severity::Error).is_empty());
+
+        let wide: String = (0..MAX_GRID_W + 1).map(|_| '#').collect();
+        let issues = lint(&format!("{}\n##\n", wide));
+        assert_eq!(messages(&issues, Severity::Warning), vec![format!("row 2 is 2 cells wide, expected {}", MAX_GRID_W + 1)]);
+        assert_eq!(
+            messages(&issues, Severity::Error),
+            vec![format!("grid is {} cells wide, the playfield fits {}", MAX_GRID_W + 1, MAX_GRID_W)],
+        );
+    }
+
+    #[test]
+    fn blocks_moving_into_the_paddle_zone_are_errors() {
+        let issues = lint("!move a path 0,0,100 0,-220,100\n#a#\n");
+        assert_eq!(
+            messages(&issues, Severity::Error),
+            vec!["block 'a' at column 2, row 1 moves into the paddle zone"],
+        );
+        assert!(lint("!move a oscillate 24 2\n#a#.....\n").iter().all(|issue| issue.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn exit_code_follows_severity() {
+        let clean = report("##..##\n");
+        let ragged = report("######\n###\n");
+        let sealed = report("#.....\n@@@...\n@#@...\n@@@...\n");
+        assert_eq!(exit_code(&[], false), 0);
+        assert_eq!(exit_code(&[clean], true), 0);
+        assert_eq!(exit_code(&[ragged], false), 0);
+        assert_eq!(exit_code(&[report("######\n###\n")], true), 1);
+        assert_eq!(exit_code(&[report("##\n"), sealed], false), 1);
+    }
+
+    #[test]
+    fn json_output() {
+        let mut report = report("!par 30\n######\n###\n");
+        report.path = PathBuf::from("levels/\"odd\".level");
+        assert_eq!(
+            report.to_json(),
+            "{\"path\":\"levels/\\\"odd\\\".level\",\"errors\":0,\"warnings\":1,\
+             \"issues\":[{\"severity\":\"warning\",\"line\":null,\"message\":\"row 2 is 3 cells wide, expected 6\"}],\
+             \"stats\":{\"width\":6,\"height\":2,\"blocks\":9,\"indestructible\":0,\"moving\":0,\
+             \"par_time\":30,\"estimated_clear_time\":12.6}}",
+        );
+        let unread = Report { path: PathBuf::from("missing.level"), issues: vec![Issue::error("gone")], stats: None };
+        assert!(unread.to_json().ends_with("\"errors\":1,\"warnings\":0,\"issues\":[{\"severity\":\"error\",\"line\":null,\"message\":\"gone\"}],\"stats\":null}"));
+    }
+}
@@ -1,13 +1,39 @@
 extern crate freetype;
 extern crate gl;
 extern crate glutin;
+extern crate libc;
 
+mod audio;
 mod breakout;
 mod collision;
+mod editor;
 mod fonts;
+mod gamepad;
+mod generator;
 mod graphics;
+mod highscores;
+mod input;
+mod keys;
+mod level;
 mod math;
+mod menu;
+mod motion;
+mod music;
+mod netplay;
+mod pack;
+mod particles;
+mod png;
 mod renderer;
+mod save;
+mod scenes;
+mod scoring;
+mod settings;
+mod spectate;
+mod splits;
+mod sprites;
+mod storage;
+mod theme;
+mod tween;
 
 use glutin::{
     Api as GlApi,
@@ -24,20 +50,93 @@ use glutin::{
     WindowBuilder,
     WindowEvent,
 };
-use std::time::Instant;
+use std::env;
+use std::net::TcpListener;
+use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use breakout::Game;
+use breakout::{net_check, Game};
+use netplay::{Hello, Session, DEFAULT_INPUT_DELAY, MAX_INPUT_DELAY};
+use settings::Settings;
+
+/// Ticks `--netcheck` runs for when not given a count.
+const NET_CHECK_TICKS: u32 = 10_000;
+
+/// The value after `name` on the command line, parsed.
+fn arg_value<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    let i = args.iter().position(|arg| arg == name)?;
+    match args.get(i + 1).and_then(|value| value.parse().ok()) {
+        Some(value) => Some(value),
+        None => {
+            eprintln!("Error: {} needs a valid value", name);
+            process::exit(2);
+        },
+    }
+}
+
+/// Connects to the other player before the window opens, for `--host` and
+/// `--join`.
+fn connect(args: &[String]) -> Option<Session> {
+    let input_delay = arg_value(args, "--input-delay").unwrap_or(DEFAULT_INPUT_DELAY).min(MAX_INPUT_DELAY);
+    let result = if let Some(port) = arg_value::<u16>(args, "--host") {
+        let listener = TcpListener::bind(("0.0.0.0", port)).unwrap_or_else(|err| {
+            eprintln!("Error: cannot listen on port {}: {}", port, err);
+            process::exit(1);
+        });
+        println!("Waiting for the other player on port {}...", port);
+        let hello = Hello {
+            seed: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() ^ d.subsec_nanos() as u64).unwrap_or(1),
+            input_delay,
+            is_coop: args.iter().any(|arg| arg == "--coop"),
+        };
+        Session::host(&listener, hello)
+    } else if let Some(addr) = arg_value::<String>(args, "--join") {
+        println!("Connecting to {}...", addr);
+        Session::join(addr.as_str())
+    } else {
+        return None;
+    };
+    match result {
+        Ok(session) => Some(session),
+        Err(err) => {
+            eprintln!("Error: cannot start network game: {}", err);
+            process::exit(1);
+        },
+    }
+}
 
 fn main() {
     const INITIAL_WIDTH: u32 = 900;
     const INITIAL_HEIGHT: u32 = 900;
 
+    let args: Vec<String> = env::args().collect();
+    // Plays two networked games against each other without a window.
+    if let Some(i) = args.iter().position(|arg| arg == "--netcheck") {
+        let ticks = args.get(i + 1).and_then(|ticks| ticks.parse().ok()).unwrap_or(NET_CHECK_TICKS);
+        let input_delay = arg_value(&args, "--input-delay").unwrap_or(DEFAULT_INPUT_DELAY).min(MAX_INPUT_DELAY);
+        let settings = Settings::new("");
+        match net_check(ticks, input_delay, arg_value(&args, "--desync-at"), [&settings, &settings]) {
+            Ok(summary) => println!("{}", summary),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            },
+        }
+        return;
+    }
+    let session = connect(&args);
+
+    let settings = Settings::load(storage::data_dir().join("settings.txt"));
     let mut events_loop = EventsLoop::new();
+    let mut is_fullscreen = settings.fullscreen;
+    let monitor = if is_fullscreen { Some(events_loop.get_primary_monitor()) } else { None };
     let window_spec = WindowBuilder::new()
         .with_title("B R E A K O U T ! ! - by Eugene Che")
-        .with_dimensions(INITIAL_WIDTH, INITIAL_HEIGHT);
+        .with_dimensions(INITIAL_WIDTH, INITIAL_HEIGHT)
+        .with_fullscreen(monitor);
     let context_spec = ContextBuilder::new()
-        .with_gl(GlRequest::Specific(GlApi::OpenGl, (3, 3)));
+        .with_gl(GlRequest::Specific(GlApi::OpenGl, (3, 3)))
+        .with_vsync(settings.vsync);
     
     let window = GlWindow::new(window_spec, context_spec, &events_loop).unwrap();
 
@@ -46,7 +145,22 @@ fn main() {
         gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
     }
 
-    let mut game = Game::new(INITIAL_WIDTH as _, INITIAL_WIDTH as _);
+    let mut game = Game::new(INITIAL_WIDTH as _, INITIAL_WIDTH as _, settings);
+    if let Some(session) = session {
+        game.start_net_game(session);
+    }
+    if let Some(addr) = arg_value::<String>(&args, "--broadcast") {
+        game.start_broadcast(&addr);
+    }
+    if let Some(addr) = arg_value::<String>(&args, "--spectate") {
+        game.spectate(&addr);
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--edit") {
+        match args.get(i + 1) {
+            Some(path) => game.open_editor(path),
+            None => eprintln!("Warning: --edit needs a level file"),
+        }
+    }
     let mut is_running = true;
     let mut last_update = Instant::now();
     while is_running {
@@ -80,5 +194,13 @@ fn main() {
         game.step(dt);
         game.render();
         window.swap_buffers().unwrap();
+        if game.is_quit_requested() {
+            is_running = false;
+        }
+        if game.settings().fullscreen != is_fullscreen {
+            is_fullscreen = game.settings().fullscreen;
+            window.set_fullscreen(if is_fullscreen { Some(window.get_current_monitor()) } else { None });
+        }
     }
+    game.on_quit();
 }
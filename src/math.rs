@@ -1,6 +1,6 @@
 use std::ops;
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -26,6 +26,11 @@ impl Vec2 {
     pub fn unit(self) -> Vec2 {
         (1. / self.norm()) * self
     }
+
+    /// The point `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        self + t * (other - self)
+    }
 }
 
 impl ops::Add for Vec2 {
@@ -0,0 +1,95 @@
+use collision::{check_rect_contains, Rectangle};
+use math::Vec2;
+
+pub const MENU_ITEM_W: f32 = 480.;
+pub const MENU_ITEM_H: f32 = 40.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuNav {
+    Prev,
+    Next,
+    Confirm,
+}
+
+pub struct MenuItem<A> {
+    pub label: String,
+    /// Items without an action are shown but cannot be focused.
+    pub action: Option<A>,
+}
+
+pub struct Menu<A> {
+    pub title: String,
+    pub p: Vec2,
+    pub items: Vec<MenuItem<A>>,
+    pub focus: Option<usize>,
+}
+
+impl<A> MenuItem<A> {
+    pub fn new<S: Into<String>>(label: S, action: A) -> Self {
+        MenuItem { label: label.into(), action: Some(action) }
+    }
+
+    pub fn text<S: Into<String>>(label: S) -> Self {
+        MenuItem { label: label.into(), action: None }
+    }
+}
+
+impl<A: Copy> Menu<A> {
+    /// Creates a menu whose first item's baseline sits at `p`, with the rest
+    /// stacked below it.
+    pub fn new<S: Into<String>>(title: S, p: Vec2, items: Vec<MenuItem<A>>) -> Self {
+        let focus = items.iter().position(|item| item.action.is_some());
+        Menu { title: title.into(), p, items, focus }
+    }
+
+    pub fn item_rect(&self, i: usize) -> Rectangle {
+        Rectangle::new(
+            self.p + Vec2::new(0., -(i as f32) * MENU_ITEM_H - MENU_ITEM_H / 4.),
+            MENU_ITEM_W,
+            MENU_ITEM_H,
+        )
+    }
+
+    pub fn item_at(&self, p: Vec2) -> Option<usize> {
+        (0..self.items.len())
+            .filter(|&i| self.items[i].action.is_some())
+            .find(|&i| check_rect_contains(self.item_rect(i), p))
+    }
+
+    fn step_focus(&mut self, forward: bool) {
+        let len = self.items.len();
+        let start = match self.focus {
+            Some(focus) => focus,
+            None => return,
+        };
+        let mut i = start;
+        loop {
+            i = if forward { (i + 1) % len } else { (i + len - 1) % len };
+            if i == start || self.items[i].action.is_some() {
+                break;
+            }
+        }
+        self.focus = Some(i);
+    }
+
+    pub fn navigate(&mut self, nav: MenuNav) -> Option<A> {
+        match nav {
+            MenuNav::Prev => self.step_focus(false),
+            MenuNav::Next => self.step_focus(true),
+            MenuNav::Confirm => return self.focus.and_then(|i| self.items[i].action),
+        }
+        None
+    }
+
+    pub fn on_mouse_motion(&mut self, p: Vec2) {
+        if let Some(i) = self.item_at(p) {
+            self.focus = Some(i);
+        }
+    }
+
+    pub fn on_click(&mut self, p: Vec2) -> Option<A> {
+        let i = self.item_at(p)?;
+        self.focus = Some(i);
+        self.items[i].action
+    }
+}
@@ -0,0 +1,124 @@
+use std::f32::consts::PI;
+
+use math::Vec2;
+
+#[derive(Clone, Debug)]
+pub struct Waypoint {
+    pub p: Vec2,
+    pub speed: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum Motion {
+    #[default]
+    Static,
+    Oscillate { amplitude: f32, period: f32, phase: f32 },
+    Orbit { radius: f32, period: f32, phase: f32 },
+    Waypoints(Vec<Waypoint>),
+}
+
+fn parse_f32(token: Option<&str>, what: &str) -> Result<f32, String> {
+    let token = token.ok_or_else(|| format!("Missing {}", what))?;
+    token.parse().map_err(|_| format!("Invalid {} '{}'", what, token))
+}
+
+fn parse_waypoint(token: &str) -> Result<Waypoint, String> {
+    let mut parts = token.split(',');
+    let x = parse_f32(parts.next(), "waypoint x")?;
+    let y = parse_f32(parts.next(), "waypoint y")?;
+    let speed = parse_f32(parts.next(), "waypoint speed")?;
+    if parts.next().is_some() {
+        return Err(format!("Waypoint '{}' has too many components", token));
+    }
+    if speed <= 0. {
+        return Err(format!("Waypoint '{}' must have a positive speed", token));
+    }
+    Ok(Waypoint { p: Vec2::new(x, y), speed })
+}
+
+impl Motion {
+    /// Parses a motion spec such as `oscillate 48 2`, `orbit 16 3 0.5` or
+    /// `path 0,0,60 96,0,120`. Waypoints are `x,y,speed` offsets from the
+    /// block's resting position, where speed applies to the segment leaving
+    /// that waypoint.
+    pub fn parse(spec: &str) -> Result<Motion, String> {
+        let mut tokens = spec.split_whitespace();
+        let kind = tokens.next().ok_or_else(|| "Empty motion".to_string())?;
+        let motion = match kind {
+            "static" => Motion::Static,
+            "oscillate" => Motion::Oscillate {
+                amplitude: parse_f32(tokens.next(), "amplitude")?,
+                period: parse_f32(tokens.next(), "period")?,
+                phase: parse_f32(tokens.next().or(Some("0")), "phase")?,
+            },
+            "orbit" => Motion::Orbit {
+                radius: parse_f32(tokens.next(), "radius")?,
+                period: parse_f32(tokens.next(), "period")?,
+                phase: parse_f32(tokens.next().or(Some("0")), "phase")?,
+            },
+            "path" => {
+                let points = tokens.by_ref().map(parse_waypoint).collect::<Result<Vec<_>, _>>()?;
+                if points.len() < 2 {
+                    return Err("Path needs at least two waypoints".into());
+                }
+                Motion::Waypoints(points)
+            },
+            _ => return Err(format!("Unknown motion '{}'", kind)),
+        };
+        if let Some(token) = tokens.next() {
+            return Err(format!("Unexpected '{}' after {} motion", token, kind));
+        }
+        match motion {
+            Motion::Oscillate { period, .. } | Motion::Orbit { period, .. } if period <= 0. => {
+                Err(format!("{} motion must have a positive period", kind))
+            },
+            motion => Ok(motion),
+        }
+    }
+
+    /// Offset from the resting position at simulation time `t`.
+    pub fn offset(&self, t: f32) -> Vec2 {
+        match *self {
+            Motion::Static => Vec2::default(),
+            Motion::Oscillate { amplitude, period, phase } => {
+                let theta = 2. * PI * (t / period + phase);
+                Vec2::new(amplitude * theta.sin(), 0.)
+            },
+            Motion::Orbit { radius, period, phase } => {
+                // Offset so the block starts on its resting position.
+                let theta0 = 2. * PI * phase;
+                let theta = 2. * PI * (t / period + phase);
+                Vec2::new(
+                    radius * (theta.cos() - theta0.cos()),
+                    radius * (theta.sin() - theta0.sin()),
+                )
+            },
+            Motion::Waypoints(ref points) => {
+                let segment_time = |i: usize| {
+                    let from = &points[i];
+                    let to = &points[(i + 1) % points.len()];
+                    (to.p - from.p).norm() / from.speed
+                };
+                let cycle: f32 = (0..points.len()).map(&segment_time).sum();
+                if cycle <= 0. {
+                    return points[0].p;
+                }
+                let mut t = t % cycle;
+                for i in 0..points.len() {
+                    let duration = segment_time(i);
+                    if t < duration {
+                        let from = points[i].p;
+                        let to = points[(i + 1) % points.len()].p;
+                        return from + (t / duration) * (to - from);
+                    }
+                    t -= duration;
+                }
+                points[0].p
+            },
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        matches!(*self, Motion::Static)
+    }
+}
@@ -0,0 +1,354 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::rc::Rc;
+
+use audio::{read_wav, resample, symbol, SAMPLE_RATE};
+use libc::{self, c_char, c_int, c_long, c_void};
+
+/// Where level files and pack manifests find their tracks.
+pub const MUSIC_DIR: &str = "res/music";
+/// Time for one track to fade out and the next to fade in.
+const CROSSFADE_TIME: f32 = 1.5;
+/// Music level while an effect it ducks under plays.
+const DUCK_GAIN: f32 = 0.3;
+const DUCK_ATTACK_TIME: f32 = 0.05;
+const DUCK_RELEASE_TIME: f32 = 0.6;
+/// Bigger than `OggVorbis_File` on any platform libvorbisfile supports. Only
+/// the library looks inside it.
+const VORBIS_FILE_SIZE: usize = 4096;
+
+/// A looping track and how loud it is in the crossfade.
+struct Stream {
+    samples: Rc<Vec<f32>>,
+    pos: usize,
+    gain: f32,
+    /// Gain change per sample, negative while fading out.
+    fade: f32,
+}
+
+/// Loops one track at a time, crossfading when it changes.
+pub struct MusicPlayer {
+    /// Name of the track asked for, even if it could not be loaded.
+    track: Option<String>,
+    streams: Vec<Stream>,
+    volume: f32,
+    duck: f32,
+    /// Samples left until the music comes back up.
+    duck_hold: usize,
+    /// Tracks loaded so far, none for those that failed.
+    loaded: Vec<(String, Option<Rc<Vec<f32>>>)>,
+}
+
+type OvFopen = unsafe extern "C" fn(*const c_char, *mut c_void) -> c_int;
+type OvInfo = unsafe extern "C" fn(*mut c_void, c_int) -> *const VorbisInfo;
+type OvRead = unsafe extern "C" fn(*mut c_void, *mut c_char, c_int, c_int, c_int, c_int, *mut c_int) -> c_long;
+type OvClear = unsafe extern "C" fn(*mut c_void) -> c_int;
+
+/// The start of `vorbis_info` from vorbis/codec.h.
+#[repr(C)]
+struct VorbisInfo {
+    version: c_int,
+    channels: c_int,
+    rate: c_long,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Averages interleaved 16 bit frames down to mono at `SAMPLE_RATE`.
+fn to_mono(frames: &[i16], channels: usize, rate: u32) -> io::Result<Vec<f32>> {
+    if rate == 0 {
+        return Err(invalid_data("sample rate is 0".into()));
+    }
+    let samples: Vec<f32> = frames.chunks(channels)
+        .filter(|frame| frame.len() == channels)
+        .map(|frame| frame.iter().map(|&s| s as f32).sum::<f32>() / channels as f32 / 32768.)
+        .collect();
+    if samples.is_empty() || rate == SAMPLE_RATE {
+        return Ok(samples);
+    }
+    Ok(resample(&samples, rate as f32 / SAMPLE_RATE as f32))
+}
+
+/// Decodes an Ogg Vorbis file with libvorbisfile, loaded at run time like
+/// libasound.
+fn read_ogg(path: &Path) -> io::Result<Vec<f32>> {
+    let unavailable = |err: String| io::Error::new(io::ErrorKind::Other, format!("cannot decode Ogg: {}", err));
+    let lib_name = CString::new("libvorbisfile.so.3").expect("No NUL");
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| invalid_data("bad path".into()))?;
+    unsafe {
+        let lib = libc::dlopen(lib_name.as_ptr(), libc::RTLD_NOW);
+        if lib.is_null() {
+            return Err(unavailable("libvorbisfile is not installed".into()));
+        }
+        let result = (|| {
+            let fopen: OvFopen = symbol(lib, "ov_fopen").map_err(unavailable)?;
+            let info: OvInfo = symbol(lib, "ov_info").map_err(unavailable)?;
+            let read: OvRead = symbol(lib, "ov_read").map_err(unavailable)?;
+            let clear: OvClear = symbol(lib, "ov_clear").map_err(unavailable)?;
+            let mut vorbis_file = vec![0u64; VORBIS_FILE_SIZE / 8];
+            let file = vorbis_file.as_mut_ptr() as *mut c_void;
+            if fopen(c_path.as_ptr(), file) != 0 {
+                return Err(invalid_data("not an Ogg Vorbis file".into()));
+            }
+            let vorbis_info = info(file, -1);
+            if vorbis_info.is_null() || (*vorbis_info).channels <= 0 {
+                clear(file);
+                return Err(invalid_data("no stream info".into()));
+            }
+            let (channels, rate) = ((*vorbis_info).channels as usize, (*vorbis_info).rate as u32);
+            let mut frames: Vec<i16> = Vec::new();
+            let mut buf = [0i16; 4096];
+            let mut section = 0;
+            loop {
+                // Little endian, 16 bit, signed.
+                let n = read(file, buf.as_mut_ptr() as *mut c_char, (buf.len() * 2) as c_int, 0, 2, 1, &mut section);
+                match n {
+                    0 => break,
+                    // A hole in the data, decoding carries on after it.
+                    -3 => continue,
+                    n if n < 0 => {
+                        clear(file);
+                        return Err(invalid_data(format!("decoding failed (error {})", n)));
+                    },
+                    n => frames.extend_from_slice(&buf[..n as usize / 2]),
+                }
+            }
+            clear(file);
+            to_mono(&frames, channels, rate)
+        })();
+        libc::dlclose(lib);
+        result
+    }
+}
+
+/// Loads a WAV or Ogg Vorbis track as mono samples at `SAMPLE_RATE`.
+pub fn load_track<P: AsRef<Path>>(path: P) -> io::Result<Vec<f32>> {
+    let path = path.as_ref();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("wav") => read_wav(path),
+        Some("ogg") => read_ogg(path),
+        _ => Err(invalid_data("only .wav and .ogg tracks are supported".into())),
+    }
+}
+
+impl MusicPlayer {
+    pub fn new(volume: f32) -> Self {
+        MusicPlayer { track: None, streams: Vec::new(), volume, duck: 1., duck_hold: 0, loaded: Vec::new() }
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    fn load(&mut self, track: &str) -> Option<Rc<Vec<f32>>> {
+        if let Some(&(_, ref samples)) = self.loaded.iter().find(|&&(ref name, _)| name == track) {
+            return samples.clone();
+        }
+        let path = Path::new(MUSIC_DIR).join(track);
+        let samples = match load_track(&path) {
+            Ok(ref samples) if samples.is_empty() => None,
+            Ok(samples) => Some(Rc::new(samples)),
+            Err(err) => {
+                eprintln!("Warning: cannot load {}: {}", path.display(), err);
+                None
+            },
+        };
+        self.loaded.push((track.to_string(), samples.clone()));
+        samples
+    }
+
+    /// Crossfades to `track`, a file in `MUSIC_DIR`, or to silence. Asking
+    /// for the track already playing changes nothing.
+    pub fn play(&mut self, track: Option<&str>) {
+        if self.track.as_ref().map(String::as_str) == track {
+            return;
+        }
+        self.track = track.map(String::from);
+        let step = 1. / (CROSSFADE_TIME * SAMPLE_RATE as f32);
+        for stream in self.streams.iter_mut() {
+            stream.fade = -step;
+        }
+        if let Some(samples) = track.and_then(|track| self.load(track)) {
+            self.streams.push(Stream { samples, pos: 0, gain: 0., fade: step });
+        }
+    }
+
+    /// Lowers the music for the next `samples` samples.
+    pub fn duck(&mut self, samples: usize) {
+        self.duck_hold = self.duck_hold.max(samples);
+    }
+
+    /// Mixes the music into `out`.
+    pub fn render(&mut self, out: &mut [f32]) {
+        let attack = (1. - DUCK_GAIN) / (DUCK_ATTACK_TIME * SAMPLE_RATE as f32);
+        let release = (1. - DUCK_GAIN) / (DUCK_RELEASE_TIME * SAMPLE_RATE as f32);
+        for sample in out.iter_mut() {
+            if self.duck_hold > 0 {
+                self.duck_hold -= 1;
+                self.duck = (self.duck - attack).max(DUCK_GAIN);
+            } else {
+                self.duck = (self.duck + release).min(1.);
+            }
+            let mut value = 0.;
+            for stream in self.streams.iter_mut() {
+                stream.gain = (stream.gain + stream.fade).max(0.).min(1.);
+                value += stream.gain * stream.samples[stream.pos];
+                stream.pos = (stream.pos + 1) % stream.samples.len();
+            }
+            *sample = (*sample + value * self.volume * self.duck).max(-1.).min(1.);
+        }
+        self.streams.retain(|stream| stream.fade > 0. || stream.gain > 0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio::write_wav;
+    use std::env;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+    use std::process;
+
+    const FADE_SAMPLES: usize = (CROSSFADE_TIME * SAMPLE_RATE as f32) as usize + 2;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("breakout-{}-{}", process::id(), name))
+    }
+
+    /// Writes `samples` as a track and returns the name to play it by.
+    fn track(name: &str, samples: &[f32]) -> String {
+        let path = temp_path(name);
+        write_wav(File::create(&path).unwrap(), samples).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// A 16 bit PCM WAV file with `channels` interleaved in `frames`.
+    fn wav_bytes(channels: u16, rate: u32, frames: &[i16]) -> Vec<u8> {
+        let data_len = frames.len() as u32 * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        bytes.extend_from_slice(&(rate * 2 * channels as u32).to_le_bytes());
+        bytes.extend_from_slice(&(2 * channels).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for &frame in frames {
+            bytes.extend_from_slice(&frame.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn render(music: &mut MusicPlayer, n: usize) -> Vec<f32> {
+        let mut out = vec![0.; n];
+        music.render(&mut out);
+        out
+    }
+
+    #[test]
+    fn crossfades_in_and_out() {
+        let (a, b) = (track("fade-a.wav", &[0.5; 1000]), track("fade-b.wav", &[-0.25; 1000]));
+        let mut music = MusicPlayer::new(1.);
+        music.play(Some(&a));
+        let level = music.streams[0].samples[0];
+        let out = render(&mut music, FADE_SAMPLES);
+        assert!(out[0] < 0.01);
+        assert_eq!(music.streams[0].gain, 1.);
+        assert_eq!(out[FADE_SAMPLES - 1], level);
+
+        music.play(Some(&b));
+        let out = render(&mut music, FADE_SAMPLES / 2);
+        assert_eq!(music.streams.len(), 2);
+        assert!(out[out.len() - 1] < level && out[out.len() - 1] > -0.25);
+        render(&mut music, FADE_SAMPLES);
+        assert_eq!(music.streams.len(), 1);
+        assert_eq!(music.streams[0].gain, 1.);
+
+        music.play(None);
+        render(&mut music, FADE_SAMPLES);
+        assert!(music.streams.is_empty());
+        assert_eq!(render(&mut music, 10), vec![0.; 10]);
+        for name in [a, b].iter() {
+            fs::remove_file(name).unwrap();
+        }
+    }
+
+    #[test]
+    fn ducks_and_releases() {
+        let name = track("duck.wav", &[0.5; 1000]);
+        let mut music = MusicPlayer::new(1.);
+        music.play(Some(&name));
+        render(&mut music, FADE_SAMPLES);
+        let level = music.streams[0].samples[0];
+        let hold = SAMPLE_RATE as usize / 2;
+        music.duck(hold);
+        let out = render(&mut music, hold);
+        assert!((out[hold - 1] - DUCK_GAIN * level).abs() < 1e-4, "{}", out[hold - 1]);
+        let release = (DUCK_RELEASE_TIME * SAMPLE_RATE as f32) as usize + 2;
+        let out = render(&mut music, release);
+        assert!(out[0] < level);
+        assert_eq!(out[release - 1], level);
+        fs::remove_file(&name).unwrap();
+    }
+
+    #[test]
+    fn loops_at_the_end() {
+        let samples: Vec<f32> = (0..100).map(|i| i as f32 / 200.).collect();
+        let name = track("loop.wav", &samples);
+        let mut music = MusicPlayer::new(1.);
+        music.play(Some(&name));
+        render(&mut music, FADE_SAMPLES);
+        let (loaded, start) = (music.streams[0].samples.clone(), music.streams[0].pos);
+        let out = render(&mut music, 250);
+        for (i, &sample) in out.iter().enumerate() {
+            assert_eq!(sample, loaded[(start + i) % loaded.len()]);
+        }
+        fs::remove_file(&name).unwrap();
+    }
+
+    #[test]
+    fn reads_stereo_and_other_rates() {
+        let stereo = temp_path("stereo.wav");
+        fs::write(&stereo, wav_bytes(2, SAMPLE_RATE, &[1000, 3000, -2000, -4000])).unwrap();
+        let samples = load_track(&stereo).unwrap();
+        fs::remove_file(&stereo).unwrap();
+        assert_eq!(samples, vec![2000. / 32768., -3000. / 32768.]);
+
+        let half_rate = temp_path("half-rate.wav");
+        let frames: Vec<i16> = (0..100).map(|i| i * 100).collect();
+        fs::write(&half_rate, wav_bytes(1, SAMPLE_RATE / 2, &frames)).unwrap();
+        let samples = load_track(&half_rate).unwrap();
+        fs::remove_file(&half_rate).unwrap();
+        assert_eq!(samples.len(), 200);
+        // Every other sample lands between two of the file's.
+        assert_eq!(samples[2], 100. / 32768.);
+        assert!((samples[3] - 150. / 32768.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_a_zero_sample_rate() {
+        let path = temp_path("zero-rate.wav");
+        fs::write(&path, wav_bytes(1, 0, &[1000, 2000])).unwrap();
+        let err = load_track(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(to_mono(&[1000, 2000], 2, 0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(to_mono(&[1000, 3000], 2, SAMPLE_RATE).unwrap(), vec![2000. / 32768.]);
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert!(load_track("track.mp3").is_err());
+        assert!(load_track(temp_path("missing.wav")).is_err());
+    }
+}
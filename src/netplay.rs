@@ -0,0 +1,297 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+const PROTOCOL_VERSION: u32 = 1;
+/// Ticks between local input and the tick it is played on, which gives it
+/// time to reach the other side.
+pub const DEFAULT_INPUT_DELAY: u32 = 3;
+pub const MAX_INPUT_DELAY: u32 = 30;
+/// Ticks between state checksums.
+pub const CHECKSUM_INTERVAL: u32 = 30;
+
+/// One player's input for one tick. The paddle axis is sent as an integer so
+/// both sides play exactly the same value.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerInput {
+    pub move_paddle: i16,
+    pub launch: bool,
+}
+
+/// Game settings the host picks and sends when the other side connects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hello {
+    pub seed: u64,
+    pub input_delay: u32,
+    pub is_coop: bool,
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    Protocol(String),
+    Disconnected,
+    /// The two simulations disagree about the state after `tick`.
+    Desync { tick: u32 },
+}
+
+/// FNV-1a, to condense the simulation state into something cheap to compare.
+pub struct Checksum {
+    hash: u64,
+}
+
+/// A lockstep connection to the other player. Only inputs cross the wire:
+/// both sides run the same simulation and move on to a tick once they have
+/// both players' input for it.
+pub struct Session {
+    stream: TcpStream,
+    received: Vec<u8>,
+    /// Index of the local player, 0 for the host.
+    pub local: usize,
+    pub hello: Hello,
+    /// Ticks simulated so far.
+    tick: u32,
+    /// Inputs for the ticks from `tick` on, oldest first.
+    local_inputs: VecDeque<PlayerInput>,
+    remote_inputs: VecDeque<PlayerInput>,
+    local_checksums: VecDeque<(u32, u64)>,
+    remote_checksums: VecDeque<(u32, u64)>,
+}
+
+impl PlayerInput {
+    pub fn new(move_paddle: f32, launch: bool) -> Self {
+        PlayerInput { move_paddle: (move_paddle.max(-1.).min(1.) * i16::max_value() as f32) as i16, launch }
+    }
+
+    pub fn move_paddle(&self) -> f32 {
+        self.move_paddle as f32 / i16::max_value() as f32
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(err: io::Error) -> Self {
+        NetError::Io(err)
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetError::Io(ref err) => write!(f, "{}", err),
+            NetError::Protocol(ref message) => write!(f, "bad message from the other side: {}", message),
+            NetError::Disconnected => write!(f, "the other side disconnected"),
+            NetError::Desync { tick } => write!(f, "games went out of sync by tick {}", tick),
+        }
+    }
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Checksum { hash: 0xcbf2_9ce4_8422_2325 }
+    }
+
+    pub fn add_u64(&mut self, value: u64) {
+        for i in 0..8 {
+            self.hash ^= (value >> (i * 8)) & 0xff;
+            self.hash = self.hash.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    pub fn add_i32(&mut self, value: i32) {
+        self.add_u64(value as u32 as u64);
+    }
+
+    pub fn add_f32(&mut self, value: f32) {
+        self.add_u64(value.to_bits() as u64);
+    }
+
+    pub fn value(&self) -> u64 {
+        self.hash
+    }
+}
+
+fn protocol_error<S: Into<String>>(message: S) -> NetError {
+    NetError::Protocol(message.into())
+}
+
+fn parse_field<T: ::std::str::FromStr>(field: Option<&str>, line: &str) -> Result<T, NetError> {
+    field.and_then(|field| field.parse().ok()).ok_or_else(|| protocol_error(line))
+}
+
+/// Reads one line before the connection switches to non-blocking.
+fn read_line_blocking(stream: &mut TcpStream) -> Result<String, NetError> {
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        if stream.read(&mut byte)? == 0 {
+            return Err(NetError::Disconnected);
+        }
+        if byte[0] == b'\n' {
+            break;
+        }
+        line.push(byte[0]);
+    }
+    String::from_utf8(line).map_err(|_| protocol_error("not text"))
+}
+
+impl Session {
+    /// Waits for the other player to connect to `listener` and sends them
+    /// the game settings.
+    pub fn host(listener: &TcpListener, hello: Hello) -> Result<Self, NetError> {
+        let (mut stream, _) = listener.accept()?;
+        writeln!(
+            stream,
+            "hello {} {} {} {}",
+            PROTOCOL_VERSION, hello.seed, hello.input_delay, if hello.is_coop { "coop" } else { "versus" },
+        )?;
+        Session::new(stream, 0, hello)
+    }
+
+    /// Connects to a host and reads the game settings from it.
+    pub fn join<A: ToSocketAddrs>(addr: A) -> Result<Self, NetError> {
+        let mut stream = TcpStream::connect(addr)?;
+        let line = read_line_blocking(&mut stream)?;
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("hello") {
+            return Err(protocol_error(line.clone()));
+        }
+        let version: u32 = parse_field(fields.next(), &line)?;
+        if version != PROTOCOL_VERSION {
+            return Err(protocol_error(format!("protocol version {}, expected {}", version, PROTOCOL_VERSION)));
+        }
+        let seed = parse_field(fields.next(), &line)?;
+        let input_delay: u32 = parse_field(fields.next(), &line)?;
+        let is_coop = match fields.next() {
+            Some("coop") => true,
+            Some("versus") => false,
+            _ => return Err(protocol_error(line.clone())),
+        };
+        Session::new(stream, 1, Hello { seed, input_delay: input_delay.min(MAX_INPUT_DELAY), is_coop })
+    }
+
+    fn new(stream: TcpStream, local: usize, hello: Hello) -> Result<Self, NetError> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        // Nobody has sent anything for the first ticks, they play no input.
+        let delay = hello.input_delay as usize;
+        Ok(Session {
+            stream,
+            received: Vec::new(),
+            local,
+            hello,
+            tick: 0,
+            local_inputs: vec![PlayerInput::default(); delay].into_iter().collect(),
+            remote_inputs: vec![PlayerInput::default(); delay].into_iter().collect(),
+            local_checksums: VecDeque::new(),
+            remote_checksums: VecDeque::new(),
+        })
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Whether the tick just simulated should be checked with `check`.
+    pub fn is_checksum_due(&self) -> bool {
+        self.tick % CHECKSUM_INTERVAL == 0
+    }
+
+    fn send(&mut self, line: String) -> Result<(), NetError> {
+        // Lines are tiny and the socket buffer is large, so a would-block
+        // here means the other side stopped reading long ago.
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(line.as_bytes());
+        self.stream.set_nonblocking(true)?;
+        result.map_err(NetError::from)
+    }
+
+    /// Reads whatever the other side has sent so far.
+    pub fn poll(&mut self) -> Result<(), NetError> {
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        while let Some(end) = self.received.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.received.drain(..end + 1).collect();
+            let line = String::from_utf8_lossy(&line[..end]).into_owned();
+            self.on_line(&line)?;
+        }
+        self.compare_checksums()
+    }
+
+    fn on_line(&mut self, line: &str) -> Result<(), NetError> {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("input") => {
+                let tick: u32 = parse_field(fields.next(), line)?;
+                let expected = self.tick + self.remote_inputs.len() as u32;
+                if tick != expected {
+                    return Err(protocol_error(format!("input for tick {}, expected {}", tick, expected)));
+                }
+                let move_paddle = parse_field(fields.next(), line)?;
+                let launch = parse_field::<u8>(fields.next(), line)? != 0;
+                self.remote_inputs.push_back(PlayerInput { move_paddle, launch });
+            },
+            Some("checksum") => {
+                let tick = parse_field(fields.next(), line)?;
+                let value = fields.next()
+                    .and_then(|value| u64::from_str_radix(value, 16).ok())
+                    .ok_or_else(|| protocol_error(line))?;
+                self.remote_checksums.push_back((tick, value));
+            },
+            _ => return Err(protocol_error(line)),
+        }
+        Ok(())
+    }
+
+    fn compare_checksums(&mut self) -> Result<(), NetError> {
+        while let (Some(&(local_tick, local)), Some(&(remote_tick, remote))) =
+            (self.local_checksums.front(), self.remote_checksums.front())
+        {
+            if local_tick != remote_tick || local != remote {
+                return Err(NetError::Desync { tick: local_tick.min(remote_tick) });
+            }
+            self.local_checksums.pop_front();
+            self.remote_checksums.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Both players' inputs for the next tick, indexed by player, or nothing
+    /// while the other side's input has not arrived. `input` is the local
+    /// player's input for `input_delay` ticks later. It is sent on the first
+    /// call for a tick, and ignored while waiting.
+    pub fn advance(&mut self, input: PlayerInput) -> Result<Option<[PlayerInput; 2]>, NetError> {
+        if self.local_inputs.len() <= self.hello.input_delay as usize {
+            let tick = self.tick + self.local_inputs.len() as u32;
+            self.send(format!("input {} {} {}\n", tick, input.move_paddle, input.launch as u8))?;
+            self.local_inputs.push_back(input);
+        }
+        if self.remote_inputs.is_empty() {
+            self.poll()?;
+        }
+        let remote = match self.remote_inputs.pop_front() {
+            Some(remote) => remote,
+            None => return Ok(None),
+        };
+        let local = self.local_inputs.pop_front().expect("Local input is queued before the tick");
+        self.tick += 1;
+        Ok(Some(if self.local == 0 { [local, remote] } else { [remote, local] }))
+    }
+
+    /// Sends the local checksum for the tick just simulated and compares it
+    /// with the other side's once that arrives.
+    pub fn check(&mut self, checksum: u64) -> Result<(), NetError> {
+        let tick = self.tick;
+        self.local_checksums.push_back((tick, checksum));
+        self.send(format!("checksum {} {:016x}\n", tick, checksum))?;
+        self.compare_checksums()
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Optional settings for a level pack, read from `pack.txt` next to its
+/// levels. Lines are `key = value`, `#` starts a comment:
+///
+///     music.title = title.ogg
+///     music.level = level.ogg
+///     music.finish = finish.wav
+///     theme = colorblind
+///
+/// Tracks are files in `res/music`. A level's own `!music` directive wins
+/// over `music.level`. The theme is used unless the player picked one.
+#[derive(Clone, Debug, Default)]
+pub struct PackManifest {
+    pub title_music: Option<String>,
+    pub level_music: Option<String>,
+    pub finish_music: Option<String>,
+    pub theme: Option<String>,
+}
+
+impl PackManifest {
+    /// Loads the manifest at `path`. A missing file is an empty manifest,
+    /// malformed lines are skipped with a warning.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let mut manifest = PackManifest::default();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return manifest,
+            Err(err) => {
+                eprintln!("Warning: cannot read {}: {}", path.display(), err);
+                return manifest;
+            },
+        };
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(err) = manifest.apply_line(line) {
+                eprintln!("Warning: {} line {}: {}", path.display(), n + 1, err);
+            }
+        }
+        manifest
+    }
+
+    fn apply_line(&mut self, line: &str) -> Result<(), String> {
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().ok_or_else(|| format!("expected 'key = value', got '{}'", line))?.trim();
+        if value.is_empty() {
+            return Err(format!("'{}' needs a value", key));
+        }
+        let value = Some(value.to_string());
+        match key {
+            "music.title" => self.title_music = value,
+            "music.level" => self.level_music = value,
+            "music.finish" => self.finish_music = value,
+            "theme" => self.theme = value,
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn reads_every_key() {
+        let mut manifest = PackManifest::default();
+        for line in ["music.title = title.ogg", "music.level=level.ogg", "music.finish =  finish.wav ", "theme = colorblind"].iter() {
+            manifest.apply_line(line).unwrap();
+        }
+        assert_eq!(manifest.title_music.as_ref().unwrap(), "title.ogg");
+        assert_eq!(manifest.level_music.as_ref().unwrap(), "level.ogg");
+        assert_eq!(manifest.finish_music.as_ref().unwrap(), "finish.wav");
+        assert_eq!(manifest.theme.as_ref().unwrap(), "colorblind");
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        let mut manifest = PackManifest::default();
+        assert_eq!(manifest.apply_line("music.boss = boss.ogg").unwrap_err(), "unknown key 'music.boss'");
+        assert_eq!(manifest.apply_line("theme =").unwrap_err(), "'theme' needs a value");
+        assert_eq!(manifest.apply_line("theme").unwrap_err(), "expected 'key = value', got 'theme'");
+        assert!(manifest.theme.is_none());
+    }
+
+    #[test]
+    fn skips_comments_and_bad_lines() {
+        let path = env::temp_dir().join(format!("breakout-{}-pack.txt", process::id()));
+        fs::write(&path, "# Music\nmusic.title = title.ogg\n\nmusic.level =\nbogus = 1\ntheme = high-contrast\n").unwrap();
+        let manifest = PackManifest::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(manifest.title_music.as_ref().unwrap(), "title.ogg");
+        assert!(manifest.level_music.is_none());
+        assert_eq!(manifest.theme.as_ref().unwrap(), "high-contrast");
+        assert!(PackManifest::load(env::temp_dir().join("breakout-no-such-pack.txt")).title_music.is_none());
+    }
+}
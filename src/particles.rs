@@ -0,0 +1,159 @@
+use std::f32::consts::PI;
+
+use generator::Rng;
+use math::Vec2;
+use renderer::{Color, Renderer};
+
+/// Most particles alive at once on a field. New ones take the place of
+/// random old ones past this, so frame time stays bounded however much
+/// breaks at once.
+pub const MAX_PARTICLES: usize = 600;
+const GRAVITY: f32 = -900.;
+
+const DEBRIS_PER_BLOCK: usize = 12;
+const DEBRIS_SIZE: f32 = 4.;
+const DEBRIS_SPEED: f32 = 160.;
+const DEBRIS_LIFETIME: f32 = 0.9;
+
+const SPARKS_PER_BOUNCE: usize = 6;
+const SPARK_RADIUS: f32 = 1.5;
+const SPARK_SPEED: f32 = 220.;
+const SPARK_LIFETIME: f32 = 0.3;
+/// Widest angle a spark flies off at from the surface normal.
+const SPARK_SPREAD: f32 = PI / 3.;
+
+const TRAIL_LIFETIME: f32 = 0.2;
+
+#[derive(Clone, Copy)]
+enum Shape {
+    /// A square of the given side, for debris.
+    Square(f32),
+    /// A circle of the given radius, for sparks and trails.
+    Circle(f32),
+}
+
+#[derive(Clone, Copy)]
+struct Particle {
+    p: Vec2,
+    v: Vec2,
+    shape: Shape,
+    color: Color,
+    /// Share of `GRAVITY` that pulls on it.
+    weight: f32,
+    /// Whether it shrinks away instead of fading.
+    shrinks: bool,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Short-lived bits thrown off by what the ball hits. Purely for show: they
+/// run on frame time and never touch the simulation.
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: Rng,
+}
+
+impl Particle {
+    fn life_left(&self) -> f32 {
+        1. - self.age / self.lifetime
+    }
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Particles { particles: Vec::new(), rng: Rng::new(1) }
+    }
+
+    fn spawn(&mut self, particle: Particle) {
+        if self.particles.len() < MAX_PARTICLES {
+            self.particles.push(particle);
+        } else {
+            let i = self.rng.below(self.particles.len());
+            self.particles[i] = particle;
+        }
+    }
+
+    /// Velocity of `speed` times 0.5..1, at `spread` radians either side of
+    /// `angle`.
+    fn scatter(&mut self, angle: f32, spread: f32, speed: f32) -> Vec2 {
+        let angle = angle + spread * (2. * self.rng.next_f32() - 1.);
+        let speed = speed * (0.5 + 0.5 * self.rng.next_f32());
+        Vec2::new(speed * angle.cos(), speed * angle.sin())
+    }
+
+    /// Pieces of a broken block at `p`, `w` by `h`, flying out and falling.
+    pub fn debris(&mut self, p: Vec2, w: f32, h: f32, color: Color) {
+        for _ in 0..DEBRIS_PER_BLOCK {
+            let offset = Vec2::new(self.rng.next_f32() * (w - DEBRIS_SIZE), self.rng.next_f32() * (h - DEBRIS_SIZE));
+            let v = self.scatter(PI / 2., PI, DEBRIS_SPEED);
+            let lifetime = DEBRIS_LIFETIME * (0.6 + 0.4 * self.rng.next_f32());
+            self.spawn(Particle {
+                p: p + offset,
+                v,
+                shape: Shape::Square(DEBRIS_SIZE),
+                color,
+                weight: 1.,
+                shrinks: false,
+                age: 0.,
+                lifetime,
+            });
+        }
+    }
+
+    /// Sparks off a surface at `p` facing `normal`.
+    pub fn sparks(&mut self, p: Vec2, normal: Vec2, color: Color) {
+        let angle = normal.y.atan2(normal.x);
+        for _ in 0..SPARKS_PER_BOUNCE {
+            let v = self.scatter(angle, SPARK_SPREAD, SPARK_SPEED);
+            self.spawn(Particle {
+                p,
+                v,
+                shape: Shape::Circle(SPARK_RADIUS),
+                color,
+                weight: 0.3,
+                shrinks: false,
+                age: 0.,
+                lifetime: SPARK_LIFETIME,
+            });
+        }
+    }
+
+    /// A fading copy of the ball left where it is now.
+    pub fn trail(&mut self, p: Vec2, radius: f32, color: Color) {
+        self.spawn(Particle {
+            p,
+            v: Vec2::default(),
+            shape: Shape::Circle(radius),
+            color,
+            weight: 0.,
+            shrinks: true,
+            age: 0.,
+            lifetime: TRAIL_LIFETIME,
+        });
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        for particle in self.particles.iter_mut() {
+            particle.v.y += particle.weight * GRAVITY * dt;
+            particle.p = particle.p + dt * particle.v;
+            particle.age += dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    /// Adds every particle to the renderer's current batch, offset by `vp`.
+    pub fn draw(&self, renderer: &mut Renderer, vp: Vec2) {
+        for particle in self.particles.iter() {
+            let life = particle.life_left();
+            let (scale, alpha) = if particle.shrinks { (life, 0.5 * particle.color.a) } else { (1., life * particle.color.a) };
+            let color = Color::new(particle.color.r, particle.color.g, particle.color.b, alpha);
+            match particle.shape {
+                Shape::Square(size) => {
+                    let size = scale * size;
+                    renderer.draw_rectangle(vp + particle.p, size, size, color);
+                },
+                Shape::Circle(radius) => renderer.draw_circle(vp + particle.p, scale * radius, color),
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use audio::symbol;
+use libc::{self, c_int, c_uchar, c_ulong};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Biggest image read, in pixels, so a bad header cannot ask for gigabytes.
+const MAX_PIXELS: usize = 4096 * 4096;
+
+type Uncompress = unsafe extern "C" fn(*mut c_uchar, *mut c_ulong, *const c_uchar, c_ulong) -> c_int;
+
+/// An 8 bit RGBA image, rows from the top down.
+pub struct Image {
+    pub w: usize,
+    pub h: usize,
+    pub pixels: Vec<u8>,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+/// Inflates zlib data that is known to come out at `size` bytes, with zlib
+/// loaded at run time like libvorbisfile.
+fn inflate(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let unavailable = |err: String| io::Error::new(io::ErrorKind::Other, format!("cannot inflate: {}", err));
+    let lib_name = CString::new("libz.so.1").expect("No NUL");
+    unsafe {
+        let lib = libc::dlopen(lib_name.as_ptr(), libc::RTLD_NOW);
+        if lib.is_null() {
+            return Err(unavailable("zlib is not installed".into()));
+        }
+        let result = (|| {
+            let uncompress: Uncompress = symbol(lib, "uncompress").map_err(unavailable)?;
+            let mut out = vec![0u8; size];
+            let mut out_len = size as c_ulong;
+            let status = uncompress(out.as_mut_ptr(), &mut out_len, data.as_ptr(), data.len() as c_ulong);
+            if status != 0 || out_len as usize != size {
+                return Err(invalid_data(format!("bad image data (zlib error {})", status)));
+            }
+            Ok(out)
+        })();
+        libc::dlclose(lib);
+        result
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Undoes the per-row filters in place. Each row starts with its filter
+/// type, the rows come out packed without it.
+fn unfilter(data: &mut Vec<u8>, stride: usize, bpp: usize) -> io::Result<()> {
+    let rows = data.len() / (stride + 1);
+    let mut prev = vec![0u8; stride];
+    for row in 0..rows {
+        let start = row * (stride + 1);
+        let filter = data[start];
+        let mut line = data[start + 1..start + 1 + stride].to_vec();
+        for i in 0..stride {
+            let a = if i >= bpp { line[i - bpp] } else { 0 };
+            let (b, c) = (prev[i], if i >= bpp { prev[i - bpp] } else { 0 });
+            line[i] = match filter {
+                0 => line[i],
+                1 => line[i].wrapping_add(a),
+                2 => line[i].wrapping_add(b),
+                3 => line[i].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                4 => line[i].wrapping_add(paeth(a, b, c)),
+                _ => return Err(invalid_data(format!("unknown row filter {}", filter))),
+            };
+        }
+        data[row * stride..(row + 1) * stride].copy_from_slice(&line);
+        prev = line;
+    }
+    data.truncate(rows * stride);
+    Ok(())
+}
+
+/// Reads a PNG as RGBA. Handles 8 bit gray, gray and alpha, RGB, RGBA and
+/// paletted images without interlacing, which is what image editors write
+/// by default.
+pub fn read_png<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(SIGNATURE) {
+        return Err(invalid_data("not a PNG file".into()));
+    }
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut data = Vec::new();
+    let mut at = SIGNATURE.len();
+    while at + 8 <= bytes.len() {
+        let len = read_u32(&bytes[at..]) as usize;
+        let kind = &bytes[at + 4..at + 8];
+        let body = bytes.get(at + 8..at + 8 + len).ok_or_else(|| invalid_data("truncated chunk".into()))?;
+        match kind {
+            b"IHDR" if len == 13 => {
+                // Width, height, bit depth, color type and interlacing.
+                header = Some((read_u32(body) as usize, read_u32(&body[4..]) as usize, body[8], body[9], body[12]));
+            },
+            b"PLTE" if len / 3 * 3 != len => return Err(invalid_data(format!("palette of {} bytes", len))),
+            b"PLTE" => palette = body.chunks(3).map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+            b"tRNS" => {
+                for (entry, &alpha) in palette.iter_mut().zip(body.iter()) {
+                    entry[3] = alpha;
+                }
+            },
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => (),
+        }
+        // Length, type, data and CRC.
+        at += 12 + len;
+    }
+    let (w, h, depth, color_type, interlace) = header.ok_or_else(|| invalid_data("missing header".into()))?;
+    let channels = match color_type {
+        0 => 1,
+        2 => 3,
+        3 => 1,
+        4 => 2,
+        6 => 4,
+        _ => return Err(invalid_data(format!("unknown color type {}", color_type))),
+    };
+    if depth != 8 || interlace != 0 {
+        return Err(invalid_data("only 8 bit images without interlacing are supported".into()));
+    }
+    if w == 0 || h == 0 || w > MAX_PIXELS / h {
+        return Err(invalid_data(format!("bad image size {}x{}", w, h)));
+    }
+    if color_type == 3 && palette.is_empty() {
+        return Err(invalid_data("missing palette".into()));
+    }
+    let stride = w * channels;
+    let mut raw = inflate(&data, h * (stride + 1))?;
+    unfilter(&mut raw, stride, channels)?;
+    let mut pixels = Vec::with_capacity(w * h * 4);
+    for px in raw.chunks(channels) {
+        let rgba = match color_type {
+            0 => [px[0], px[0], px[0], 255],
+            2 => [px[0], px[1], px[2], 255],
+            3 => *palette.get(px[0] as usize).ok_or_else(|| invalid_data("color outside the palette".into()))?,
+            4 => [px[0], px[0], px[0], px[1]],
+            _ => [px[0], px[1], px[2], px[3]],
+        };
+        pixels.extend_from_slice(&rgba);
+    }
+    Ok(Image { w, h, pixels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn chunk(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut bytes = (body.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(body);
+        // read_png does not check CRCs.
+        bytes.extend_from_slice(&[0; 4]);
+        bytes
+    }
+
+    /// zlib data holding `data` in one stored deflate block.
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u16;
+        let mut bytes = vec![0x78, 0x01, 0x01];
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&(!len).to_le_bytes());
+        bytes.extend_from_slice(data);
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        bytes.extend_from_slice(&(b << 16 | a).to_be_bytes());
+        bytes
+    }
+
+    /// A PNG of `w` by `h` with `rows` already filtered, plus `extra` chunks
+    /// before the image data.
+    fn png(w: u32, h: u32, color_type: u8, extra: &[Vec<u8>], rows: &[u8]) -> Vec<u8> {
+        let mut header = w.to_be_bytes().to_vec();
+        header.extend_from_slice(&h.to_be_bytes());
+        header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(chunk(b"IHDR", &header));
+        for extra in extra {
+            bytes.extend_from_slice(extra);
+        }
+        bytes.extend(chunk(b"IDAT", &stored(rows)));
+        bytes.extend(chunk(b"IEND", &[]));
+        bytes
+    }
+
+    fn read(name: &str, bytes: &[u8]) -> io::Result<Image> {
+        let path = env::temp_dir().join(format!("breakout-{}-{}.png", process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let image = read_png(&path);
+        fs::remove_file(&path).unwrap();
+        image
+    }
+
+    #[test]
+    fn reads_rgba() {
+        // Two rows, the first unfiltered and the second with the up filter.
+        let rows = [
+            0, 255, 0, 0, 255, 0, 255, 0, 128,
+            2, 0, 0, 255, 0, 0, 0, 0, 127,
+        ];
+        let image = match read("rgba", &png(2, 2, 6, &[], &rows)) {
+            Ok(image) => image,
+            Err(ref err) if err.kind() == io::ErrorKind::Other => return eprintln!("skipped: {}", err),
+            Err(err) => panic!("{}", err),
+        };
+        assert_eq!((image.w, image.h), (2, 2));
+        assert_eq!(image.pixels, vec![
+            255, 0, 0, 255, 0, 255, 0, 128,
+            255, 0, 255, 255, 0, 255, 0, 255,
+        ]);
+    }
+
+    #[test]
+    fn reads_paletted() {
+        let palette = chunk(b"PLTE", &[255, 255, 255, 0, 0, 255]);
+        let alpha = chunk(b"tRNS", &[0]);
+        let image = match read("paletted", &png(3, 1, 3, &[palette, alpha], &[0, 1, 0, 1])) {
+            Ok(image) => image,
+            Err(ref err) if err.kind() == io::ErrorKind::Other => return eprintln!("skipped: {}", err),
+            Err(err) => panic!("{}", err),
+        };
+        assert_eq!((image.w, image.h), (3, 1));
+        assert_eq!(image.pixels, vec![0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_bad_palettes_and_sizes() {
+        let palette = chunk(b"PLTE", &[255, 255, 255, 0]);
+        let err = read("bad-palette", &png(1, 1, 3, &[palette], &[0, 0])).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read("huge", &png(100_000, 100_000, 6, &[], &[0])).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read("empty", &png(0, 4, 6, &[], &[0])).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(read("not-png", b"GIF89a").is_err());
+    }
+}
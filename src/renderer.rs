@@ -9,6 +9,7 @@ use graphics::{
     Shader,
 };
 use math::Vec2;
+use sprites::Sprite;
 
 macro_rules! offset_of {
     ($ty: ty, $field: ident) => {{
@@ -50,6 +51,16 @@ struct LineVertex {
     color: [f32; 4],
 }
 
+#[derive(Clone, Copy, Debug)]
+struct SpriteVertex {
+    bound: [f32; 4],
+    uv_bound: [f32; 4],
+    color: [f32; 4],
+    /// Point the quad turns about.
+    pivot: [f32; 2],
+    rotation: f32,
+}
+
 #[derive(Clone, Copy, Debug)]
 struct TextVertex {
     bound: [f32; 4],
@@ -81,6 +92,16 @@ struct LineTechnique {
     vertices: Vec<LineVertex>,
 }
 
+struct SpriteTechnique {
+    program: GLuint,
+    uniform_viewport: GLint,
+    uniform_sprite_tex: GLint,
+    vao: GLuint,
+    vbo: GLuint,
+    /// Quads by the atlas page they are on, one draw call each.
+    batches: Vec<(GLuint, Vec<SpriteVertex>)>,
+}
+
 struct TextTechnique {
     program: GLuint, 
     uniform_viewport: GLint,
@@ -93,6 +114,7 @@ pub struct Renderer {
     rect_tech: RectTechnique,
     circle_tech: CircleTechnique,
     line_tech: LineTechnique,
+    sprite_tech: SpriteTechnique,
     text_tech: TextTechnique,
 }
 
@@ -101,6 +123,16 @@ impl Color {
         Color { r, g, b, a }
     }
 
+    /// The color `t` of the way from `self` to `other`, channel by channel.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        Color::new(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+
     fn to_array(&self) -> [f32; 4] {
         [self.r, self.g, self.b, self.a]
     }
@@ -170,7 +202,10 @@ impl RectTechnique {
             self.vertices.as_ptr() as _,
             gl::STREAM_DRAW,
         );
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         gl::DrawArrays(gl::POINTS, 0, self.vertices.len() as _);
+        gl::Disable(gl::BLEND);
     }}
 }
 
@@ -237,7 +272,10 @@ impl CircleTechnique {
             self.vertices.as_ptr() as _,
             gl::STREAM_DRAW,
         );
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         gl::DrawArrays(gl::POINTS, 0, self.vertices.len() as _);
+        gl::Disable(gl::BLEND);
     }}
 }
 
@@ -299,6 +337,112 @@ impl LineTechnique {
     }}
 }
 
+impl SpriteTechnique {
+    pub fn new() -> Self { unsafe {
+        let program = Program::new(&[
+            Shader::from_file(gl::VERTEX_SHADER, "res/shaders/sprite.vert").expect("Cannot initialize GL resources"),
+            Shader::from_file(gl::GEOMETRY_SHADER, "res/shaders/sprite.geom").expect("Cannot initialize GL resources"),
+            Shader::from_file(gl::FRAGMENT_SHADER, "res/shaders/sprite.frag").expect("Cannot initialize GL resources"),
+        ]).expect("Cannot initialize GL resources");
+        let uniform_viewport = program.get_uniform_location("viewport");
+        let uniform_sprite_tex = program.get_uniform_location("sprite_tex");
+        let mut vao = 0;
+        let mut vbo = 0;
+        gl::GenVertexArrays(1, &mut vao);
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindVertexArray(vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(
+            0,
+            4,
+            gl::FLOAT,
+            gl::FALSE,
+            mem::size_of::<SpriteVertex>() as _,
+            offset_of!(SpriteVertex, bound) as _,
+        );
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(
+            1,
+            4,
+            gl::FLOAT,
+            gl::FALSE,
+            mem::size_of::<SpriteVertex>() as _,
+            offset_of!(SpriteVertex, uv_bound) as _,
+        );
+        gl::EnableVertexAttribArray(2);
+        gl::VertexAttribPointer(
+            2,
+            4,
+            gl::FLOAT,
+            gl::FALSE,
+            mem::size_of::<SpriteVertex>() as _,
+            offset_of!(SpriteVertex, color) as _,
+        );
+        gl::EnableVertexAttribArray(3);
+        gl::VertexAttribPointer(
+            3,
+            2,
+            gl::FLOAT,
+            gl::FALSE,
+            mem::size_of::<SpriteVertex>() as _,
+            offset_of!(SpriteVertex, pivot) as _,
+        );
+        gl::EnableVertexAttribArray(4);
+        gl::VertexAttribPointer(
+            4,
+            1,
+            gl::FLOAT,
+            gl::FALSE,
+            mem::size_of::<SpriteVertex>() as _,
+            offset_of!(SpriteVertex, rotation) as _,
+        );
+        SpriteTechnique { program: program.id, uniform_viewport, uniform_sprite_tex, vao, vbo, batches: Vec::new() }
+    }}
+
+    pub fn begin_batch(&mut self) {
+        for &mut (_, ref mut vertices) in self.batches.iter_mut() {
+            vertices.clear();
+        }
+    }
+
+    pub fn draw(&mut self, texture: GLuint, vertex: SpriteVertex) {
+        match self.batches.iter_mut().find(|&&mut (t, _)| t == texture) {
+            Some(&mut (_, ref mut vertices)) => vertices.push(vertex),
+            None => self.batches.push((texture, vec![vertex])),
+        }
+    }
+
+    pub fn end_batch(&mut self, viewport: &[f32; 4]) { unsafe {
+        gl::UseProgram(self.program);
+        gl::Uniform4f(self.uniform_viewport, viewport[0], viewport[1], viewport[2], viewport[3]);
+        gl::Uniform1i(self.uniform_sprite_tex, 0);
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        for &(texture, ref vertices) in self.batches.iter().filter(|&&(_, ref vertices)| !vertices.is_empty()) {
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (mem::size_of::<SpriteVertex>() * vertices.len()) as _,
+                vertices.as_ptr() as _,
+                gl::STREAM_DRAW,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::DrawArrays(gl::POINTS, 0, vertices.len() as _);
+        }
+        gl::Disable(gl::BLEND);
+    }}
+}
+
+/// Splits `size` into the three spans of a 9-slice, `lo` and `hi` at the
+/// ends. Ends that do not fit shrink evenly.
+fn slice_spans(size: f32, lo: f32, hi: f32) -> [f32; 3] {
+    let scale = if lo + hi > size && lo + hi > 0. { size / (lo + hi) } else { 1. };
+    [lo * scale, size - (lo + hi) * scale, hi * scale]
+}
+
 impl TextTechnique {
     pub fn new() -> Self { unsafe {
         let program = Program::new(&[
@@ -402,6 +546,7 @@ impl Renderer {
             rect_tech: RectTechnique::new(),
             circle_tech: CircleTechnique::new(),
             line_tech: LineTechnique::new(),
+            sprite_tech: SpriteTechnique::new(),
             text_tech: TextTechnique::new(),
         }
     }
@@ -410,6 +555,7 @@ impl Renderer {
         self.rect_tech.begin_batch();
         self.circle_tech.begin_batch();
         self.line_tech.begin_batch();
+        self.sprite_tech.begin_batch();
     }
 
     pub fn draw_rectangle(&mut self, p: Vec2, w: f32, h: f32, color: Color) {
@@ -424,11 +570,60 @@ impl Renderer {
         self.line_tech.draw(from.x, from.y, to.x, to.y, &color.to_array());
     }
 
+    /// Draws `sprite` stretched over `w` by `h` from `p`, multiplied by
+    /// `tint` and turned `rotation` radians counterclockwise about its
+    /// middle. A 9-slice sprite is drawn as nine quads so its corners keep
+    /// their size.
+    pub fn draw_sprite(&mut self, sprite: &Sprite, p: Vec2, w: f32, h: f32, rotation: f32, tint: Color) {
+        let pivot = [p.x + w / 2., p.y + h / 2.];
+        let color = tint.to_array();
+        let slice = match sprite.slice {
+            Some(slice) => slice,
+            None => {
+                self.sprite_tech.draw(sprite.texture, SpriteVertex {
+                    bound: [p.x, p.y, w, h],
+                    uv_bound: [sprite.uv_min.x, sprite.uv_min.y, sprite.uv_max.x, sprite.uv_max.y],
+                    color,
+                    pivot,
+                    rotation,
+                });
+                return;
+            },
+        };
+        // Spans on screen and in the texture, left to right and bottom to top.
+        let xs = slice_spans(w, slice.left, slice.right);
+        let ys = slice_spans(h, slice.bottom, slice.top);
+        let (du, dv) = ((sprite.uv_max.x - sprite.uv_min.x) / sprite.w, (sprite.uv_max.y - sprite.uv_min.y) / sprite.h);
+        let us = [slice.left * du, (sprite.w - slice.left - slice.right) * du, slice.right * du];
+        let vs = [slice.bottom * dv, (sprite.h - slice.bottom - slice.top) * dv, slice.top * dv];
+        let (mut y, mut v) = (p.y, sprite.uv_min.y);
+        for j in 0..3 {
+            let (mut x, mut u) = (p.x, sprite.uv_min.x);
+            for i in 0..3 {
+                if xs[i] > 0. && ys[j] > 0. {
+                    self.sprite_tech.draw(sprite.texture, SpriteVertex {
+                        bound: [x, y, xs[i], ys[j]],
+                        uv_bound: [u, v, u + us[i], v + vs[j]],
+                        color,
+                        pivot,
+                        rotation,
+                    });
+                }
+                x += xs[i];
+                u += us[i];
+            }
+            y += ys[j];
+            v += vs[j];
+        }
+    }
+
     pub fn draw_text(&mut self, viewport: &Viewport, text: &str, p: Vec2, color: Color, font: &Font) {
         self.text_tech.draw(&viewport.to_array(), text, p, color, font);
     }
 
+    /// Draws the batch. Sprites go under the flat shapes.
     pub fn end_batch(&mut self, viewport: &Viewport) {
+        self.sprite_tech.end_batch(&viewport.to_array());
         self.rect_tech.end_batch(&viewport.to_array());
         self.circle_tech.end_batch(&viewport.to_array());
         self.line_tech.end_batch(&viewport.to_array());
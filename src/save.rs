@@ -0,0 +1,261 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use math::Vec2;
+use scoring::Score;
+use storage::{read_versioned, write_atomic};
+
+const MAGIC: &str = "breakout-save";
+const VERSION: u32 = 2;
+
+#[derive(Clone, Debug)]
+pub struct SavedBall {
+    pub p: Vec2,
+    pub v: Vec2,
+    pub combo: i32,
+}
+
+/// An in-progress level. Blocks are identified by their load order in the
+/// level file, so the level itself is reloaded and then trimmed down.
+/// Version 1 saves kept only the ids, their blocks come back undamaged.
+#[derive(Clone, Debug, Default)]
+pub struct LevelSnapshot {
+    pub time: f32,
+    pub balls_lost: i32,
+    pub is_launching_ball: bool,
+    pub paddle_x: f32,
+    pub paddle_dx: f32,
+    pub balls: Vec<SavedBall>,
+    pub block_count: usize,
+    /// The id and hits taken of each block still in play.
+    pub blocks: Vec<(usize, i32)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SaveGame {
+    pub pack: String,
+    pub mode: String,
+    pub level: usize,
+    pub balls_left: i32,
+    pub score: Score,
+    /// Whether assists were on at any point in the game so far.
+    pub is_assisted: bool,
+    pub snapshot: Option<LevelSnapshot>,
+}
+
+fn parse_values<T: ::std::str::FromStr>(key: &str, values: &[&str], n: usize) -> Result<Vec<T>, String> {
+    if values.len() != n {
+        return Err(format!("'{}' expects {} values", key, n));
+    }
+    values.iter()
+        .map(|v| v.parse().map_err(|_| format!("Invalid value '{}' for '{}'", v, key)))
+        .collect()
+}
+
+fn parse_value<T: ::std::str::FromStr>(key: &str, values: &[&str]) -> Result<T, String> {
+    Ok(parse_values(key, values, 1)?.remove(0))
+}
+
+impl SaveGame {
+    /// Reads the save at `path`, if there is a usable one. Unknown keys are
+    /// skipped so saves from newer builds of the same version still load.
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<SaveGame> {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                eprintln!("Warning: cannot read {}: {}", path.display(), err);
+                return None;
+            },
+        };
+        let result = read_versioned(&contents, MAGIC)
+            .map_err(|err| err.to_string())
+            .and_then(|(version, body)| {
+                if version > VERSION {
+                    Err(format!("unsupported version {}", version))
+                } else {
+                    SaveGame::parse(version, body)
+                }
+            });
+        match result {
+            Ok(save) => Some(save),
+            Err(err) => {
+                eprintln!("Warning: ignoring save {}: {}", path.display(), err);
+                None
+            },
+        }
+    }
+
+    fn parse(version: u32, body: &str) -> Result<SaveGame, String> {
+        let mut save = SaveGame {
+            pack: String::new(),
+            mode: String::new(),
+            level: 0,
+            balls_left: 0,
+            score: Score::default(),
+            is_assisted: false,
+            snapshot: None,
+        };
+        let mut snapshot = LevelSnapshot::default();
+        let mut has_snapshot = false;
+        for line in body.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let (key, values) = match tokens.split_first() {
+                Some((key, values)) => (*key, values),
+                None => continue,
+            };
+            match key {
+                "pack" => save.pack = values.join(" "),
+                "mode" => save.mode = values.join(" "),
+                "level" => save.level = parse_value(key, values)?,
+                "balls_left" => save.balls_left = parse_value(key, values)?,
+                "score.blocks" => save.score.blocks = parse_value(key, values)?,
+                "score.combo" => save.score.combo = parse_value(key, values)?,
+                "score.rows" => save.score.rows = parse_value(key, values)?,
+                "score.clusters" => save.score.clusters = parse_value(key, values)?,
+                "score.time" => save.score.time = parse_value(key, values)?,
+                "score.no_miss" => save.score.no_miss = parse_value(key, values)?,
+                "assisted" => save.is_assisted = parse_value::<i32>(key, values)? != 0,
+                "snapshot.time" => {
+                    has_snapshot = true;
+                    snapshot.time = parse_value(key, values)?;
+                },
+                "snapshot.balls_lost" => snapshot.balls_lost = parse_value(key, values)?,
+                "snapshot.launching" => snapshot.is_launching_ball = parse_value::<i32>(key, values)? != 0,
+                "snapshot.paddle" => {
+                    let v = parse_values(key, values, 2)?;
+                    snapshot.paddle_x = v[0];
+                    snapshot.paddle_dx = v[1];
+                },
+                "snapshot.ball" => {
+                    let v: Vec<f32> = parse_values(key, values, 5)?;
+                    snapshot.balls.push(SavedBall {
+                        p: Vec2::new(v[0], v[1]),
+                        v: Vec2::new(v[2], v[3]),
+                        combo: v[4] as i32,
+                    });
+                },
+                "snapshot.blocks" => {
+                    let (count, blocks) = values.split_first().ok_or_else(|| "Empty 'snapshot.blocks'".to_string())?;
+                    snapshot.block_count = parse_value(key, &[*count])?;
+                    snapshot.blocks = if version < 2 {
+                        parse_values(key, blocks, blocks.len())?.into_iter().map(|id| (id, 0)).collect()
+                    } else {
+                        blocks.chunks(2)
+                            .map(|pair| match *pair {
+                                [id, hits] => Ok((parse_value(key, &[id])?, parse_value(key, &[hits])?)),
+                                _ => Err(format!("'{}' expects id and hits pairs", key)),
+                            })
+                            .collect::<Result<_, _>>()?
+                    };
+                },
+                _ => (),
+            }
+        }
+        if save.pack.is_empty() || save.mode.is_empty() {
+            return Err("Missing pack or mode".into());
+        }
+        if has_snapshot {
+            save.snapshot = Some(snapshot);
+        }
+        Ok(save)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut lines = vec![
+            format!("{} {}", MAGIC, VERSION),
+            format!("pack {}", self.pack),
+            format!("mode {}", self.mode),
+            format!("level {}", self.level),
+            format!("balls_left {}", self.balls_left),
+        ];
+        for &(key, points) in [
+            ("blocks", self.score.blocks),
+            ("combo", self.score.combo),
+            ("rows", self.score.rows),
+            ("clusters", self.score.clusters),
+            ("time", self.score.time),
+            ("no_miss", self.score.no_miss),
+        ].iter() {
+            lines.push(format!("score.{} {}", key, points));
+        }
+        lines.push(format!("assisted {}", self.is_assisted as i32));
+        if let Some(ref snapshot) = self.snapshot {
+            lines.push(format!("snapshot.time {}", snapshot.time));
+            lines.push(format!("snapshot.balls_lost {}", snapshot.balls_lost));
+            lines.push(format!("snapshot.launching {}", snapshot.is_launching_ball as i32));
+            lines.push(format!("snapshot.paddle {} {}", snapshot.paddle_x, snapshot.paddle_dx));
+            for ball in snapshot.balls.iter() {
+                lines.push(format!(
+                    "snapshot.ball {} {} {} {} {}",
+                    ball.p.x, ball.p.y, ball.v.x, ball.v.y, ball.combo,
+                ));
+            }
+            let blocks = snapshot.blocks.iter().map(|&(id, hits)| format!("{} {}", id, hits)).collect::<Vec<_>>();
+            lines.push(format!("snapshot.blocks {} {}", snapshot.block_count, blocks.join(" ")));
+        }
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        write_atomic(path, contents.as_bytes())
+    }
+
+    pub fn delete<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn save_with(blocks: Vec<(usize, i32)>) -> SaveGame {
+        SaveGame {
+            pack: "default".into(),
+            mode: "campaign".into(),
+            level: 2,
+            balls_left: 1,
+            score: Score { blocks: 40, ..Score::default() },
+            is_assisted: false,
+            snapshot: Some(LevelSnapshot { time: 12.5, block_count: 6, blocks, ..LevelSnapshot::default() }),
+        }
+    }
+
+    #[test]
+    fn damaged_blocks_round_trip() {
+        let path = env::temp_dir().join(format!("breakout-{}-snapshot.txt", process::id()));
+        save_with(vec![(0, 0), (3, 2), (5, 1)]).save(&path).unwrap();
+        let save = SaveGame::load(&path);
+        fs::remove_file(&path).unwrap();
+        let snapshot = save.unwrap().snapshot.unwrap();
+        assert_eq!(snapshot.block_count, 6);
+        assert_eq!(snapshot.blocks, vec![(0, 0), (3, 2), (5, 1)]);
+    }
+
+    #[test]
+    fn keeps_the_assisted_flag() {
+        let path = env::temp_dir().join(format!("breakout-{}-assisted-save.txt", process::id()));
+        let mut save = save_with(Vec::new());
+        save.is_assisted = true;
+        save.save(&path).unwrap();
+        let loaded = SaveGame::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.unwrap().is_assisted);
+        // Saves from before the flag count as unassisted.
+        assert!(!SaveGame::parse(1, "pack default\nmode campaign\nlevel 2\n").unwrap().is_assisted);
+    }
+
+    #[test]
+    fn reads_version_1_block_ids() {
+        let body = "pack default\nmode campaign\nsnapshot.time 3\nsnapshot.blocks 6 0 3 5\n";
+        let snapshot = SaveGame::parse(1, body).unwrap().snapshot.unwrap();
+        assert_eq!(snapshot.blocks, vec![(0, 0), (3, 0), (5, 0)]);
+        assert!(SaveGame::parse(2, "pack default\nmode campaign\nsnapshot.time 3\nsnapshot.blocks 6 0 3 5\n").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::mem;
+
+use math::Vec2;
+use renderer::{Color, Renderer, Viewport};
+use tween::Ease;
+
+const FADE_TIME: f32 = 0.6;
+const WIPE_TIME: f32 = 0.7;
+const IRIS_TIME: f32 = 0.9;
+
+/// How the screen goes from one scene to the next. Each covers the old
+/// scene, swaps in the new one while nothing shows and uncovers it again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transition {
+    /// Swaps on the spot.
+    Cut,
+    /// Through black.
+    Fade,
+    /// A black band sweeps across from left to right.
+    Wipe,
+    /// A black disc opens out from the middle and closes back in.
+    Iris,
+}
+
+/// A swap of the base scene in progress.
+struct Change<S> {
+    /// The incoming scene, until the old one is covered up.
+    next: Option<S>,
+    transition: Transition,
+    time: f32,
+}
+
+/// A base scene, like the title or a level, with overlays such as the pause
+/// menu stacked on top. Only the topmost scene takes input and runs, the ones
+/// below stay frozen and show through.
+pub struct SceneStack<S> {
+    pub base: S,
+    /// Topmost last.
+    pub overlays: Vec<S>,
+    change: Option<Change<S>>,
+}
+
+impl Transition {
+    fn duration(&self) -> f32 {
+        match *self {
+            Transition::Cut => 0.,
+            Transition::Fade => FADE_TIME,
+            Transition::Wipe => WIPE_TIME,
+            Transition::Iris => IRIS_TIME,
+        }
+    }
+}
+
+impl<S> Change<S> {
+    /// How much of the screen is hidden, from 0 to 1 and back.
+    fn cover(&self) -> f32 {
+        let half = self.transition.duration() / 2.;
+        if half <= 0. {
+            0.
+        } else if self.next.is_some() {
+            (self.time / half).min(1.)
+        } else {
+            (2. - self.time / half).max(0.)
+        }
+    }
+}
+
+impl<S> SceneStack<S> {
+    pub fn new(base: S) -> Self {
+        SceneStack { base, overlays: Vec::new(), change: None }
+    }
+
+    pub fn top(&self) -> &S {
+        self.overlays.last().unwrap_or(&self.base)
+    }
+
+    pub fn top_mut(&mut self) -> &mut S {
+        match self.overlays.last_mut() {
+            Some(overlay) => overlay,
+            None => &mut self.base,
+        }
+    }
+
+    /// Every scene from the base up, in drawing order.
+    pub fn iter(&self) -> impl Iterator<Item = &S> {
+        Some(&self.base).into_iter().chain(self.overlays.iter())
+    }
+
+    pub fn push(&mut self, overlay: S) {
+        self.overlays.push(overlay);
+    }
+
+    /// Removes the topmost overlay. The base always stays.
+    pub fn pop(&mut self) -> Option<S> {
+        self.overlays.pop()
+    }
+
+    /// Replaces the base and drops the overlays through `transition`. Until
+    /// the old scene is covered it stays where it is. Switching again midway
+    /// carries on from however much is covered.
+    pub fn switch(&mut self, scene: S, transition: Transition) {
+        let cover = self.change.as_ref().map_or(0., Change::cover);
+        self.change = Some(Change {
+            next: Some(scene),
+            transition,
+            time: cover * transition.duration() / 2.,
+        });
+    }
+
+    pub fn is_switching(&self) -> bool {
+        self.change.is_some()
+    }
+
+    /// Moves a switch on by `dt` seconds. Returns the old base on the step
+    /// the new one takes its place.
+    pub fn step(&mut self, dt: f32) -> Option<S> {
+        let mut change = self.change.take()?;
+        change.time += dt;
+        let mut old = None;
+        if change.time >= change.transition.duration() / 2. {
+            if let Some(next) = change.next.take() {
+                self.overlays.clear();
+                old = Some(mem::replace(&mut self.base, next));
+            }
+        }
+        if change.time < change.transition.duration() {
+            self.change = Some(change);
+        }
+        old
+    }
+
+    /// Draws a switch in progress over the whole viewport.
+    pub fn draw_transition(&self, renderer: &mut Renderer, viewport: &Viewport) {
+        let change = match self.change {
+            Some(ref change) => change,
+            None => return,
+        };
+        let cover = Ease::InOutSine.apply(change.cover());
+        let (w, h) = (viewport.w, viewport.h);
+        let black = Color::new(0., 0., 0., 1.);
+        renderer.begin_batch();
+        match change.transition {
+            Transition::Cut => (),
+            Transition::Fade => renderer.draw_rectangle(Vec2::default(), w, h, Color::new(0., 0., 0., cover)),
+            Transition::Wipe => {
+                // Comes in from the left edge and leaves by the right one.
+                let x = if change.next.is_some() { 0. } else { (1. - cover) * w };
+                renderer.draw_rectangle(Vec2::new(x, 0.), cover * w, h, black);
+            },
+            Transition::Iris => {
+                let radius = cover * (w * w + h * h).sqrt() / 2.;
+                renderer.draw_circle(Vec2::new(w / 2., h / 2.), radius, black);
+            },
+        }
+        renderer.end_batch(viewport);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cover(stack: &SceneStack<&'static str>) -> f32 {
+        stack.change.as_ref().map_or(0., Change::cover)
+    }
+
+    #[test]
+    fn swaps_when_the_old_scene_is_covered() {
+        let mut stack = SceneStack::new("title");
+        stack.push("pause");
+        stack.switch("level", Transition::Fade);
+        assert_eq!(stack.step(FADE_TIME / 4.), None);
+        assert_eq!((stack.base, stack.overlays.len()), ("title", 1));
+        assert_eq!(cover(&stack), 0.5);
+
+        assert_eq!(stack.step(FADE_TIME / 4.), Some("title"));
+        assert_eq!((stack.base, stack.overlays.len()), ("level", 0));
+        assert_eq!(cover(&stack), 1.);
+        assert!(stack.is_switching());
+
+        assert_eq!(stack.step(FADE_TIME / 4.), None);
+        assert_eq!(cover(&stack), 0.5);
+        assert_eq!(stack.step(FADE_TIME / 4.), None);
+        assert!(!stack.is_switching());
+        assert_eq!(stack.base, "level");
+    }
+
+    #[test]
+    fn interrupted_switch_carries_on_from_its_cover() {
+        let mut stack = SceneStack::new("title");
+        stack.switch("level", Transition::Fade);
+        stack.step(FADE_TIME / 4.);
+        stack.switch("editor", Transition::Wipe);
+        assert_eq!(cover(&stack), 0.5);
+        assert_eq!(stack.step(WIPE_TIME / 4.), Some("title"));
+        assert_eq!(stack.base, "editor");
+
+        // Part way through uncovering, the next switch covers up again from
+        // there instead of starting over.
+        stack.step(WIPE_TIME / 4.);
+        assert_eq!(cover(&stack), 0.5);
+        stack.switch("level", Transition::Iris);
+        assert_eq!(cover(&stack), 0.5);
+        assert_eq!(stack.step(IRIS_TIME / 8.), None);
+        assert_eq!(stack.step(IRIS_TIME / 8.), Some("editor"));
+        assert_eq!(stack.base, "level");
+    }
+
+    #[test]
+    fn cut_swaps_on_the_next_step() {
+        let mut stack = SceneStack::new("title");
+        stack.push("pause");
+        stack.switch("level", Transition::Cut);
+        assert_eq!(stack.base, "title");
+        assert!(stack.is_switching());
+        assert_eq!(stack.step(0.), Some("title"));
+        assert_eq!((stack.base, stack.overlays.len()), ("level", 0));
+        assert!(!stack.is_switching());
+        assert_eq!(stack.step(1.), None);
+    }
+}
@@ -0,0 +1,100 @@
+use std::cmp;
+
+use math::Vec2;
+
+pub const POINTS_PER_BLOCK: i32 = 10;
+pub const MAX_COMBO_MULTIPLIER: i32 = 8;
+pub const ROW_CLEAR_BONUS: i32 = 50;
+pub const CLUSTER_CLEAR_BONUS: i32 = 100;
+pub const TIME_BONUS_PER_SECOND: i32 = 5;
+pub const NO_MISS_BONUS: i32 = 250;
+
+const POPUP_LIFETIME: f32 = 0.8;
+const POPUP_RISE_SPEED: f32 = 48.;
+
+/// Running score split by where the points came from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Score {
+    pub blocks: i32,
+    pub combo: i32,
+    pub rows: i32,
+    pub clusters: i32,
+    pub time: i32,
+    pub no_miss: i32,
+}
+
+/// A set of blocks that pays a bonus once all of them are broken.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockGroup {
+    pub size: i32,
+    pub remaining: i32,
+}
+
+pub struct ScorePopup {
+    pub p: Vec2,
+    pub text: String,
+    pub age: f32,
+}
+
+impl Score {
+    pub fn total(&self) -> i32 {
+        self.blocks + self.combo + self.rows + self.clusters + self.time + self.no_miss
+    }
+
+    pub fn breakdown(&self) -> [(&'static str, i32); 6] {
+        [
+            ("Blocks", self.blocks),
+            ("Combos", self.combo),
+            ("Rows", self.rows),
+            ("Clusters", self.clusters),
+            ("Time", self.time),
+            ("No miss", self.no_miss),
+        ]
+    }
+}
+
+impl BlockGroup {
+    pub fn new(size: i32) -> Self {
+        BlockGroup { size, remaining: size }
+    }
+
+    /// Removes one block, returning true if that cleared a group worth a bonus.
+    /// Lone blocks do not count as a group.
+    pub fn remove_one(&mut self) -> bool {
+        self.remaining -= 1;
+        self.remaining == 0 && self.size > 1
+    }
+}
+
+impl ScorePopup {
+    pub fn new(p: Vec2, text: String) -> Self {
+        ScorePopup { p, text, age: 0. }
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        self.age += dt;
+        self.p.y += POPUP_RISE_SPEED * dt;
+    }
+
+    pub fn alpha(&self) -> f32 {
+        1. - self.age / POPUP_LIFETIME
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.age >= POPUP_LIFETIME
+    }
+}
+
+/// Multiplier for the `combo`-th consecutive block hit since the ball last
+/// touched the paddle.
+pub fn combo_multiplier(combo: i32) -> i32 {
+    cmp::min(cmp::max(combo, 1), MAX_COMBO_MULTIPLIER)
+}
+
+pub fn time_bonus(par_time: f32, time: f32) -> i32 {
+    if time < par_time {
+        ((par_time - time) * TIME_BONUS_PER_SECOND as f32) as i32
+    } else {
+        0
+    }
+}
@@ -0,0 +1,421 @@
+use glutin::VirtualKeyCode;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use keys::{key_name, parse_key};
+use storage::{read_versioned, write_atomic};
+use theme::{theme_names, PACK_THEME};
+
+const MAGIC: &str = "breakout-settings";
+const VERSION: u32 = 1;
+pub const MAX_KEYS_PER_CONTROL: usize = 3;
+
+const MOUSE_SENSITIVITY_STEPS: &[f32] = &[0.25, 0.5, 0.75, 1., 1.5, 2., 3.];
+/// What networked games play with, whatever either side has set.
+pub const DEFAULT_PADDLE_ACCELERATION: f32 = 96.;
+const PADDLE_ACCELERATION_STEPS: &[f32] = &[48., 64., 80., 96., 128., 160.];
+const VOLUME_STEP: f32 = 0.1;
+/// Shares of the full effect, for the options that tone one down.
+const STRENGTH_STEPS: &[f32] = &[0., 0.25, 0.5, 0.75, 1.];
+const GAME_SPEED_STEPS: &[f32] = &[0.5, 0.6, 0.7, 0.8, 0.9, 1.];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    MoveLeft,
+    MoveRight,
+    Launch,
+    Pause,
+    Player2MoveLeft,
+    Player2MoveRight,
+    Player2Launch,
+}
+
+pub const CONTROLS: &[Control] = &[
+    Control::MoveLeft,
+    Control::MoveRight,
+    Control::Launch,
+    Control::Pause,
+    Control::Player2MoveLeft,
+    Control::Player2MoveRight,
+    Control::Player2Launch,
+];
+
+/// A settings entry that can be changed from the settings menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettingOption {
+    MouseSensitivity,
+    PaddleAcceleration,
+    Fullscreen,
+    Vsync,
+    Volume,
+    MusicVolume,
+    ReducedMotion,
+    ScreenShake,
+    HitStop,
+    BallSpeedUp,
+    GameSpeed,
+    AimAssist,
+    Theme,
+}
+
+pub const OPTIONS: &[SettingOption] = &[
+    SettingOption::MouseSensitivity,
+    SettingOption::PaddleAcceleration,
+    SettingOption::Fullscreen,
+    SettingOption::Vsync,
+    SettingOption::Volume,
+    SettingOption::MusicVolume,
+    SettingOption::Theme,
+];
+
+/// Options for players who need the game gentler or slower, in a menu of
+/// their own.
+pub const ACCESSIBILITY_OPTIONS: &[SettingOption] = &[
+    SettingOption::ReducedMotion,
+    SettingOption::ScreenShake,
+    SettingOption::HitStop,
+    SettingOption::BallSpeedUp,
+    SettingOption::GameSpeed,
+    SettingOption::AimAssist,
+];
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub bindings: Vec<(Control, Vec<VirtualKeyCode>)>,
+    pub mouse_sensitivity: f32,
+    pub paddle_acceleration: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub volume: f32,
+    pub music_volume: f32,
+    pub reduced_motion: bool,
+    /// How hard the field shakes when hit, from 0 for not at all to 1.
+    pub screen_shake: f32,
+    /// How long the game stops for on each bounce, from 0 to 1 of the usual.
+    pub hit_stop: f32,
+    /// How much of the usual speed-up the ball gets off the paddle.
+    pub ball_speed_up: f32,
+    /// How fast single-player and local games run, 1 for full speed.
+    pub game_speed: f32,
+    /// Whether a line shows where the ball is headed.
+    pub aim_assist: bool,
+    /// A built-in theme or a file in `res/themes`, or `PACK_THEME` for
+    /// whatever the level pack asks for.
+    pub theme: String,
+    path: PathBuf,
+}
+
+impl Control {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Control::MoveLeft => "move_left",
+            Control::MoveRight => "move_right",
+            Control::Launch => "launch",
+            Control::Pause => "pause",
+            Control::Player2MoveLeft => "player2_move_left",
+            Control::Player2MoveRight => "player2_move_right",
+            Control::Player2Launch => "player2_launch",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Control::MoveLeft => "Move left",
+            Control::MoveRight => "Move right",
+            Control::Launch => "Launch",
+            Control::Pause => "Pause",
+            Control::Player2MoveLeft => "Player 2 left",
+            Control::Player2MoveRight => "Player 2 right",
+            Control::Player2Launch => "Player 2 launch",
+        }
+    }
+
+    fn default_keys(&self) -> Vec<VirtualKeyCode> {
+        match *self {
+            Control::MoveLeft => vec![VirtualKeyCode::Left, VirtualKeyCode::A],
+            Control::MoveRight => vec![VirtualKeyCode::Right, VirtualKeyCode::D],
+            Control::Launch => vec![VirtualKeyCode::Space],
+            Control::Pause => vec![VirtualKeyCode::Escape, VirtualKeyCode::P],
+            Control::Player2MoveLeft => vec![VirtualKeyCode::J, VirtualKeyCode::Numpad4],
+            Control::Player2MoveRight => vec![VirtualKeyCode::L, VirtualKeyCode::Numpad6],
+            Control::Player2Launch => vec![VirtualKeyCode::I, VirtualKeyCode::Numpad8],
+        }
+    }
+}
+
+impl SettingOption {
+    fn name(&self) -> &'static str {
+        match *self {
+            SettingOption::MouseSensitivity => "mouse_sensitivity",
+            SettingOption::PaddleAcceleration => "paddle_acceleration",
+            SettingOption::Fullscreen => "fullscreen",
+            SettingOption::Vsync => "vsync",
+            SettingOption::Volume => "volume",
+            SettingOption::MusicVolume => "music_volume",
+            SettingOption::ReducedMotion => "reduced_motion",
+            SettingOption::ScreenShake => "screen_shake",
+            SettingOption::HitStop => "hit_stop",
+            SettingOption::BallSpeedUp => "ball_speed_up",
+            SettingOption::GameSpeed => "game_speed",
+            SettingOption::AimAssist => "aim_assist",
+            SettingOption::Theme => "theme",
+        }
+    }
+}
+
+/// The step after `value`, wrapping around to the first.
+fn next_step(steps: &[f32], value: f32) -> f32 {
+    steps.iter().cloned().find(|&step| step > value + 0.001).unwrap_or(steps[0])
+}
+
+/// The volume a step up from `volume`, back to silence after full.
+fn next_volume(volume: f32) -> f32 {
+    if volume >= 1. - VOLUME_STEP / 2. {
+        0.
+    } else {
+        ((volume + VOLUME_STEP) * 10.).round() / 10.
+    }
+}
+
+/// A share of an effect as a percentage, or off at none.
+fn percent(value: f32) -> String {
+    if value <= 0. {
+        "Off".into()
+    } else {
+        format!("{}%", (value * 100.).round())
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "on" | "1" => Some(true),
+        "false" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_f32_in(value: &str, min: f32, max: f32) -> Option<f32> {
+    value.parse().ok().filter(|v: &f32| min <= *v && *v <= max)
+}
+
+impl Settings {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Settings {
+            bindings: CONTROLS.iter().map(|&control| (control, control.default_keys())).collect(),
+            mouse_sensitivity: 1.,
+            paddle_acceleration: DEFAULT_PADDLE_ACCELERATION,
+            fullscreen: false,
+            vsync: true,
+            volume: 0.8,
+            music_volume: 0.6,
+            reduced_motion: false,
+            screen_shake: 1.,
+            hit_stop: 1.,
+            ball_speed_up: 1.,
+            game_speed: 1.,
+            aim_assist: false,
+            theme: PACK_THEME.into(),
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Loads settings from `path`. Anything missing or malformed keeps its
+    /// default, with a warning for the malformed parts.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let mut settings = Settings::new(path);
+        let contents = match fs::read_to_string(&settings.path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return settings,
+            Err(err) => {
+                eprintln!("Warning: cannot read {}: {}", settings.path.display(), err);
+                return settings;
+            },
+        };
+        let body = match read_versioned(&contents, MAGIC) {
+            Ok((version, body)) => {
+                if version > VERSION {
+                    eprintln!("Warning: {} is from a newer version, reading what we can", settings.path.display());
+                }
+                body
+            },
+            Err(err) => {
+                eprintln!("Warning: ignoring {}: {}", settings.path.display(), err);
+                return settings;
+            },
+        };
+        for (n, line) in body.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(err) = settings.apply_line(line) {
+                eprintln!("Warning: {} line {}: {}, using the default", settings.path.display(), n + 2, err);
+            }
+        }
+        settings
+    }
+
+    fn apply_line(&mut self, line: &str) -> Result<(), String> {
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().ok_or_else(|| format!("expected 'key = value', got '{}'", line))?.trim();
+        let invalid = || format!("invalid value '{}' for '{}'", value, key);
+        if key.starts_with("key.") {
+            let control = CONTROLS.iter().cloned().find(|control| control.name() == &key[4..])
+                .ok_or_else(|| format!("unknown control '{}'", &key[4..]))?;
+            let mut keys = Vec::new();
+            for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                keys.push(parse_key(name).ok_or_else(|| format!("unknown key '{}'", name))?);
+            }
+            if keys.is_empty() {
+                return Err(invalid());
+            }
+            keys.truncate(MAX_KEYS_PER_CONTROL);
+            *self.keys_mut(control) = keys;
+            return Ok(());
+        }
+        match key {
+            "mouse_sensitivity" => self.mouse_sensitivity = parse_f32_in(value, 0.05, 10.).ok_or_else(invalid)?,
+            "paddle_acceleration" => self.paddle_acceleration = parse_f32_in(value, 1., 1000.).ok_or_else(invalid)?,
+            "fullscreen" => self.fullscreen = parse_bool(value).ok_or_else(invalid)?,
+            "vsync" => self.vsync = parse_bool(value).ok_or_else(invalid)?,
+            "volume" => self.volume = parse_f32_in(value, 0., 1.).ok_or_else(invalid)?,
+            "music_volume" => self.music_volume = parse_f32_in(value, 0., 1.).ok_or_else(invalid)?,
+            "reduced_motion" => self.reduced_motion = parse_bool(value).ok_or_else(invalid)?,
+            "screen_shake" => self.screen_shake = parse_f32_in(value, 0., 1.).ok_or_else(invalid)?,
+            "hit_stop" => self.hit_stop = parse_f32_in(value, 0., 1.).ok_or_else(invalid)?,
+            "ball_speed_up" => self.ball_speed_up = parse_f32_in(value, 0., 1.).ok_or_else(invalid)?,
+            "game_speed" => self.game_speed = parse_f32_in(value, 0.25, 1.).ok_or_else(invalid)?,
+            "aim_assist" => self.aim_assist = parse_bool(value).ok_or_else(invalid)?,
+            "theme" if !value.is_empty() => self.theme = value.into(),
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut lines = vec![format!("{} {}", MAGIC, VERSION)];
+        for &(control, ref keys) in self.bindings.iter() {
+            let names = keys.iter().map(|&keycode| key_name(keycode)).collect::<Vec<_>>();
+            lines.push(format!("key.{} = {}", control.name(), names.join(", ")));
+        }
+        for &option in OPTIONS.iter().chain(ACCESSIBILITY_OPTIONS.iter()) {
+            lines.push(format!("{} = {}", option.name(), self.value(option)));
+        }
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        write_atomic(&self.path, contents.as_bytes())
+    }
+
+    /// Whether scores made with these settings are marked as assisted. Less
+    /// shake and hit-stop only change how the game feels, so they do not
+    /// count.
+    pub fn is_assisted(&self) -> bool {
+        self.game_speed < 1. || self.ball_speed_up < 1. || self.aim_assist
+    }
+
+    pub fn keys(&self, control: Control) -> &[VirtualKeyCode] {
+        self.bindings.iter()
+            .find(|&&(c, _)| c == control)
+            .map(|(_, keys)| &keys[..])
+            .unwrap_or(&[])
+    }
+
+    fn keys_mut(&mut self, control: Control) -> &mut Vec<VirtualKeyCode> {
+        let i = self.bindings.iter().position(|&(c, _)| c == control).expect("Every control has a binding");
+        &mut self.bindings[i].1
+    }
+
+    pub fn is_bound(&self, control: Control, keycode: VirtualKeyCode) -> bool {
+        self.keys(control).contains(&keycode)
+    }
+
+    /// Adds `keycode` to a control's keys, or removes it if already bound.
+    /// The oldest key is dropped once the control has too many.
+    pub fn toggle_key(&mut self, control: Control, keycode: VirtualKeyCode) {
+        let keys = self.keys_mut(control);
+        if let Some(i) = keys.iter().position(|&k| k == keycode) {
+            if keys.len() > 1 {
+                keys.remove(i);
+            }
+            return;
+        }
+        keys.push(keycode);
+        if keys.len() > MAX_KEYS_PER_CONTROL {
+            keys.remove(0);
+        }
+    }
+
+    pub fn reset_keys(&mut self) {
+        for &mut (control, ref mut keys) in self.bindings.iter_mut() {
+            *keys = control.default_keys();
+        }
+    }
+
+    pub fn value(&self, option: SettingOption) -> String {
+        match option {
+            SettingOption::MouseSensitivity => format!("{}", self.mouse_sensitivity),
+            SettingOption::PaddleAcceleration => format!("{}", self.paddle_acceleration),
+            SettingOption::Fullscreen => format!("{}", self.fullscreen),
+            SettingOption::Vsync => format!("{}", self.vsync),
+            SettingOption::Volume => format!("{}", self.volume),
+            SettingOption::MusicVolume => format!("{}", self.music_volume),
+            SettingOption::ReducedMotion => format!("{}", self.reduced_motion),
+            SettingOption::ScreenShake => format!("{}", self.screen_shake),
+            SettingOption::HitStop => format!("{}", self.hit_stop),
+            SettingOption::BallSpeedUp => format!("{}", self.ball_speed_up),
+            SettingOption::GameSpeed => format!("{}", self.game_speed),
+            SettingOption::AimAssist => format!("{}", self.aim_assist),
+            SettingOption::Theme => self.theme.clone(),
+        }
+    }
+
+    pub fn label(&self, option: SettingOption) -> String {
+        let on_off = |value: bool| if value { "On" } else { "Off" };
+        match option {
+            SettingOption::MouseSensitivity => format!("Mouse sensitivity: {}", self.mouse_sensitivity),
+            SettingOption::PaddleAcceleration => format!("Paddle acceleration: {}", self.paddle_acceleration),
+            SettingOption::Fullscreen => format!("Fullscreen: {}", on_off(self.fullscreen)),
+            SettingOption::Vsync => format!("Vsync: {} (applies on restart)", on_off(self.vsync)),
+            SettingOption::Volume => format!("Volume: {}%", (self.volume * 100.).round()),
+            SettingOption::MusicVolume => format!("Music volume: {}%", (self.music_volume * 100.).round()),
+            SettingOption::ReducedMotion => format!("Reduced motion: {}", on_off(self.reduced_motion)),
+            SettingOption::ScreenShake => format!("Screen shake: {}", percent(self.screen_shake)),
+            SettingOption::HitStop => format!("Hit-stop: {}", percent(self.hit_stop)),
+            SettingOption::BallSpeedUp => format!("Ball speed-up: {}", percent(self.ball_speed_up)),
+            SettingOption::GameSpeed => format!("Game speed: {}", percent(self.game_speed)),
+            SettingOption::AimAssist => format!("Aim assist: {}", on_off(self.aim_assist)),
+            SettingOption::Theme if self.theme == PACK_THEME => "Theme: Level pack's".into(),
+            SettingOption::Theme => format!("Theme: {}", self.theme),
+        }
+    }
+
+    /// Moves an option on to its next value, wrapping around at the end.
+    pub fn cycle(&mut self, option: SettingOption) {
+        match option {
+            SettingOption::MouseSensitivity => {
+                self.mouse_sensitivity = next_step(MOUSE_SENSITIVITY_STEPS, self.mouse_sensitivity);
+            },
+            SettingOption::PaddleAcceleration => {
+                self.paddle_acceleration = next_step(PADDLE_ACCELERATION_STEPS, self.paddle_acceleration);
+            },
+            SettingOption::Fullscreen => self.fullscreen = !self.fullscreen,
+            SettingOption::Vsync => self.vsync = !self.vsync,
+            SettingOption::Volume => self.volume = next_volume(self.volume),
+            SettingOption::MusicVolume => self.music_volume = next_volume(self.music_volume),
+            SettingOption::ReducedMotion => self.reduced_motion = !self.reduced_motion,
+            SettingOption::ScreenShake => self.screen_shake = next_step(STRENGTH_STEPS, self.screen_shake),
+            SettingOption::HitStop => self.hit_stop = next_step(STRENGTH_STEPS, self.hit_stop),
+            SettingOption::BallSpeedUp => self.ball_speed_up = next_step(STRENGTH_STEPS, self.ball_speed_up),
+            SettingOption::GameSpeed => self.game_speed = next_step(GAME_SPEED_STEPS, self.game_speed),
+            SettingOption::AimAssist => self.aim_assist = !self.aim_assist,
+            SettingOption::Theme => {
+                let mut names = theme_names();
+                names.insert(0, PACK_THEME.into());
+                let i = names.iter().position(|name| *name == self.theme).map_or(0, |i| i + 1);
+                self.theme = names[i % names.len()].clone();
+            },
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+/// Addresses starting with this are Unix socket paths, anything else is a
+/// TCP `host:port`.
+const UNIX_PREFIX: &str = "unix:";
+/// Ends each frame, so a spectator joining mid-stream knows where the next
+/// whole one starts.
+pub const FRAME_END: &str = "end";
+/// Bytes a spectator may fall behind by before it is dropped.
+const MAX_BACKLOG: usize = 64 * 1024;
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+struct Spectator {
+    stream: Box<dyn Write>,
+    /// Bytes of a frame that did not fit in the socket buffer yet.
+    backlog: Vec<u8>,
+}
+
+/// Sends the game as text frames to anyone who connects. Frames are plain
+/// lines, one thing per line, so overlays can pick out what they need.
+pub struct SpectatorServer {
+    listener: Listener,
+    spectators: Vec<Spectator>,
+}
+
+/// The watching end of a `SpectatorServer`.
+pub struct SpectatorClient {
+    stream: Box<dyn Read>,
+    received: Vec<u8>,
+    pub addr: String,
+    pub is_connected: bool,
+}
+
+fn set_up_tcp(stream: &TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_nonblocking(true)
+}
+
+impl SpectatorServer {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = if let Some(path) = addr.strip_prefix(UNIX_PREFIX) {
+            // A socket left behind by an earlier run would block the bind.
+            let _ = ::std::fs::remove_file(path);
+            let listener = UnixListener::bind(path)?;
+            listener.set_nonblocking(true)?;
+            Listener::Unix(listener)
+        } else {
+            let listener = TcpListener::bind(addr)?;
+            listener.set_nonblocking(true)?;
+            Listener::Tcp(listener)
+        };
+        Ok(SpectatorServer { listener, spectators: Vec::new() })
+    }
+
+    /// Takes on spectators that connected since the last call.
+    pub fn accept(&mut self) {
+        loop {
+            let stream: io::Result<Box<dyn Write>> = match self.listener {
+                Listener::Tcp(ref listener) => listener.accept()
+                    .and_then(|(stream, _)| set_up_tcp(&stream).map(|_| Box::new(stream) as Box<dyn Write>)),
+                Listener::Unix(ref listener) => listener.accept()
+                    .and_then(|(stream, _)| stream.set_nonblocking(true).map(|_| Box::new(stream) as Box<dyn Write>)),
+            };
+            match stream {
+                Ok(stream) => self.spectators.push(Spectator { stream, backlog: Vec::new() }),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    eprintln!("Warning: cannot accept spectator: {}", err);
+                    break;
+                },
+            }
+        }
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Sends `frame` to every spectator, dropping any that went away or fell
+    /// too far behind.
+    pub fn broadcast(&mut self, frame: &str) {
+        let bytes = format!("{}{}\n", frame, FRAME_END).into_bytes();
+        self.spectators.retain(|spectator| spectator.backlog.len() < MAX_BACKLOG);
+        for spectator in self.spectators.iter_mut() {
+            spectator.backlog.extend_from_slice(&bytes);
+        }
+        let mut i = 0;
+        while i < self.spectators.len() {
+            if flush(&mut self.spectators[i]).is_err() {
+                self.spectators.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Writes as much of the backlog as the socket takes without blocking.
+fn flush(spectator: &mut Spectator) -> io::Result<()> {
+    while !spectator.backlog.is_empty() {
+        match spectator.stream.write(&spectator.backlog) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                spectator.backlog.drain(..n);
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+impl SpectatorClient {
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        let stream: Box<dyn Read> = if let Some(path) = addr.strip_prefix(UNIX_PREFIX) {
+            let stream = UnixStream::connect(path)?;
+            stream.set_nonblocking(true)?;
+            Box::new(stream)
+        } else {
+            let stream = TcpStream::connect(addr)?;
+            set_up_tcp(&stream)?;
+            Box::new(stream)
+        };
+        Ok(SpectatorClient { stream, received: Vec::new(), addr: addr.into(), is_connected: true })
+    }
+
+    /// The newest whole frame received since the last call, if any. Older
+    /// ones are skipped, only the latest matters for watching.
+    pub fn poll(&mut self) -> io::Result<Option<String>> {
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.is_connected = false;
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the game closed the connection"));
+                },
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.is_connected = false;
+                    return Err(err);
+                },
+            }
+        }
+        let marker = format!("\n{}\n", FRAME_END);
+        let marker = marker.as_bytes();
+        let end = match self.received.windows(marker.len()).rposition(|window| window == marker) {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let frame: Vec<u8> = self.received.drain(..end + marker.len()).collect();
+        // Everything up to the previous end marker is an older frame.
+        let start = frame[..end + 1].windows(marker.len())
+            .rposition(|window| window == marker)
+            .map_or(0, |start| start + marker.len());
+        Ok(Some(String::from_utf8_lossy(&frame[start..end + 1]).into_owned()))
+    }
+}
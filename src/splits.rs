@@ -0,0 +1,169 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use storage::{read_records, write_atomic};
+
+const MAGIC: &str = "breakout-splits";
+const VERSION: u32 = 1;
+
+/// Simulation steps per second.
+pub const TICK_RATE: u32 = 60;
+
+/// Timing of the run in progress, in simulation ticks so a run takes the
+/// same number of ticks however fast frames are drawn. Ticks only count while
+/// a level is being played, so pause menus and the screens between levels are
+/// left out. The hit-stop `delay` after a bounce does count: it is part of
+/// playing the level, the same length for everyone, and being measured in
+/// ticks it does not depend on frame rate either.
+#[derive(Clone, Debug, Default)]
+pub struct RunTimer {
+    /// Ticks taken by each finished level.
+    pub splits: Vec<u32>,
+    /// Ticks so far in the current level.
+    pub level_ticks: u32,
+}
+
+/// The fastest complete run for each level pack.
+pub struct PersonalBests {
+    path: PathBuf,
+    runs: Vec<(String, Vec<u32>)>,
+    read_only: bool,
+}
+
+/// Formats ticks as `m:ss.cc`.
+pub fn format_ticks(ticks: u32) -> String {
+    let hundredths = ticks as u64 * 100 / TICK_RATE as u64;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
+
+/// Formats the difference from a personal best, `-` when ahead of it.
+pub fn format_delta(ticks: u32, best: u32) -> String {
+    if ticks < best {
+        format!("-{}", format_ticks(best - ticks))
+    } else {
+        format!("+{}", format_ticks(ticks - best))
+    }
+}
+
+fn parse_run(line: &str) -> Option<(String, Vec<u32>)> {
+    let mut fields = line.split('\t');
+    let pack = fields.next()?;
+    let splits = fields.next()?
+        .split_whitespace()
+        .map(|ticks| ticks.parse().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    if fields.next().is_some() || splits.is_empty() {
+        return None;
+    }
+    Some((pack.into(), splits))
+}
+
+impl RunTimer {
+    pub fn total(&self) -> u32 {
+        self.splits.iter().sum::<u32>() + self.level_ticks
+    }
+
+    /// Ends the current level's segment.
+    pub fn split(&mut self) {
+        self.splits.push(self.level_ticks);
+        self.level_ticks = 0;
+    }
+}
+
+impl PersonalBests {
+    /// Loads bests from `path`, with the same rules as the high score file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let (runs, read_only) = read_records(&path, MAGIC, VERSION, "personal best", parse_run);
+        PersonalBests { path, runs, read_only }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let mut contents = format!("{} {}\n", MAGIC, VERSION);
+        for &(ref pack, ref splits) in self.runs.iter() {
+            let splits = splits.iter().map(|ticks| ticks.to_string()).collect::<Vec<_>>();
+            contents.push_str(&format!("{}\t{}\n", pack, splits.join(" ")));
+        }
+        write_atomic(&self.path, contents.as_bytes())
+    }
+
+    /// Splits of the best complete run of `pack`.
+    pub fn best(&self, pack: &str) -> Option<&[u32]> {
+        self.runs.iter().find(|&&(ref p, _)| p == pack).map(|&(_, ref splits)| &splits[..])
+    }
+
+    /// Keeps `splits` if they beat the best run of the same length. Returns
+    /// whether they did.
+    pub fn record(&mut self, pack: &str, splits: &[u32]) -> bool {
+        let total: u32 = splits.iter().sum();
+        match self.runs.iter().position(|&(ref p, _)| p == pack) {
+            Some(i) => {
+                let best = &self.runs[i].1;
+                if best.len() == splits.len() && best.iter().sum::<u32>() <= total {
+                    return false;
+                }
+                self.runs[i].1 = splits.to_vec();
+            },
+            None => self.runs.push((pack.into(), splits.to_vec())),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("breakout-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn formats_ticks_and_deltas() {
+        assert_eq!(format_ticks(0), "0:00.00");
+        assert_eq!(format_ticks(TICK_RATE * 61 + TICK_RATE / 2), "1:01.50");
+        assert_eq!(format_ticks(1), "0:00.01");
+        assert_eq!(format_delta(TICK_RATE, TICK_RATE * 3), "-0:02.00");
+        assert_eq!(format_delta(TICK_RATE * 3, TICK_RATE), "+0:02.00");
+        assert_eq!(format_delta(TICK_RATE, TICK_RATE), "+0:00.00");
+    }
+
+    #[test]
+    fn records_only_faster_runs() {
+        let path = temp_path("splits.txt");
+        let mut bests = PersonalBests::load(&path);
+        assert!(bests.record("default", &[300, 400]));
+        assert_eq!(bests.best("default"), Some(&[300, 400][..]));
+        assert!(!bests.record("default", &[350, 400]));
+        assert!(!bests.record("default", &[400, 300]));
+        assert!(bests.record("default", &[200, 450]));
+        assert!(bests.record("extra", &[900]));
+        assert_eq!(bests.best("default"), Some(&[200, 450][..]));
+
+        bests.save().unwrap();
+        let loaded = PersonalBests::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.best("default"), Some(&[200, 450][..]));
+        assert_eq!(loaded.best("extra"), Some(&[900][..]));
+        assert_eq!(loaded.best("missing"), None);
+    }
+
+    #[test]
+    fn newer_files_are_left_alone() {
+        let path = temp_path("newer-splits.txt");
+        fs::write(&path, format!("{} {}\ndefault\t100\n", MAGIC, VERSION + 1)).unwrap();
+        let mut bests = PersonalBests::load(&path);
+        assert_eq!(bests.best("default"), None);
+        bests.record("default", &[50]);
+        bests.save().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(contents.ends_with("default\t100\n"));
+    }
+}
@@ -0,0 +1,269 @@
+use gl;
+use gl::types::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use math::Vec2;
+use png::{read_png, Image};
+
+/// Where sprite images live, each with an optional `<name>.sheet` beside it.
+pub const SPRITES_DIR: &str = "res/sprites";
+/// Side of an atlas page in pixels.
+const PAGE_SIZE: usize = 1024;
+/// Space left between images on a page, so none bleeds into its neighbor.
+const PADDING: usize = 1;
+
+/// Margins of a 9-slice sprite in pixels. Corners keep their size when the
+/// sprite is stretched, edges stretch along their side and the middle both
+/// ways.
+#[derive(Clone, Copy, Debug)]
+pub struct Slice {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// A picture on an atlas page. `uv_min` is its bottom left corner as drawn
+/// and `uv_max` its top right.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub texture: GLuint,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    /// Size in pixels.
+    pub w: f32,
+    pub h: f32,
+    pub slice: Option<Slice>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteHandle(u32);
+
+/// A part of an image named by its sheet, in pixels from the image's top
+/// left.
+struct SheetEntry {
+    name: String,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    slice: Option<Slice>,
+}
+
+/// A page being filled, shelf by shelf from the top.
+struct Page {
+    pixels: Vec<u8>,
+    x: usize,
+    y: usize,
+    shelf_h: usize,
+}
+
+/// Every sprite in `SPRITES_DIR`, packed onto as few textures as they fit
+/// so a batch needs one draw call per page.
+///
+/// Each PNG is one sprite named after the file, unless a sheet next to it
+/// cuts it up. Sheets are `name = x y w h` lines in pixels from the image's
+/// top left, `#` at the start of a line is a comment:
+///
+///     paddle = 0 0 64 16 slice 8 8 4 4
+///     ball = 64 0 16 16 frames 4
+///
+/// `slice left right top bottom` makes a 9-slice sprite. `frames n` is a
+/// row of `n` frames of that size going right, named `ball.0` to `ball.3`.
+pub struct SpriteAtlas {
+    sprites: Vec<Sprite>,
+    names: HashMap<String, SpriteHandle>,
+}
+
+fn parse_sheet_line(line: &str) -> Result<Vec<SheetEntry>, String> {
+    let mut parts = line.splitn(2, '=');
+    let name = parts.next().unwrap_or("").trim();
+    let value = parts.next().ok_or_else(|| format!("expected 'name = x y w h', got '{}'", line))?;
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    let numbers = |tokens: &[&str]| -> Result<Vec<usize>, String> {
+        tokens.iter().map(|t| t.parse().map_err(|_| format!("invalid number '{}' for '{}'", t, name))).collect()
+    };
+    if name.is_empty() || tokens.len() < 4 {
+        return Err(format!("expected 'name = x y w h', got '{}'", line));
+    }
+    let rect = numbers(&tokens[..4])?;
+    let mut slice = None;
+    let mut frames = None;
+    let mut rest = &tokens[4..];
+    while let Some((&key, tail)) = rest.split_first() {
+        match key {
+            "slice" if tail.len() >= 4 => {
+                let m = numbers(&tail[..4])?;
+                slice = Some(Slice { left: m[0] as f32, right: m[1] as f32, top: m[2] as f32, bottom: m[3] as f32 });
+                rest = &tail[4..];
+            },
+            "frames" if !tail.is_empty() => {
+                frames = Some(numbers(&tail[..1])?[0]);
+                rest = &tail[1..];
+            },
+            _ => return Err(format!("unexpected '{}' for '{}'", key, name)),
+        }
+    }
+    let entry = |name: String, i: usize| SheetEntry { name, x: rect[0] + i * rect[2], y: rect[1], w: rect[2], h: rect[3], slice };
+    Ok(match frames {
+        Some(n) => (0..n).map(|i| entry(format!("{}.{}", name, i), i)).collect(),
+        None => vec![entry(name.into(), 0)],
+    })
+}
+
+/// The parts of an image its sheet names, or the whole image as `name` if
+/// it has no sheet. Bad lines are skipped with a warning.
+fn read_sheet(path: &Path, name: &str, image: &Image) -> Vec<SheetEntry> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return vec![SheetEntry { name: name.into(), x: 0, y: 0, w: image.w, h: image.h, slice: None }],
+    };
+    let mut entries = Vec::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_sheet_line(line) {
+            Ok(parsed) => {
+                for entry in parsed {
+                    if entry.x + entry.w > image.w || entry.y + entry.h > image.h {
+                        eprintln!("Warning: {} line {}: '{}' is outside the image", path.display(), n + 1, entry.name);
+                    } else {
+                        entries.push(entry);
+                    }
+                }
+            },
+            Err(err) => eprintln!("Warning: {} line {}: {}", path.display(), n + 1, err),
+        }
+    }
+    entries
+}
+
+impl Page {
+    fn new() -> Self {
+        Page { pixels: vec![0; PAGE_SIZE * PAGE_SIZE * 4], x: 0, y: 0, shelf_h: 0 }
+    }
+
+    /// Copies `image` in, returning where its top left went, or none if the
+    /// page is full.
+    fn place(&mut self, image: &Image) -> Option<(usize, usize)> {
+        if self.x + image.w > PAGE_SIZE {
+            self.x = 0;
+            self.y += self.shelf_h + PADDING;
+            self.shelf_h = 0;
+        }
+        if self.x + image.w > PAGE_SIZE || self.y + image.h > PAGE_SIZE {
+            return None;
+        }
+        let (x, y) = (self.x, self.y);
+        for row in 0..image.h {
+            let from = row * image.w * 4;
+            let to = ((y + row) * PAGE_SIZE + x) * 4;
+            self.pixels[to..to + image.w * 4].copy_from_slice(&image.pixels[from..from + image.w * 4]);
+        }
+        self.x += image.w + PADDING;
+        self.shelf_h = self.shelf_h.max(image.h);
+        Some((x, y))
+    }
+
+    /// Uploads the page. Its top row goes in first, so `v` runs down it.
+    fn upload(&self) -> GLuint { unsafe {
+        let mut tex = 0;
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA as _,
+            PAGE_SIZE as _,
+            PAGE_SIZE as _,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            self.pixels.as_ptr() as _
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as _);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as _);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+        tex
+    }}
+}
+
+impl SpriteAtlas {
+    /// Packs every PNG in `dir`. A missing directory is an empty atlas,
+    /// images that cannot be read are skipped with a warning.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Self {
+        let mut atlas = SpriteAtlas { sprites: Vec::new(), names: HashMap::new() };
+        let mut paths: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map_or(false, |ext| ext == "png"))
+                .collect(),
+            Err(_) => return atlas,
+        };
+        paths.sort();
+        let mut images = Vec::new();
+        for path in paths {
+            let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("").to_string();
+            match read_png(&path) {
+                Ok(ref image) if image.w + PADDING > PAGE_SIZE || image.h + PADDING > PAGE_SIZE => {
+                    eprintln!("Warning: {} is too big for a {}px atlas page", path.display(), PAGE_SIZE);
+                },
+                Ok(image) => {
+                    let entries = read_sheet(&path.with_extension("sheet"), &name, &image);
+                    images.push((image, entries));
+                },
+                Err(err) => eprintln!("Warning: cannot load sprite {}: {}", path.display(), err),
+            }
+        }
+        // Tallest first packs the shelves tighter.
+        images.sort_by(|a, b| b.0.h.cmp(&a.0.h));
+        let mut pages = vec![Page::new()];
+        let mut placed = Vec::new();
+        for (image, entries) in images {
+            let spot = match pages.last_mut().and_then(|page| page.place(&image)) {
+                Some(spot) => spot,
+                None => {
+                    pages.push(Page::new());
+                    pages.last_mut().and_then(|page| page.place(&image)).expect("Images fit on an empty page")
+                },
+            };
+            placed.push((pages.len() - 1, spot, entries));
+        }
+        if placed.is_empty() {
+            return atlas;
+        }
+        let textures: Vec<GLuint> = pages.iter().map(Page::upload).collect();
+        let size = PAGE_SIZE as f32;
+        for (page, (x, y), entries) in placed {
+            for entry in entries {
+                let (left, top) = ((x + entry.x) as f32, (y + entry.y) as f32);
+                let (w, h) = (entry.w as f32, entry.h as f32);
+                atlas.names.insert(entry.name, SpriteHandle(atlas.sprites.len() as _));
+                atlas.sprites.push(Sprite {
+                    texture: textures[page],
+                    uv_min: Vec2::new(left / size, (top + h) / size),
+                    uv_max: Vec2::new((left + w) / size, top / size),
+                    w,
+                    h,
+                    slice: entry.slice,
+                });
+            }
+        }
+        atlas
+    }
+
+    pub fn find(&self, name: &str) -> Option<SpriteHandle> {
+        self.names.get(name).cloned()
+    }
+
+    pub fn get(&self, handle: SpriteHandle) -> &Sprite {
+        let SpriteHandle(index) = handle;
+        &self.sprites[index as usize]
+    }
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "breakout";
+
+/// Per-user directory for persistent game data. `BREAKOUT_DATA_DIR` overrides
+/// the platform default.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("BREAKOUT_DATA_DIR") {
+        return dir.into();
+    }
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    };
+    base.unwrap_or_else(|| PathBuf::from(".")).join(APP_DIR)
+}
+
+/// Replaces the file at `path` so readers see either the old or the new
+/// contents, never a partial write.
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut tmp_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// Splits a versioned file into its version and body. The first line must
+/// read `<magic> <version>`.
+pub fn read_versioned<'a>(contents: &'a str, magic: &str) -> io::Result<(u32, &'a str)> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let (header, body) = match contents.find('\n') {
+        Some(i) => (&contents[..i], &contents[i + 1..]),
+        None => (contents, ""),
+    };
+    let mut parts = header.split_whitespace();
+    if parts.next() != Some(magic) {
+        return Err(invalid(format!("Missing '{}' header", magic)));
+    }
+    let version = parts.next()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| invalid(format!("Invalid '{}' version", magic)))?;
+    Ok((version, body))
+}
+
+/// Reads the one-per-line records of the versioned file at `path`, skipping
+/// lines `parse` rejects. `what` names a record in warnings. A missing file
+/// has no records. Also returns whether the file must be left alone: it could
+/// not be read, or a newer version wrote it.
+pub fn read_records<T, F>(path: &Path, magic: &str, version: u32, what: &str, parse: F) -> (Vec<T>, bool)
+where
+    F: Fn(&str) -> Option<T>,
+{
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return (Vec::new(), false),
+        Err(err) => {
+            eprintln!("Warning: cannot read {}: {}", path.display(), err);
+            return (Vec::new(), true);
+        },
+    };
+    match read_versioned(&contents, magic) {
+        Ok((file_version, body)) if file_version <= version => {
+            let mut records = Vec::new();
+            for line in body.lines().filter(|line| !line.is_empty()) {
+                match parse(line) {
+                    Some(record) => records.push(record),
+                    None => eprintln!("Warning: skipping bad {} '{}'", what, line),
+                }
+            }
+            (records, false)
+        },
+        Ok((file_version, _)) => {
+            eprintln!("Warning: {} has unsupported version {}, it will not be saved", path.display(), file_version);
+            (Vec::new(), true)
+        },
+        Err(err) => {
+            eprintln!("Warning: ignoring {}: {}", path.display(), err);
+            (Vec::new(), false)
+        },
+    }
+}
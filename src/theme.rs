@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::Path;
+
+use renderer::Color;
+
+/// Where theme files live, as `<name>.theme`.
+pub const THEMES_DIR: &str = "res/themes";
+/// Theme setting that leaves the choice to the level pack.
+pub const PACK_THEME: &str = "pack";
+pub const DEFAULT_THEME: &str = "classic";
+const BUILTIN_THEMES: &[&str] = &[DEFAULT_THEME, "high-contrast", "colorblind"];
+
+/// The palette the game is drawn with. Theme files are `key = value` lines,
+/// `#` at the start of a line is a comment and colors are `#rrggbb` or
+/// `#rrggbbaa`:
+///
+///     base = classic
+///     background = #101018
+///     block.tough = #ff8000
+///
+/// `base` is the built-in theme the rest is changed from, classic if it is
+/// left out.
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub background: Color,
+    pub playfield: Color,
+    pub paddle: Color,
+    pub ball: Color,
+    /// Score, lives and the rest of the text kept up during play.
+    pub hud: Color,
+    /// Prompts and help, like "Press Enter to continue...".
+    pub prompt: Color,
+    /// Big headings, like the title and "G A M E   O V E R".
+    pub title: Color,
+    /// What the title pulses up to on the title screen.
+    pub title_glow: Color,
+    /// Blocks that break in one hit.
+    pub block: Color,
+    /// Blocks that take several hits, darker the more they take.
+    pub tough_block: Color,
+    pub indestructible_block: Color,
+    pub moving_block: Color,
+}
+
+fn rgb(hex: u32) -> Color {
+    let channel = |shift: u32| ((hex >> shift) & 0xff) as f32 / 255.;
+    Color::new(channel(16), channel(8), channel(0), 1.)
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`.
+pub fn parse_color(value: &str) -> Option<Color> {
+    if !value.starts_with('#') || !(value.len() == 7 || value.len() == 9) {
+        return None;
+    }
+    let hex = u32::from_str_radix(&value[1..], 16).ok()?;
+    if value.len() == 7 {
+        return Some(rgb(hex));
+    }
+    let color = rgb(hex >> 8);
+    Some(Color::new(color.r, color.g, color.b, (hex & 0xff) as f32 / 255.))
+}
+
+/// Every theme there is: the built-in ones, then the files in `THEMES_DIR`
+/// by name.
+pub fn theme_names() -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(THEMES_DIR)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "theme"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(String::from))
+            .filter(|name| !BUILTIN_THEMES.contains(&name.as_str()))
+            .collect())
+        .unwrap_or_default();
+    files.sort();
+    BUILTIN_THEMES.iter().map(|&name| name.to_string()).chain(files).collect()
+}
+
+impl Theme {
+    /// The game's original colors.
+    pub fn classic() -> Self {
+        Theme {
+            background: Color::new(0.1, 0.1, 0.1, 1.),
+            playfield: Color::new(0.01, 0.01, 0.01, 1.),
+            paddle: Color::new(1., 0., 0., 1.),
+            ball: Color::new(0., 0., 1., 1.),
+            hud: Color::new(0., 1., 0., 1.),
+            prompt: Color::new(0., 0., 1., 1.),
+            title: Color::new(1., 0., 0., 1.),
+            title_glow: Color::new(1., 0.45, 0.35, 1.),
+            block: Color::new(1., 1., 0., 1.),
+            tough_block: Color::new(1., 0.5, 0., 1.),
+            indestructible_block: Color::new(0.5, 0.5, 0.5, 1.),
+            moving_block: Color::new(1., 1., 0., 1.),
+        }
+    }
+
+    /// Bright colors on black, far apart in brightness as well as hue.
+    pub fn high_contrast() -> Self {
+        Theme {
+            background: rgb(0x303030),
+            playfield: rgb(0x000000),
+            paddle: rgb(0xffffff),
+            ball: rgb(0x00ffff),
+            hud: rgb(0xffffff),
+            prompt: rgb(0x00ffff),
+            title: rgb(0xff4040),
+            title_glow: rgb(0xffc0c0),
+            block: rgb(0xffff00),
+            tough_block: rgb(0xff40ff),
+            indestructible_block: rgb(0xb0b0b0),
+            moving_block: rgb(0x40ff40),
+        }
+    }
+
+    /// Okabe and Ito's palette, which stays distinct with the common kinds
+    /// of color blindness.
+    pub fn colorblind() -> Self {
+        Theme {
+            background: rgb(0x1a1a1a),
+            playfield: rgb(0x000000),
+            paddle: rgb(0xe69f00),
+            ball: rgb(0x56b4e9),
+            hud: rgb(0xffffff),
+            prompt: rgb(0x56b4e9),
+            title: rgb(0xd55e00),
+            title_glow: rgb(0xe69f00),
+            block: rgb(0xf0e442),
+            tough_block: rgb(0xcc79a7),
+            indestructible_block: rgb(0x999999),
+            moving_block: rgb(0x009e73),
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            DEFAULT_THEME => Some(Theme::classic()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            "colorblind" => Some(Theme::colorblind()),
+            _ => None,
+        }
+    }
+
+    /// The built-in theme or theme file called `name`. A theme that cannot
+    /// be loaded is classic, with a warning. Bad lines in a file are skipped
+    /// with a warning.
+    pub fn load(name: &str) -> Self {
+        if let Some(theme) = Theme::builtin(name) {
+            return theme;
+        }
+        let path = Path::new(THEMES_DIR).join(format!("{}.theme", name));
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("Warning: cannot load theme {}: {}, using {}", path.display(), err, DEFAULT_THEME);
+                return Theme::classic();
+            },
+        };
+        let lines: Vec<(usize, &str)> = contents.lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        // The base goes first, wherever it is in the file.
+        let base = lines.iter().filter_map(|&(_, line)| key_value(line)).find(|&(key, _)| key == "base");
+        let mut theme = match base {
+            Some((_, name)) => Theme::builtin(name).unwrap_or_else(|| {
+                eprintln!("Warning: {}: unknown base theme '{}'", path.display(), name);
+                Theme::classic()
+            }),
+            None => Theme::classic(),
+        };
+        for &(n, line) in lines.iter() {
+            if let Err(err) = theme.apply_line(line) {
+                eprintln!("Warning: {} line {}: {}", path.display(), n + 1, err);
+            }
+        }
+        theme
+    }
+
+    fn apply_line(&mut self, line: &str) -> Result<(), String> {
+        let (key, value) = key_value(line).ok_or_else(|| format!("expected 'key = value', got '{}'", line))?;
+        if key == "base" {
+            return Ok(());
+        }
+        let color = parse_color(value).ok_or_else(|| format!("invalid color '{}' for '{}'", value, key))?;
+        match key {
+            "background" => self.background = color,
+            "playfield" => self.playfield = color,
+            "paddle" => self.paddle = color,
+            "ball" => self.ball = color,
+            "hud" => self.hud = color,
+            "prompt" => self.prompt = color,
+            "title" => self.title = color,
+            "title.glow" => self.title_glow = color,
+            "block" => self.block = color,
+            "block.tough" => self.tough_block = color,
+            "block.indestructible" => self.indestructible_block = color,
+            "block.moving" => self.moving_block = color,
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.splitn(2, '=');
+    let key = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    Some((key, value))
+}
@@ -0,0 +1,257 @@
+use std::f32::consts::PI;
+
+use math::Vec2;
+use renderer::Color;
+
+/// Values a tween can move between.
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+/// How a tween's progress maps onto the way between its ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    Linear,
+    InQuad,
+    OutQuad,
+    OutCubic,
+    InOutSine,
+    /// Overshoots the end a little and settles back.
+    OutBack,
+}
+
+/// A tree of tweens, each moving the property `K` through values of `T`.
+/// Times are in seconds from the start of the animation.
+#[derive(Clone, Debug)]
+pub enum Anim<K, T> {
+    Tween { key: K, from: T, to: T, duration: f32, ease: Ease },
+    Wait(f32),
+    /// One after another.
+    Sequence(Vec<Anim<K, T>>),
+    /// All at once, lasting as long as the longest.
+    Parallel(Vec<Anim<K, T>>),
+}
+
+/// An animation started at some point on a clock, like a level's
+/// simulation time.
+#[derive(Clone, Debug)]
+pub struct Timeline<K, T> {
+    anim: Anim<K, T>,
+    start: f32,
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vec2 {
+    fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        Vec2::lerp(self, other, t)
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, other: Color, t: f32) -> Color {
+        Color::lerp(self, other, t)
+    }
+}
+
+impl Ease {
+    /// Eased progress for `t` in 0..1.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match *self {
+            Ease::Linear => t,
+            Ease::InQuad => t * t,
+            Ease::OutQuad => t * (2. - t),
+            Ease::OutCubic => 1. - (1. - t).powi(3),
+            Ease::InOutSine => 0.5 - 0.5 * (PI * t).cos(),
+            Ease::OutBack => {
+                const OVERSHOOT: f32 = 1.70158;
+                let u = t - 1.;
+                1. + u * u * ((OVERSHOOT + 1.) * u + OVERSHOOT)
+            },
+        }
+    }
+}
+
+pub fn tween<K, T>(key: K, from: T, to: T, duration: f32, ease: Ease) -> Anim<K, T> {
+    Anim::Tween { key, from, to, duration, ease }
+}
+
+pub fn wait<K, T>(duration: f32) -> Anim<K, T> {
+    Anim::Wait(duration)
+}
+
+pub fn sequence<K, T>(anims: Vec<Anim<K, T>>) -> Anim<K, T> {
+    Anim::Sequence(anims)
+}
+
+pub fn parallel<K, T>(anims: Vec<Anim<K, T>>) -> Anim<K, T> {
+    Anim::Parallel(anims)
+}
+
+/// Of two tweens with their start times, the one that started last by `t`,
+/// or the earliest if neither has started.
+fn pick<'a, A>(a: Option<(f32, &'a A)>, b: Option<(f32, &'a A)>, t: f32) -> Option<(f32, &'a A)> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let is_b_later = if b.0 <= t { a.0 > t || b.0 >= a.0 } else { a.0 > t && b.0 < a.0 };
+            Some(if is_b_later { b } else { a })
+        },
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+impl<K: Copy + PartialEq, T: Lerp> Anim<K, T> {
+    pub fn duration(&self) -> f32 {
+        match *self {
+            Anim::Tween { duration, .. } | Anim::Wait(duration) => duration,
+            Anim::Sequence(ref anims) => anims.iter().map(Anim::duration).sum(),
+            Anim::Parallel(ref anims) => anims.iter().map(Anim::duration).fold(0., f32::max),
+        }
+    }
+
+    /// The tween of `key` that started last by `t`, with its start time. If
+    /// none has started yet, the first one.
+    fn current(&self, key: K, start: f32, t: f32) -> Option<(f32, &Anim<K, T>)> {
+        match *self {
+            Anim::Tween { key: k, .. } if k == key => Some((start, self)),
+            Anim::Tween { .. } | Anim::Wait(_) => None,
+            Anim::Sequence(ref anims) => {
+                let mut found = None;
+                let mut at = start;
+                for anim in anims {
+                    if at > t && found.is_some() {
+                        break;
+                    }
+                    found = pick(found, anim.current(key, at, t), t);
+                    at += anim.duration();
+                }
+                found
+            },
+            Anim::Parallel(ref anims) => anims.iter()
+                .fold(None, |found, anim| pick(found, anim.current(key, start, t), t)),
+        }
+    }
+
+    /// The value of `key` at `t`. Before its first tween it holds that
+    /// tween's start value, after its last one the end value.
+    pub fn value(&self, key: K, t: f32) -> Option<T> {
+        match self.current(key, 0., t) {
+            Some((start, &Anim::Tween { from, to, duration, ease, .. })) => {
+                let progress = if duration > 0. { (t - start) / duration } else { 1. };
+                Some(from.lerp(to, ease.apply(progress)))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl<K: Copy + PartialEq, T: Lerp> Timeline<K, T> {
+    pub fn new(anim: Anim<K, T>, start: f32) -> Self {
+        Timeline { anim, start }
+    }
+
+    pub fn value(&self, key: K, now: f32) -> Option<T> {
+        self.anim.value(key, now - self.start)
+    }
+
+    /// `value`, playing the animation over and over.
+    pub fn looped_value(&self, key: K, now: f32) -> Option<T> {
+        let duration = self.anim.duration();
+        let t = if duration > 0. { (now - self.start).max(0.) % duration } else { 0. };
+        self.anim.value(key, t)
+    }
+
+    pub fn is_done(&self, now: f32) -> bool {
+        now - self.start >= self.anim.duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Key {
+        X,
+        Y,
+    }
+
+    fn assert_value(anim: &Anim<Key, f32>, key: Key, t: f32, expected: f32) {
+        let value = anim.value(key, t).expect("key has a tween");
+        assert!((value - expected).abs() < 1e-4, "{:?} is {} at {}, expected {}", key, value, t, expected);
+    }
+
+    #[test]
+    fn pick_prefers_the_latest_started() {
+        let (a, b) = ("a", "b");
+        assert_eq!(pick(Some((0., &a)), Some((1., &b)), 0.5), Some((0., &a)));
+        assert_eq!(pick(Some((0., &a)), Some((1., &b)), 1.5), Some((1., &b)));
+        assert_eq!(pick(Some((1., &a)), Some((0., &b)), 1.5), Some((1., &a)));
+        // Neither has started, so the one that starts first.
+        assert_eq!(pick(Some((2., &a)), Some((1., &b)), 0.), Some((1., &b)));
+        assert_eq!(pick(None, Some((1., &b)), 0.), Some((1., &b)));
+        assert_eq!(pick::<&str>(None, None, 0.), None);
+    }
+
+    #[test]
+    fn sequence_hands_over_between_tweens() {
+        let anim = sequence(vec![
+            tween(Key::X, 0., 10., 1., Ease::Linear),
+            wait(1.),
+            tween(Key::X, 20., 30., 1., Ease::Linear),
+        ]);
+        assert_eq!(anim.duration(), 3.);
+        assert_value(&anim, Key::X, 0.5, 5.);
+        // The first tween holds its end value through the wait.
+        assert_value(&anim, Key::X, 1.5, 10.);
+        assert_value(&anim, Key::X, 2., 20.);
+        assert_value(&anim, Key::X, 2.5, 25.);
+    }
+
+    #[test]
+    fn parallel_groups_run_together() {
+        let anim = parallel(vec![
+            tween(Key::X, 0., 10., 2., Ease::Linear),
+            sequence(vec![wait(1.), tween(Key::Y, 0., 1., 1., Ease::Linear)]),
+            sequence(vec![wait(3.), tween(Key::X, 100., 200., 1., Ease::Linear)]),
+        ]);
+        assert_eq!(anim.duration(), 4.);
+        assert_value(&anim, Key::X, 1., 5.);
+        assert_value(&anim, Key::Y, 0.5, 0.);
+        assert_value(&anim, Key::Y, 1.5, 0.5);
+        assert_value(&anim, Key::X, 2.5, 10.);
+        assert_value(&anim, Key::X, 3.5, 150.);
+    }
+
+    #[test]
+    fn holds_the_ends_outside_its_tweens() {
+        let anim = sequence(vec![wait(1.), tween(Key::X, 3., 5., 1., Ease::OutBack)]);
+        assert_value(&anim, Key::X, -1., 3.);
+        assert_value(&anim, Key::X, 0.5, 3.);
+        assert_value(&anim, Key::X, 2., 5.);
+        assert_value(&anim, Key::X, 10., 5.);
+        assert_eq!(anim.value(Key::Y, 1.5), None);
+    }
+
+    #[test]
+    fn looped_value_repeats() {
+        let timeline = Timeline::new(sequence(vec![
+            tween(Key::X, 0., 10., 1., Ease::Linear),
+            tween(Key::X, 10., 0., 1., Ease::Linear),
+        ]), 5.);
+        let looped = |now: f32| timeline.looped_value(Key::X, now).unwrap();
+        assert!((looped(5.5) - 5.).abs() < 1e-4);
+        assert!((looped(6.75) - 2.5).abs() < 1e-4);
+        assert!((looped(8.25) - 7.5).abs() < 1e-4);
+        // Before the start it waits at the beginning.
+        assert_eq!(looped(4.), 0.);
+        assert!(!timeline.is_done(6.5));
+        assert!(timeline.is_done(7.));
+    }
+}